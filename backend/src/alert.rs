@@ -0,0 +1,448 @@
+//! Declarative threshold alerts evaluated against the JSON output of widget runs.
+//!
+//! Rules are written as `<widget id>.<field path> <operator> <value> [for <n> runs]`, e.g.
+//! `weather_widget_unique_id.temperature < 0 for 2 runs`. The field path is split on `.` and numeric
+//! parts index into arrays. The value is parsed as JSON, so numbers, booleans and quoted strings (which
+//! may contain spaces) work.
+use std::{str::FromStr, sync::Arc};
+
+use anyhow::{anyhow, bail};
+use common::{
+    alert::{AlertEvent, AlertState, AlertStatus},
+    backend::BackendRun,
+    notification::Level,
+    WidgetId,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::sync::RwLock;
+
+use crate::webhook::delivery_agent;
+
+#[derive(Debug, Deserialize, Default)]
+pub struct AlertConfig {
+    #[serde(default)]
+    pub rules: Vec<AlertRuleConfig>,
+
+    /// URLs that alert state changes are POSTed to
+    #[serde(default)]
+    pub webhooks: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct AlertRuleConfig {
+    pub name: String,
+    pub rule: String,
+    #[serde(default = "default_level")]
+    pub level: Level,
+}
+
+fn default_level() -> Level {
+    Level::Warning
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+}
+
+impl FromStr for Operator {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "<" => Operator::Less,
+            "<=" => Operator::LessOrEqual,
+            ">" => Operator::Greater,
+            ">=" => Operator::GreaterOrEqual,
+            "==" => Operator::Equal,
+            "!=" => Operator::NotEqual,
+            _ => bail!("unknown operator `{s}`"),
+        })
+    }
+}
+
+/// A parsed alert rule
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub widget: WidgetId,
+    pub path: Vec<String>,
+    pub operator: Operator,
+    pub value: Value,
+    /// Number of consecutive runs the condition has to hold before the alert fires
+    pub runs: usize,
+}
+
+/// Splits a rule on whitespace, except inside double quoted strings which are kept as one token
+/// including the quotes
+fn tokenize(s: &str) -> anyhow::Result<Vec<&str>> {
+    let mut tokens = Vec::new();
+    let mut rest = s.trim_start();
+
+    while !rest.is_empty() {
+        let end = if rest.starts_with('"') {
+            // find the closing quote, skipping escaped characters
+            let mut escaped = false;
+            let close = rest
+                .char_indices()
+                .skip(1)
+                .find(|&(_, c)| match c {
+                    _ if escaped => {
+                        escaped = false;
+                        false
+                    }
+                    '\\' => {
+                        escaped = true;
+                        false
+                    }
+                    c => c == '"',
+                })
+                .map(|(i, _)| i + 1)
+                .ok_or_else(|| anyhow!("unterminated string `{rest}`"))?;
+            if rest[close..].starts_with(|c: char| !c.is_whitespace()) {
+                bail!("expected whitespace after the string `{}`", &rest[..close]);
+            }
+            close
+        } else {
+            rest.find(char::is_whitespace).unwrap_or(rest.len())
+        };
+
+        tokens.push(&rest[..end]);
+        rest = rest[end..].trim_start();
+    }
+
+    Ok(tokens)
+}
+
+impl FromStr for Rule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(s)?;
+
+        let (target, operator, value, runs) = match tokens.as_slice() {
+            [target, operator, value] => (target, operator, value, 1),
+            [target, operator, value, "for", runs, "run" | "runs"] => (
+                target,
+                operator,
+                value,
+                runs.parse()
+                    .map_err(|_| anyhow!("invalid number of runs `{runs}`"))?,
+            ),
+            _ => bail!("expected `<widget>.<field> <operator> <value> [for <n> runs]`"),
+        };
+
+        let (widget, path) = target
+            .split_once('.')
+            .ok_or_else(|| anyhow!("expected `<widget>.<field>` but got `{target}`"))?;
+        let widget: WidgetId = serde_json::from_value(Value::String(widget.into()))?;
+
+        if runs == 0 {
+            bail!("the number of runs must be at least 1");
+        }
+
+        Ok(Rule {
+            widget,
+            path: path.split('.').map(String::from).collect(),
+            operator: operator.parse()?,
+            value: serde_json::from_str(value)
+                .map_err(|_| anyhow!("invalid value `{value}`, strings need to be quoted"))?,
+            runs,
+        })
+    }
+}
+
+impl Rule {
+    /// Extracts the value the rule looks at from the output of a run
+    pub fn extract<'v>(&self, output: &'v Value) -> Option<&'v Value> {
+        self.path
+            .iter()
+            .try_fold(output, |value, part| match value {
+                Value::Array(items) => items.get(part.parse::<usize>().ok()?),
+                _ => value.get(part),
+            })
+    }
+
+    /// Checks the condition against an extracted value, `None` if the values can not be compared
+    pub fn matches(&self, actual: &Value) -> Option<bool> {
+        match self.operator {
+            Operator::Equal => return Some(*actual == self.value),
+            Operator::NotEqual => return Some(*actual != self.value),
+            _ => {}
+        }
+
+        let (actual, expected) = (actual.as_f64()?, self.value.as_f64()?);
+        Some(match self.operator {
+            Operator::Less => actual < expected,
+            Operator::LessOrEqual => actual <= expected,
+            Operator::Greater => actual > expected,
+            Operator::GreaterOrEqual => actual >= expected,
+            Operator::Equal | Operator::NotEqual => unreachable!(),
+        })
+    }
+}
+
+/// Keeps track of the state of all alert rules
+pub struct Alerts {
+    rules: Vec<Rule>,
+    status: RwLock<Vec<AlertStatus>>,
+    webhooks: Vec<String>,
+    agent: ureq::Agent,
+}
+
+impl Alerts {
+    /// `rules` are the parsed `config.rules`, in the same order
+    pub fn new(config: &AlertConfig, rules: Vec<Rule>) -> Self {
+        let status = config
+            .rules
+            .iter()
+            .zip(&rules)
+            .map(|(rule_config, rule)| AlertStatus {
+                name: rule_config.name.clone(),
+                rule: rule_config.rule.clone(),
+                widget: rule.widget.clone(),
+                level: rule_config.level,
+                state: AlertState::Ok,
+                since: None,
+                last_value: None,
+                consecutive: 0,
+            })
+            .collect();
+
+        Self {
+            rules,
+            status: RwLock::new(status),
+            webhooks: config.webhooks.clone(),
+            agent: delivery_agent(),
+        }
+    }
+
+    pub async fn status(&self) -> Vec<AlertStatus> {
+        self.status.read().await.clone()
+    }
+
+    /// Evaluates all rules for the widget of the run and returns the alerts that changed state.
+    ///
+    /// Failed runs, runs without output and outputs without the field do not affect the alert state.
+    pub async fn evaluate(&self, run: &BackendRun) -> Vec<AlertEvent> {
+        let Ok(Some(output)) = &run.result else {
+            return Vec::new();
+        };
+        let Ok(output) = serde_json::from_str::<Value>(output) else {
+            return Vec::new();
+        };
+
+        let mut status = self.status.write().await;
+        let mut events = Vec::new();
+
+        for (rule, status) in self.rules.iter().zip(status.iter_mut()) {
+            if rule.widget != run.widget {
+                continue;
+            }
+            let Some(value) = rule.extract(&output) else {
+                continue;
+            };
+            let Some(matches) = rule.matches(value) else {
+                continue;
+            };
+
+            status.last_value = Some(value.clone());
+            status.since.get_or_insert(run.ended);
+
+            let previous = status.state;
+            if matches {
+                status.consecutive += 1;
+                if status.consecutive >= rule.runs {
+                    status.state = AlertState::Firing;
+                }
+            } else {
+                status.consecutive = 0;
+                status.state = AlertState::Ok;
+            }
+
+            if status.state != previous {
+                status.since = Some(run.ended);
+                events.push(AlertEvent {
+                    state: status.state,
+                    alert: status.clone(),
+                });
+            }
+        }
+
+        events
+    }
+
+    /// POSTs the event to all configured webhooks
+    pub async fn deliver(self: Arc<Self>, event: AlertEvent) {
+        for url in &self.webhooks {
+            let agent = self.agent.clone();
+            let url = url.clone();
+            let event = event.clone();
+
+            let result =
+                tokio::task::spawn_blocking(move || agent.post(&url).send_json(&event).map(|_| ()))
+                    .await;
+
+            match result {
+                Ok(Ok(())) => {}
+                Ok(Err(err)) => tracing::warn!("could not deliver alert event: {err}"),
+                Err(err) => tracing::warn!("alert delivery panicked: {err}"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use serde_json::json;
+
+    use super::*;
+    use crate::widget::test_run;
+
+    fn rule(rule: &str) -> Rule {
+        rule.parse().unwrap()
+    }
+
+    fn error(rule: &str) -> String {
+        rule.parse::<Rule>().unwrap_err().to_string()
+    }
+
+    #[test]
+    fn parses_rules() {
+        assert_eq!(
+            rule("weather.temperature < 0"),
+            Rule {
+                widget: "weather".parse().unwrap(),
+                path: vec!["temperature".into()],
+                operator: Operator::Less,
+                value: json!(0),
+                runs: 1,
+            }
+        );
+
+        let parsed = rule("  disk.mounts.0.used_percent   >=  90.5 for 3 runs ");
+        assert_eq!(parsed.path, ["mounts", "0", "used_percent"]);
+        assert_eq!(parsed.operator, Operator::GreaterOrEqual);
+        assert_eq!(parsed.value, json!(90.5));
+        assert_eq!(parsed.runs, 3);
+
+        assert_eq!(rule("backup.ok == false for 1 run").value, json!(false));
+    }
+
+    #[test]
+    fn parses_quoted_strings() {
+        assert_eq!(rule(r#"ci.status != "ok""#).value, json!("ok"));
+        assert_eq!(
+            rule(r#"ci.status == "build failed" for 2 runs"#).value,
+            json!("build failed")
+        );
+        assert_eq!(
+            rule(r#"ci.status == "say \"hi there\"""#).value,
+            json!("say \"hi there\"")
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        assert!(error("weather.temperature <").starts_with("expected"));
+        assert!(error("weather.temperature < 0 for 2").starts_with("expected"));
+        assert!(error("weather < 0").starts_with("expected `<widget>.<field>`"));
+        assert!(error("weather.temperature =~ 0").starts_with("unknown operator"));
+        assert!(error("weather.temperature < 0 for 0 runs").contains("at least 1"));
+        assert!(error("weather.temperature < 0 for many runs").starts_with("invalid number"));
+        assert!(error("ci.status == failed").contains("strings need to be quoted"));
+        assert!(error(r#"ci.status == "build failed"#).starts_with("unterminated string"));
+        assert!(error(r#"ci.status == "build"failed"#).starts_with("expected whitespace"));
+    }
+
+    #[test]
+    fn extracts_and_compares_values() {
+        let output = json!({ "mounts": [{ "used_percent": 95.0 }], "status": "ok" });
+
+        let rule = rule("disk.mounts.0.used_percent > 90");
+        let value = rule.extract(&output).unwrap();
+        assert_eq!(rule.matches(value), Some(true));
+
+        assert!(rule.extract(&json!({ "mounts": [] })).is_none());
+        // only equality works on strings
+        assert_eq!(rule.matches(&json!("full")), None);
+        assert_eq!(rule_matches(r#"disk.status == "ok""#, &output), Some(true));
+    }
+
+    fn rule_matches(rule: &str, output: &Value) -> Option<bool> {
+        let rule: Rule = rule.parse().unwrap();
+        rule.matches(rule.extract(output)?)
+    }
+
+    fn run(widget: &str, minute: u32, output: Value) -> BackendRun {
+        let time = Utc.with_ymd_and_hms(2024, 1, 1, 12, minute, 0).unwrap();
+        test_run(widget, time, Ok(Some(output.to_string())))
+    }
+
+    #[tokio::test]
+    async fn fires_after_consecutive_runs() {
+        let config = AlertConfig {
+            rules: vec![AlertRuleConfig {
+                name: "freezing".into(),
+                rule: "weather.temperature < 0 for 2 runs".into(),
+                level: Level::Warning,
+            }],
+            webhooks: Vec::new(),
+        };
+        let rules = config
+            .rules
+            .iter()
+            .map(|r| r.rule.parse().unwrap())
+            .collect();
+        let alerts = Alerts::new(&config, rules);
+        let temperature = |t: f64| json!({ "temperature": t });
+
+        assert!(alerts
+            .evaluate(&run("weather", 0, temperature(-1.0)))
+            .await
+            .is_empty());
+        // a run without the field or of another widget does not reset the count
+        assert!(alerts
+            .evaluate(&run("weather", 1, json!({})))
+            .await
+            .is_empty());
+        assert!(alerts
+            .evaluate(&run("other", 2, temperature(5.0)))
+            .await
+            .is_empty());
+
+        let events = alerts.evaluate(&run("weather", 3, temperature(-2.0))).await;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].state, AlertState::Firing);
+        assert_eq!(events[0].alert.consecutive, 2);
+        assert_eq!(
+            events[0].alert.since,
+            Some(run("weather", 3, json!({})).ended)
+        );
+
+        // still firing, so no new event
+        assert!(alerts
+            .evaluate(&run("weather", 4, temperature(-3.0)))
+            .await
+            .is_empty());
+
+        let events = alerts.evaluate(&run("weather", 5, temperature(1.0))).await;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].state, AlertState::Ok);
+        assert_eq!(events[0].alert.consecutive, 0);
+        assert_eq!(events[0].alert.last_value, Some(json!(1.0)));
+
+        // one matching run is not enough to fire again
+        assert!(alerts
+            .evaluate(&run("weather", 6, temperature(-1.0)))
+            .await
+            .is_empty());
+        assert_eq!(alerts.status().await[0].state, AlertState::Ok);
+    }
+}
@@ -1,62 +1,439 @@
 use axum::{
     body::Body,
-    extract::{Path, State},
-    http::{Response, StatusCode},
+    extract::{MatchedPath, Path, Query, Request, State},
+    http::{header, HeaderMap, Response, StatusCode},
+    middleware::{self, Next},
     response::IntoResponse,
-    routing::get,
+    routing::{get, post},
     Json, Router,
 };
-use common::{backend::RunId, WidgetEnum, WidgetId};
-use std::{borrow::BorrowMut, net::SocketAddr, path::PathBuf, sync::Arc};
-use tokio::{fs, sync::RwLock};
+use chrono::{DateTime, Utc};
+use common::{
+    alert::AlertStatus,
+    backend::{Attempt, Initiator, RunId, RunPage},
+    freshness::Freshness,
+    notification::{Notification, NotificationId},
+    uptime::TargetUptime,
+    Dashboard, WidgetEnum, WidgetId,
+};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    path::PathBuf,
+    sync::{atomic::AtomicBool, Arc, Mutex, PoisonError},
+    time::Instant,
+};
+use tokio::{
+    fs,
+    sync::{
+        mpsc::{self, UnboundedReceiver},
+        RwLock,
+    },
+};
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
 use tower::{ServiceBuilder, ServiceExt};
 
 use tower_http::{services::ServeDir, trace::TraceLayer};
 
 use crate::{
+    alert::Alerts,
     config::Config,
-    database::{Database, DatabaseError, InMemoryDatabase},
-    widget::{self, BackendStateStorage},
+    database::{Database, DatabaseError, DatabaseResult, InMemoryDatabase},
+    freshness,
+    health::{self, Readiness},
+    metrics::Metrics,
+    notification::Notifier,
+    scheduler::{self, Retry, RunRequest},
+    secrets::Secrets,
+    shutdown,
+    webhook::{Delivery, Webhooks},
+    widget::{self, BackendStateStorage, RaisedNotification, RunInput},
 };
 use common::backend::BackendRun;
 
+/// Where the built frontend is served from
+const STATIC_DIR: &str = "../dist";
+
 // type SharedState = Arc<RwLock<AppState>>;
 
 // #[derive(Default)]
-struct AppState {
-    db: Arc<RwLock<dyn Database + Send + Sync>>,
-    widgets: Arc<Vec<WidgetEnum>>,
-    backend_state: Arc<RwLock<BackendStateStorage>>,
+pub struct AppState {
+    pub db: Arc<RwLock<dyn Database + Send + Sync>>,
+    pub widgets: Arc<Vec<WidgetEnum>>,
+    pub dashboards: Vec<Dashboard>,
+    /// The state each widget keeps between runs, locked per widget so that a slow run does not
+    /// hold up the others
+    pub backend_state: HashMap<WidgetId, Mutex<BackendStateStorage>>,
+    pub metrics: Metrics,
+    pub notifier: Notifier,
+    pub alerts: Arc<Alerts>,
+    pub webhooks: Arc<Webhooks>,
+    pub scheduler_running: AtomicBool,
+    /// Runs to be started by the scheduler
+    pub run_requests: mpsc::UnboundedSender<RunRequest>,
+    /// Cancelled when the server is shutting down, no new runs are started after that
+    pub shutdown: CancellationToken,
+    /// Keeps track of the runs that are in progress
+    pub tasks: TaskTracker,
+    /// Keeps track of the webhook, alert and notification deliveries that are in progress
+    pub deliveries: TaskTracker,
+    pub secrets: Secrets,
+    /// When the backend started, scheduled widgets are not expected to have run before
+    pub started: DateTime<Utc>,
+}
+
+impl AppState {
+    /// The state for the widgets of `config`, with the receiving end of the runs it requests
+    pub fn new(config: Config) -> anyhow::Result<(Arc<Self>, UnboundedReceiver<RunRequest>)> {
+        let secrets = Secrets::resolve(&config.secrets)?;
+        let notifier = Notifier::new(&config.notifications);
+        let alerts = Arc::new(Alerts::new(&config.alerts, config.alert_rules));
+        let webhooks = Arc::new(Webhooks::new(config.webhooks));
+        let (run_requests, run_requests_rx) = mpsc::unbounded_channel();
+        let backend_state = config
+            .widgets
+            .iter()
+            .map(|w| (w.id().clone(), Mutex::new(BackendStateStorage::new())))
+            .collect();
+        let state = Arc::new(AppState {
+            db: Arc::new(RwLock::new(InMemoryDatabase::new())),
+            widgets: Arc::new(config.widgets),
+            dashboards: config.dashboards,
+            backend_state,
+            metrics: Metrics::new(),
+            notifier,
+            alerts,
+            webhooks,
+            scheduler_running: AtomicBool::new(false),
+            run_requests,
+            shutdown: CancellationToken::new(),
+            tasks: TaskTracker::new(),
+            deliveries: TaskTracker::new(),
+            secrets,
+            started: Utc::now(),
+        });
+        Ok((state, run_requests_rx))
+    }
+
+    pub fn find_widget(&self, widget_id: &WidgetId) -> DatabaseResult<&WidgetEnum> {
+        self.widgets
+            .iter()
+            .find(|w| w.id() == widget_id)
+            .ok_or(DatabaseError::InvalidWidgetId)
+    }
+
+    /// Run the backend handler of a widget and store the result
+    pub async fn execute(
+        self: &Arc<Self>,
+        widget_id: &WidgetId,
+        initiator: Initiator,
+        retry: Option<Retry>,
+    ) -> Result<RunId, ApiError> {
+        if self.shutdown.is_cancelled() {
+            return Err(ApiError::ShuttingDown);
+        }
+        if let WidgetEnum::Push(_) | WidgetEnum::Heartbeat(_) = self.find_widget(widget_id)? {
+            return Err(ApiError::NotTriggerable);
+        }
+
+        // spawn the run as a tracked task so that it is allowed to finish during shutdown,
+        // even if the request that triggered it goes away
+        let state = self.clone();
+        let widget_id = widget_id.clone();
+        let handle = self.tasks.spawn(async move {
+            let widget = state.find_widget(&widget_id)?;
+
+            let _queued = state.metrics.enqueue_run();
+            let input = RunInput {
+                initiator,
+                upstream: state.upstream_outputs(widget).await,
+                previous: state.previous_output(widget).await,
+                secrets: state.secrets.granted(widget.secrets()),
+            };
+
+            // the backend handlers block, e.g. on network requests or commands
+            let blocking_state = state.clone();
+            let (mut run, notifications) =
+                tokio::task::spawn_blocking(move || -> DatabaseResult<_> {
+                    let state = blocking_state;
+                    let widget = state.find_widget(&widget_id)?;
+                    let mut backend_state = state.backend_state[&widget_id]
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner);
+                    let backend_state = &mut *backend_state;
+
+                    Ok(match widget {
+                        WidgetEnum::Weather(w) => widget::run(w, backend_state, input),
+                        WidgetEnum::Clothing(w) => widget::run(w, backend_state, input),
+                        WidgetEnum::System(w) => widget::run(w, backend_state, input),
+                        WidgetEnum::Uptime(w) => widget::run(w, backend_state, input),
+                        WidgetEnum::Certificate(w) => widget::run(w, backend_state, input),
+                        WidgetEnum::Agenda(w) => widget::run(w, backend_state, input),
+                        WidgetEnum::Feed(w) => widget::run(w, backend_state, input),
+                        WidgetEnum::Command(w) => widget::run(w, backend_state, input),
+                        WidgetEnum::Fetch(w) => widget::run(w, backend_state, input),
+                        WidgetEnum::LogTail(w) => widget::run(w, backend_state, input),
+                        WidgetEnum::Git(w) => widget::run(w, backend_state, input),
+                        WidgetEnum::Astronomy(w) => widget::run(w, backend_state, input),
+                        WidgetEnum::Push(_) | WidgetEnum::Heartbeat(_) => {
+                            unreachable!("push and heartbeat widgets are never run")
+                        }
+                    })
+                })
+                .await
+                .map_err(|e| ApiError::RunFailed(e.to_string()))??;
+
+            // only scheduled runs are retried
+            let policy = widget.schedule().and_then(|s| s.retry.as_ref());
+            if let (Some(policy), Initiator::Schedule | Initiator::Retry) = (policy, initiator) {
+                let number = retry.map_or(1, |r| r.number);
+                let retry_later = matches!(&run.result, Err(e) if e.is_transient())
+                    && number < policy.max_attempts;
+
+                run.attempt = Some(Attempt {
+                    retry_of: retry.map(|r| r.of),
+                    number,
+                    max_attempts: policy.max_attempts,
+                    next_retry: retry_later.then(|| run.ended + policy.delay(number)),
+                });
+            }
+
+            Ok(state.store_run(run, notifications).await?)
+        });
+
+        handle
+            .await
+            .map_err(|e| ApiError::RunFailed(e.to_string()))?
+    }
+
+    /// Output of the latest successful run of a widget
+    async fn previous_output(&self, widget: &WidgetEnum) -> Option<String> {
+        let db = self.db.read().await;
+        db.get_last_successful_run(widget.id().clone())
+            .ok()?
+            .result
+            .ok()?
+    }
+
+    /// Collects the latest successful output of all widgets the widget depends on
+    async fn upstream_outputs(&self, widget: &WidgetEnum) -> HashMap<WidgetId, String> {
+        let db = self.db.read().await;
+
+        widget
+            .depends_on()
+            .iter()
+            .filter_map(|id| {
+                let run = db.get_last_successful_run(id.clone()).ok()?;
+                Some((id.clone(), run.result.ok()??))
+            })
+            .collect()
+    }
+
+    /// Store data pushed to a push widget as a run
+    pub async fn ingest(
+        self: &Arc<Self>,
+        widget_id: &WidgetId,
+        token: &str,
+        data: serde_json::Value,
+    ) -> Result<RunId, ApiError> {
+        if self.shutdown.is_cancelled() {
+            return Err(ApiError::ShuttingDown);
+        }
+        let WidgetEnum::Push(widget) = self.find_widget(widget_id)? else {
+            return Err(ApiError::NotPushable);
+        };
+
+        if !widget::push::authorize(&widget.config.token, token) {
+            return Err(ApiError::Unauthorized);
+        }
+        widget::push::validate(&widget.config, &data).map_err(ApiError::InvalidData)?;
+
+        let run = widget::push::run(widget, data);
+        let state = self.clone();
+        let handle = self
+            .tasks
+            .spawn(async move { state.store_run(run, Vec::new()).await });
+
+        Ok(handle
+            .await
+            .map_err(|e| ApiError::RunFailed(e.to_string()))??)
+    }
+
+    /// Store a ping to a heartbeat widget as a run
+    pub async fn heartbeat(
+        self: &Arc<Self>,
+        widget_id: &WidgetId,
+        token: &str,
+        body: &str,
+    ) -> Result<RunId, ApiError> {
+        if self.shutdown.is_cancelled() {
+            return Err(ApiError::ShuttingDown);
+        }
+        let WidgetEnum::Heartbeat(widget) = self.find_widget(widget_id)? else {
+            return Err(ApiError::NotHeartbeat);
+        };
+
+        if !widget::push::authorize(&widget.config.token, token) {
+            return Err(ApiError::Unauthorized);
+        }
+
+        let run = widget::heartbeat::run(widget, body);
+        let state = self.clone();
+        let handle = self
+            .tasks
+            .spawn(async move { state.store_run(run, Vec::new()).await });
+
+        Ok(handle
+            .await
+            .map_err(|e| ApiError::RunFailed(e.to_string()))??)
+    }
+
+    /// Store a finished run and let everything that reacts to new runs know about it
+    async fn store_run(
+        self: &Arc<Self>,
+        run: BackendRun,
+        notifications: Vec<RaisedNotification>,
+    ) -> DatabaseResult<RunId> {
+        let widget_id = run.widget.clone();
+        self.metrics.observe_run(&run);
+
+        let (id, state_changed) = {
+            let mut db = self.db.write().await;
+            let previous = db.get_last_run(widget_id.clone()).ok();
+            let state_changed = previous.is_none_or(|p| p.result.is_ok() != run.result.is_ok());
+
+            (
+                db.insert_run(widget_id.clone(), run.clone())?,
+                state_changed,
+            )
+        };
+        let run = BackendRun { id, ..run };
+
+        for index in self.webhooks.matching(&run, state_changed) {
+            self.deliveries
+                .spawn(self.webhooks.clone().deliver(index, run.clone()));
+        }
+
+        for event in self.alerts.evaluate(&run).await {
+            tracing::info!("alert {} is now {:?}", event.alert.name, event.state);
+            self.deliveries.spawn(self.alerts.clone().deliver(event));
+        }
+
+        // deliver in the background so that the run is not held up by slow sinks
+        for notification in self.notifier.publish(&widget_id, notifications).await {
+            let state = self.clone();
+            self.deliveries
+                .spawn(async move { state.notifier.deliver(notification).await });
+        }
+
+        if let Some(Attempt {
+            number,
+            next_retry: Some(at),
+            retry_of,
+            max_attempts,
+        }) = run.attempt
+        {
+            tracing::info!(
+                "run {id:?} of widget {widget_id} failed, retrying (attempt {} of {max_attempts}) at {at}",
+                number + 1
+            );
+            let _ = self.run_requests.send(RunRequest {
+                widget: widget_id.clone(),
+                initiator: Initiator::Retry,
+                retry: Some(Retry {
+                    of: retry_of.unwrap_or(id),
+                    number: number + 1,
+                    at,
+                }),
+            });
+        }
+
+        // chain the runs of the widgets that use the output of this one, the configuration is
+        // checked for cycles when it is loaded
+        if run.result.is_ok() {
+            for dependent in self
+                .widgets
+                .iter()
+                .filter(|w| w.depends_on().contains(&widget_id))
+            {
+                // the scheduler might already be gone when shutting down
+                let _ = self.run_requests.send(RunRequest {
+                    widget: dependent.id().clone(),
+                    initiator: Initiator::Dependency,
+                    retry: None,
+                });
+            }
+        }
+
+        Ok(id)
+    }
+}
+
+#[cfg(test)]
+impl AppState {
+    /// The state for a configuration in YAML, as if the backend had just started. The runs it
+    /// requests are dropped, as there is no scheduler.
+    pub fn for_test(config: &str) -> Arc<Self> {
+        let config = crate::config::parse_config(config).unwrap();
+        AppState::new(config).unwrap().0
+    }
 }
 
 /// The main entrypoint for the Axum web server
 pub async fn launch_api(config: Config) -> anyhow::Result<()> {
-    let shared_state = AppState {
-        db: Arc::new(RwLock::new(InMemoryDatabase::new())),
-        widgets: Arc::new(config.widgets),
-        backend_state: Arc::new(RwLock::new(BackendStateStorage::new())),
-    };
+    let (shared_state, run_requests_rx) = AppState::new(config)?;
+
+    tokio::spawn(scheduler::run_scheduler(
+        shared_state.clone(),
+        run_requests_rx,
+    ));
 
     // Build our application by composing routes
     let api_router = Router::new()
         .route("/widgets", get(get_widgets))
-        .route("/widget/:widget_id", get(get_widget))
-        .route("/widget/:widget_id/run/:run_id", get(get_run))
-        .route("/widget/:widget_id/runs", get(get_runs))
-        .route("/widget/:widget_id/latest", get(get_last_run))
-        .route("/widget/:widget_id/trigger", get(trigger_widget_run));
+        .route("/widget/{widget_id}", get(get_widget))
+        .route("/dashboards", get(get_dashboards))
+        .route("/dashboard/{name}", get(get_dashboard_widgets))
+        .route("/widget/{widget_id}/run/{run_id}", get(get_run))
+        .route("/widget/{widget_id}/runs", get(get_runs))
+        .route("/widget/{widget_id}/history", get(get_run_history))
+        .route("/widget/{widget_id}/latest", get(get_last_run))
+        .route("/widget/{widget_id}/freshness", get(get_widget_freshness))
+        .route("/widget/{widget_id}/uptime", get(get_uptime))
+        .route("/widget/{widget_id}/trigger", get(trigger_widget_run))
+        .route("/widget/{widget_id}/ingest", post(ingest_widget_data))
+        .route(
+            "/widget/{widget_id}/heartbeat",
+            get(receive_heartbeat).post(receive_heartbeat),
+        )
+        .route(
+            "/widget/{widget_id}/notifications",
+            get(get_widget_notifications),
+        )
+        .route("/notifications", get(get_notifications))
+        .route("/freshness", get(get_freshness))
+        .route("/alerts", get(get_alerts))
+        .route("/webhooks/deliveries", get(get_webhook_deliveries))
+        .route(
+            "/notification/{notification_id}/acknowledge",
+            post(acknowledge_notification),
+        );
 
     let app = Router::new()
         .nest("/api", api_router)
+        .route("/metrics", get(get_metrics))
+        .route("/healthz", get(get_health))
+        .route("/readyz", get(get_readiness))
         // Fallback to serving index.html for paths that were not found (to allow the yew SPA to work correctly)
         // See: https://robert.kra.hn/posts/2022-04-03_rust-web-wasm/
         .fallback(get(|req| async move {
-            match ServeDir::new("../dist").oneshot(req).await {
+            match ServeDir::new(STATIC_DIR).oneshot(req).await {
                 Ok(res) => {
                     let status = res.status();
                     match status {
                         StatusCode::NOT_FOUND => {
-                            let index_path = PathBuf::from("../dist").join("index.html");
+                            let index_path = PathBuf::from(STATIC_DIR).join("index.html");
                             let index_content = match fs::read_to_string(index_path).await {
                                 Err(_) => {
                                     return Response::builder()
@@ -81,7 +458,11 @@ pub async fn launch_api(config: Config) -> anyhow::Result<()> {
                     .expect("error response"),
             }
         }))
-        .with_state(Arc::new(shared_state))
+        .with_state(shared_state.clone())
+        .layer(middleware::from_fn_with_state(
+            shared_state.clone(),
+            track_http,
+        ))
         .layer(ServiceBuilder::new().layer(TraceLayer::new_for_http()));
 
     // Run our app with hyper
@@ -89,7 +470,27 @@ pub async fn launch_api(config: Config) -> anyhow::Result<()> {
     tracing::debug!("listening on {}", addr);
 
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+
+    // stop accepting connections once the shutdown starts, but let the open ones finish
+    let token = shared_state.shutdown.clone();
+    let mut server = tokio::spawn(async move {
+        axum::serve(listener, app)
+            .with_graceful_shutdown(token.cancelled_owned())
+            .await
+    });
+
+    tokio::select! {
+        result = &mut server => {
+            result??;
+            return Ok(());
+        }
+        _ = shutdown::signal() => {}
+    }
+
+    shutdown::drain(&shared_state).await;
+
+    // give the remaining responses a moment to be sent before exiting
+    let _ = tokio::time::timeout(shutdown::RESPONSE_GRACE, server).await;
 
     Ok(())
 }
@@ -100,21 +501,37 @@ async fn get_widgets(State(state): State<Arc<AppState>>) -> Json<Vec<WidgetEnum>
 }
 
 #[axum::debug_handler]
-async fn get_widget(
-    Path(widget_id): Path<WidgetId>,
+async fn get_dashboards(State(state): State<Arc<AppState>>) -> Json<Vec<Dashboard>> {
+    Json(state.dashboards.clone())
+}
+
+/// The widgets of a dashboard, in the order they should be shown
+#[axum::debug_handler]
+async fn get_dashboard_widgets(
+    Path(name): Path<String>,
     State(state): State<Arc<AppState>>,
-) -> Result<Json<WidgetEnum>, DatabaseError> {
-    let widget: &WidgetEnum = state
+) -> Result<Json<Vec<WidgetEnum>>, ApiError> {
+    let dashboard = state
+        .dashboards
+        .iter()
+        .find(|d| d.name == name)
+        .ok_or(ApiError::InvalidDashboard)?;
+
+    let widgets = dashboard
         .widgets
         .iter()
-        .find(|w| {
-            let id = match w {
-                WidgetEnum::Weather(w) => &w.id,
-            };
+        .map(|id| state.find_widget(id).cloned())
+        .collect::<DatabaseResult<_>>()?;
 
-            *id == widget_id
-        })
-        .ok_or(DatabaseError::InvalidWidgetId)?;
+    Ok(Json(widgets))
+}
+
+#[axum::debug_handler]
+async fn get_widget(
+    Path(widget_id): Path<WidgetId>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<WidgetEnum>, DatabaseError> {
+    let widget = state.find_widget(&widget_id)?;
 
     Ok(Json(widget.clone()))
 }
@@ -139,6 +556,51 @@ async fn get_runs(
     Ok(Json(run))
 }
 
+/// Largest page size of the run history
+const MAX_PER_PAGE: usize = 100;
+
+#[derive(Debug, Deserialize)]
+struct HistoryQuery {
+    #[serde(default)]
+    page: usize,
+    #[serde(default = "default_per_page")]
+    per_page: usize,
+}
+
+fn default_per_page() -> usize {
+    20
+}
+
+#[axum::debug_handler]
+async fn get_run_history(
+    Path(widget_id): Path<WidgetId>,
+    Query(query): Query<HistoryQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<RunPage>, DatabaseError> {
+    state.find_widget(&widget_id)?;
+
+    // the database only knows about widgets that have been run at least once
+    let runs = state
+        .db
+        .read()
+        .await
+        .get_runs(widget_id)
+        .unwrap_or_default();
+
+    let per_page = query.per_page.clamp(1, MAX_PER_PAGE);
+    Ok(Json(RunPage {
+        total: runs.len(),
+        runs: runs
+            .into_iter()
+            .rev()
+            .skip(query.page.saturating_mul(per_page))
+            .take(per_page)
+            .collect(),
+        page: query.page,
+        per_page,
+    }))
+}
+
 #[axum::debug_handler]
 async fn get_last_run(
     Path(widget_id): Path<WidgetId>,
@@ -153,34 +615,283 @@ async fn get_last_run(
 async fn trigger_widget_run(
     Path(widget_id): Path<WidgetId>,
     State(state): State<Arc<AppState>>,
-) -> Result<Json<RunId>, DatabaseError> {
-    // run the backend handler and store the result (on the main thread for now, not optimal)
-    // TODO: offload this responsibility to some background service
+) -> Result<Json<RunId>, ApiError> {
+    let id = state.execute(&widget_id, Initiator::Manual, None).await?;
 
-    // find the widget (this is ridicoulously difficult haha)
-    let widget: &WidgetEnum = state
-        .widgets
-        .iter()
-        .find(|w| {
-            let id = match w {
-                WidgetEnum::Weather(w) => &w.id,
-            };
+    Ok(Json(id))
+}
 
-            *id == widget_id
-        })
-        .ok_or(DatabaseError::InvalidWidgetId)?;
-    let run = {
-        let mut backend_state = state.backend_state.write().await;
+#[derive(Debug, Deserialize)]
+struct UptimeQuery {
+    #[serde(default = "default_uptime_hours")]
+    hours: i64,
+}
 
-        match widget {
-            WidgetEnum::Weather(w) => widget::run(w, backend_state.borrow_mut()),
-        }
+fn default_uptime_hours() -> i64 {
+    24
+}
+
+/// Uptime of the targets of an uptime widget over the last `hours`
+#[axum::debug_handler]
+async fn get_uptime(
+    Path(widget_id): Path<WidgetId>,
+    Query(query): Query<UptimeQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<TargetUptime>>, ApiError> {
+    let WidgetEnum::Uptime(widget) = state.find_widget(&widget_id)? else {
+        return Err(ApiError::NotUptime);
     };
-    let id = state.db.write().await.insert_run(widget_id, run)?;
+    let runs = state
+        .db
+        .read()
+        .await
+        .get_runs(widget_id)
+        .unwrap_or_default();
+
+    let since = chrono::TimeDelta::try_hours(query.hours)
+        .filter(|_| query.hours > 0)
+        .and_then(|period| Utc::now().checked_sub_signed(period))
+        .ok_or(ApiError::InvalidPeriod)?;
+    Ok(Json(widget::uptime::uptime(&widget.config, &runs, since)))
+}
+
+#[axum::debug_handler]
+async fn get_widget_freshness(
+    Path(widget_id): Path<WidgetId>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Freshness>, DatabaseError> {
+    let widget = state.find_widget(&widget_id)?;
+    let db = state.db.read().await;
+
+    Ok(Json(freshness::compute(&*db, widget, Utc::now())))
+}
+
+#[axum::debug_handler]
+async fn get_freshness(State(state): State<Arc<AppState>>) -> Json<Vec<Freshness>> {
+    let db = state.db.read().await;
+    let now = Utc::now();
+
+    Json(
+        state
+            .widgets
+            .iter()
+            .map(|widget| freshness::compute(&*db, widget, now))
+            .collect(),
+    )
+}
+
+#[axum::debug_handler]
+async fn get_alerts(State(state): State<Arc<AppState>>) -> Json<Vec<AlertStatus>> {
+    Json(state.alerts.status().await)
+}
+
+#[axum::debug_handler]
+async fn get_webhook_deliveries(State(state): State<Arc<AppState>>) -> Json<Vec<Delivery>> {
+    Json(state.webhooks.deliveries().await)
+}
+
+#[axum::debug_handler]
+async fn get_notifications(State(state): State<Arc<AppState>>) -> Json<Vec<Notification>> {
+    Json(state.notifier.store.read().await.list(None))
+}
+
+#[axum::debug_handler]
+async fn get_widget_notifications(
+    Path(widget_id): Path<WidgetId>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<Notification>>, ApiError> {
+    state.find_widget(&widget_id)?;
+
+    Ok(Json(
+        state.notifier.store.read().await.list(Some(&widget_id)),
+    ))
+}
+
+#[axum::debug_handler]
+async fn acknowledge_notification(
+    Path(notification_id): Path<NotificationId>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Notification>, ApiError> {
+    let notification = state
+        .notifier
+        .store
+        .write()
+        .await
+        .acknowledge(notification_id)
+        .ok_or(ApiError::InvalidNotificationId)?;
+
+    Ok(Json(notification))
+}
+
+/// Receives data for a push widget, authenticated with `Authorization: Bearer <token>`
+#[axum::debug_handler]
+async fn ingest_widget_data(
+    Path(widget_id): Path<WidgetId>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(data): Json<serde_json::Value>,
+) -> Result<Json<RunId>, ApiError> {
+    let token = bearer_token(&headers)?;
+    let id = state.ingest(&widget_id, token, data).await?;
+
+    Ok(Json(id))
+}
+
+/// Records a ping from an external job, authenticated with `Authorization: Bearer <token>`. The
+/// body, if any, is kept as a message.
+#[axum::debug_handler]
+async fn receive_heartbeat(
+    Path(widget_id): Path<WidgetId>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: String,
+) -> Result<Json<RunId>, ApiError> {
+    let token = bearer_token(&headers)?;
+    let id = state.heartbeat(&widget_id, token, &body).await?;
 
     Ok(Json(id))
 }
 
+fn bearer_token(headers: &HeaderMap) -> Result<&str, ApiError> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or(ApiError::Unauthorized)
+}
+
+/// Liveness probe, answers as long as the process is able to handle requests
+#[axum::debug_handler]
+async fn get_health() -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "status": "ok" }))
+}
+
+/// Readiness probe with details about each component
+#[axum::debug_handler]
+async fn get_readiness(State(state): State<Arc<AppState>>) -> (StatusCode, Json<Readiness>) {
+    let readiness = health::check_readiness(&state, std::path::Path::new(STATIC_DIR)).await;
+
+    let status = if readiness.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (status, Json(readiness))
+}
+
+#[axum::debug_handler]
+async fn get_metrics(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.encode(),
+    )
+}
+
+/// Middleware that records the count and latency of all HTTP requests
+async fn track_http(
+    State(state): State<Arc<AppState>>,
+    req: Request,
+    next: Next,
+) -> impl IntoResponse {
+    // use the route template and not the actual path to keep the number of label values bounded
+    let path = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_owned())
+        .unwrap_or_else(|| "fallback".into());
+    let method = req.method().to_string();
+
+    let start = Instant::now();
+    let response = next.run(req).await;
+
+    state
+        .metrics
+        .observe_http(&method, &path, response.status().as_str(), start.elapsed());
+
+    response
+}
+
+/// Errors that can occur when handling API requests
+#[derive(Debug)]
+pub enum ApiError {
+    Database(DatabaseError),
+    /// New runs are not accepted while the server is shutting down
+    ShuttingDown,
+    InvalidNotificationId,
+    InvalidDashboard,
+    /// Push and heartbeat widgets can only be updated from outside
+    NotTriggerable,
+    /// Only push widgets accept data through the ingest endpoint
+    NotPushable,
+    /// Only heartbeat widgets accept pings
+    NotHeartbeat,
+    Unauthorized,
+    /// Uptime is only tracked for uptime widgets
+    NotUptime,
+    /// The number of hours to compute the uptime over is not positive or too large
+    InvalidPeriod,
+    /// Pushed data did not match the schema of the widget
+    InvalidData(String),
+    /// The run could not be completed, e.g. because the backend handler panicked
+    RunFailed(String),
+}
+
+impl From<DatabaseError> for ApiError {
+    fn from(value: DatabaseError) -> Self {
+        ApiError::Database(value)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> axum::response::Response {
+        match self {
+            ApiError::Database(err) => err.into_response(),
+            ApiError::ShuttingDown => {
+                (StatusCode::SERVICE_UNAVAILABLE, "Shutting down").into_response()
+            }
+            ApiError::InvalidNotificationId => {
+                (StatusCode::NOT_FOUND, "Invalid Notification ID").into_response()
+            }
+            ApiError::InvalidDashboard => {
+                (StatusCode::NOT_FOUND, "Invalid Dashboard").into_response()
+            }
+            ApiError::NotTriggerable => (
+                StatusCode::BAD_REQUEST,
+                "Widget can only be updated from outside",
+            )
+                .into_response(),
+            ApiError::NotPushable => (
+                StatusCode::BAD_REQUEST,
+                "Widget does not accept pushed data",
+            )
+                .into_response(),
+            ApiError::NotHeartbeat => {
+                (StatusCode::BAD_REQUEST, "Widget is not a heartbeat widget").into_response()
+            }
+            ApiError::NotUptime => {
+                (StatusCode::BAD_REQUEST, "Widget is not an uptime widget").into_response()
+            }
+            ApiError::InvalidPeriod => (
+                StatusCode::BAD_REQUEST,
+                "hours has to be a positive number of hours",
+            )
+                .into_response(),
+            ApiError::Unauthorized => {
+                (StatusCode::UNAUTHORIZED, "Invalid or missing token").into_response()
+            }
+            ApiError::InvalidData(err) => {
+                (StatusCode::BAD_REQUEST, format!("Invalid data: {err}")).into_response()
+            }
+            ApiError::RunFailed(err) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Widget run failed: {err}"),
+            )
+                .into_response(),
+        }
+    }
+}
+
 impl IntoResponse for DatabaseError {
     fn into_response(self) -> axum::response::Response {
         match self {
@@ -191,3 +902,241 @@ impl IntoResponse for DatabaseError {
         .into_response()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+    use chrono::TimeDelta;
+    use common::backend::BackendError;
+    use serde_json::json;
+
+    use super::*;
+    use crate::config::parse_config;
+
+    const CONFIG: &str = "
+widgets:
+  - !Push
+    id: ci
+    config:
+      token: secret
+      schema:
+        type: object
+        required: [status]
+";
+
+    fn headers(authorization: Option<&str>) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Some(authorization) = authorization {
+            headers.insert(
+                header::AUTHORIZATION,
+                HeaderValue::from_str(authorization).unwrap(),
+            );
+        }
+        headers
+    }
+
+    async fn ingest(
+        state: &Arc<AppState>,
+        authorization: Option<&str>,
+        data: serde_json::Value,
+    ) -> StatusCode {
+        ingest_widget_data(
+            Path("ci".parse().unwrap()),
+            State(state.clone()),
+            headers(authorization),
+            Json(data),
+        )
+        .await
+        .into_response()
+        .status()
+    }
+
+    #[tokio::test]
+    async fn ingest_requires_token() {
+        let state = AppState::for_test(CONFIG);
+        let data = json!({ "status": "passed" });
+
+        assert_eq!(
+            ingest(&state, None, data.clone()).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            ingest(&state, Some("secret"), data.clone()).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            ingest(&state, Some("Bearer wrong"), data.clone()).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert!(state
+            .db
+            .read()
+            .await
+            .get_last_run("ci".parse().unwrap())
+            .is_err());
+
+        assert_eq!(
+            ingest(&state, Some("Bearer secret"), data.clone()).await,
+            StatusCode::OK
+        );
+        let run = state
+            .db
+            .read()
+            .await
+            .get_last_run("ci".parse().unwrap())
+            .unwrap();
+        assert!(matches!(run.initiated, Initiator::Webhook));
+        assert_eq!(run.result, Ok(Some(data.to_string())));
+    }
+
+    #[tokio::test]
+    async fn ingest_validates_schema() {
+        let state = AppState::for_test(CONFIG);
+
+        assert_eq!(
+            ingest(&state, Some("Bearer secret"), json!({ "state": "passed" })).await,
+            StatusCode::BAD_REQUEST
+        );
+        assert!(state
+            .db
+            .read()
+            .await
+            .get_last_run("ci".parse().unwrap())
+            .is_err());
+    }
+
+    const RETRIED: &str = "
+widgets:
+  - !Command
+    id: flaky
+    schedule:
+      cron: \"0 0 1 1 *\"
+      retry:
+        max_attempts: 2
+        initial_delay_seconds: 30
+    config:
+      argv: [\"false\"]
+  - !Command
+    id: broken
+    schedule:
+      cron: \"0 0 1 1 *\"
+      retry:
+        max_attempts: 2
+    config:
+      argv: [\"/nonexistent/command\"]
+";
+
+    #[tokio::test]
+    async fn retries_transient_failures_of_scheduled_runs() {
+        let (state, mut requests) = AppState::new(parse_config(RETRIED).unwrap()).unwrap();
+        let flaky: WidgetId = "flaky".parse().unwrap();
+        let run = |id| {
+            let state = state.clone();
+            let flaky = flaky.clone();
+            async move { state.db.read().await.get_run(flaky, id).unwrap() }
+        };
+
+        let first = state
+            .execute(&flaky, Initiator::Schedule, None)
+            .await
+            .unwrap();
+        let first_run = run(first).await;
+        let retry_at = first_run.ended + TimeDelta::seconds(30);
+        assert_eq!(
+            first_run.attempt,
+            Some(Attempt {
+                retry_of: None,
+                number: 1,
+                max_attempts: 2,
+                next_retry: Some(retry_at),
+            })
+        );
+
+        let request = requests.try_recv().unwrap();
+        assert!(matches!(request.initiator, Initiator::Retry));
+        let retry = request.retry.unwrap();
+        assert_eq!((retry.of, retry.number, retry.at), (first, 2, retry_at));
+
+        // the first run counts as an attempt, so this is the last one
+        let second = state
+            .execute(&flaky, Initiator::Retry, Some(retry))
+            .await
+            .unwrap();
+        assert_eq!(
+            run(second).await.attempt,
+            Some(Attempt {
+                retry_of: Some(first),
+                number: 2,
+                max_attempts: 2,
+                next_retry: None,
+            })
+        );
+        assert!(requests.try_recv().is_err());
+
+        // only scheduled runs are retried
+        let manual = state
+            .execute(&flaky, Initiator::Manual, None)
+            .await
+            .unwrap();
+        assert_eq!(run(manual).await.attempt, None);
+        assert!(requests.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_permanent_failures() {
+        let (state, mut requests) = AppState::new(parse_config(RETRIED).unwrap()).unwrap();
+        let broken: WidgetId = "broken".parse().unwrap();
+
+        let id = state
+            .execute(&broken, Initiator::Schedule, None)
+            .await
+            .unwrap();
+        let run = state.db.read().await.get_run(broken, id).unwrap();
+        assert!(matches!(run.result, Err(BackendError::Permanent(_))));
+        assert_eq!(
+            run.attempt,
+            Some(Attempt {
+                retry_of: None,
+                number: 1,
+                max_attempts: 2,
+                next_retry: None,
+            })
+        );
+        assert!(requests.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn uptime_requires_valid_period() {
+        let state = AppState::for_test(
+            "
+widgets:
+  - !Uptime
+    id: uptime
+    config:
+      targets:
+        - url: http://localhost:1
+",
+        );
+        let uptime = |hours| {
+            let state = state.clone();
+            async move {
+                get_uptime(
+                    Path("uptime".parse().unwrap()),
+                    Query(UptimeQuery { hours }),
+                    State(state),
+                )
+                .await
+                .into_response()
+                .status()
+            }
+        };
+
+        assert_eq!(uptime(24).await, StatusCode::OK);
+        assert_eq!(uptime(0).await, StatusCode::BAD_REQUEST);
+        assert_eq!(uptime(-1).await, StatusCode::BAD_REQUEST);
+        // too large for a duration
+        assert_eq!(uptime(i64::MAX).await, StatusCode::BAD_REQUEST);
+        // a valid duration, but further back than a date can be
+        assert_eq!(uptime(1_000_000_000_000).await, StatusCode::BAD_REQUEST);
+    }
+}
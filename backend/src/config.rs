@@ -1,18 +1,336 @@
-use std::fs;
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+};
 
 use anyhow::anyhow;
-use common::WidgetEnum;
+use common::{Dashboard, WidgetEnum, WidgetId};
 use serde::Deserialize;
 
+use crate::{
+    alert::{AlertConfig, Rule},
+    notification::NotificationConfig,
+    scheduler,
+    secrets::SecretSource,
+    webhook::WebhookConfig,
+};
+
 #[derive(Debug, Deserialize)]
 pub struct Config {
     pub widgets: Vec<WidgetEnum>,
+
+    #[serde(default)]
+    pub notifications: NotificationConfig,
+
+    #[serde(default)]
+    pub alerts: AlertConfig,
+
+    /// Called when widget runs complete
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+
+    /// Named selections of the widgets, the home page shows all of them
+    #[serde(default)]
+    pub dashboards: Vec<Dashboard>,
+
+    /// Secrets that widgets can be given access to, by name
+    #[serde(default)]
+    pub secrets: HashMap<String, SecretSource>,
+
+    /// The parsed `alerts.rules`, in the same order. Filled in by [`parse_config`].
+    #[serde(skip)]
+    pub alert_rules: Vec<Rule>,
 }
 
 pub fn load_config() -> Result<Config, anyhow::Error> {
     // read file contents
     let contents = fs::read_to_string("config.yaml")?;
-    let config: Config = serde_yaml::from_str(&contents).map_err(|e| anyhow!(e))?;
+    parse_config(&contents)
+}
+
+/// Parses and validates the contents of a configuration file
+pub fn parse_config(contents: &str) -> Result<Config, anyhow::Error> {
+    let mut config: Config = serde_yaml::from_str(contents).map_err(|e| anyhow!(e))?;
+
+    for widget in &config.widgets {
+        if let WidgetEnum::Push(push) = widget {
+            if push.config.token.is_empty() {
+                return Err(anyhow!("push widget {} needs a token", push.id));
+            }
+            if push.schedule.is_some() {
+                return Err(anyhow!("push widget {} can not be scheduled", push.id));
+            }
+            if let Some(schema) = &push.config.schema {
+                jsonschema::validator_for(schema)
+                    .map_err(|e| anyhow!("invalid schema for widget {}: {e}", push.id))?;
+            }
+        }
+        if let WidgetEnum::Heartbeat(heartbeat) = widget {
+            if heartbeat.config.token.is_empty() {
+                return Err(anyhow!("heartbeat widget {} needs a token", heartbeat.id));
+            }
+            if heartbeat.schedule.is_some() {
+                return Err(anyhow!(
+                    "heartbeat widget {} can not be scheduled",
+                    heartbeat.id
+                ));
+            }
+            if heartbeat.config.period_minutes == 0 {
+                return Err(anyhow!(
+                    "heartbeat widget {} needs a period_minutes above 0",
+                    heartbeat.id
+                ));
+            }
+        }
+        if let WidgetEnum::Certificate(certificate) = widget {
+            if certificate.config.critical_days > certificate.config.warning_days {
+                return Err(anyhow!(
+                    "certificate widget {} has critical_days above warning_days",
+                    certificate.id
+                ));
+            }
+        }
+        if let WidgetEnum::Agenda(agenda) = widget {
+            if let Some(timezone) = &agenda.config.timezone {
+                if crate::widget::agenda::parse_timezone(timezone).is_none() {
+                    return Err(anyhow!(
+                        "agenda widget {} has unknown time zone {timezone}",
+                        agenda.id
+                    ));
+                }
+            }
+        }
+        if let WidgetEnum::Astronomy(astronomy) = widget {
+            let [latitude, longitude] = astronomy.config.location;
+            if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+                return Err(anyhow!(
+                    "astronomy widget {} has a location outside of [-90, 90], [-180, 180]",
+                    astronomy.id
+                ));
+            }
+            if let Some(timezone) = &astronomy.config.timezone {
+                if crate::widget::agenda::parse_timezone(timezone).is_none() {
+                    return Err(anyhow!(
+                        "astronomy widget {} has unknown time zone {timezone}",
+                        astronomy.id
+                    ));
+                }
+            }
+        }
+        if let WidgetEnum::Command(command) = widget {
+            if command.config.argv.is_empty() {
+                return Err(anyhow!("command widget {} needs an argv", command.id));
+            }
+        }
+        if let WidgetEnum::Fetch(fetch) = widget {
+            for field in &fetch.config.fields {
+                serde_json_path::JsonPath::parse(&field.path).map_err(|e| {
+                    anyhow!("invalid path {} in widget {}: {e}", field.path, fetch.id)
+                })?;
+            }
+        }
+        if let WidgetEnum::LogTail(log_tail) = widget {
+            for pattern in &log_tail.config.patterns {
+                regex::Regex::new(&pattern.regex).map_err(|e| {
+                    anyhow!(
+                        "invalid pattern {} in widget {}: {e}",
+                        pattern.name,
+                        log_tail.id
+                    )
+                })?;
+            }
+        }
+        if let WidgetEnum::Clothing(clothing) = widget {
+            if !clothing.depends_on.contains(&clothing.config.weather) {
+                return Err(anyhow!(
+                    "widget {} needs to depend on its weather widget {}",
+                    clothing.id,
+                    clothing.config.weather
+                ));
+            }
+        }
+    }
+
+    check_dependencies(&config.widgets)?;
+
+    for widget in &config.widgets {
+        if let Some(name) = widget
+            .secrets()
+            .iter()
+            .find(|name| !config.secrets.contains_key(*name))
+        {
+            return Err(anyhow!(
+                "widget {} uses undefined secret {name}",
+                widget.id()
+            ));
+        }
+    }
+
+    // make sure all schedules are valid before starting up
+    for widget in &config.widgets {
+        if let Some(schedule) = widget.schedule() {
+            scheduler::parse_cron(&schedule.cron)
+                .map_err(|e| anyhow!("invalid schedule for widget {}: {e}", widget.id()))?;
+            if schedule.retry.as_ref().is_some_and(|r| r.max_attempts == 0) {
+                return Err(anyhow!(
+                    "retry policy of widget {} needs at least one attempt",
+                    widget.id()
+                ));
+            }
+        }
+    }
+
+    // make sure the alert rules can be parsed and refer to existing widgets
+    for alert in &config.alerts.rules {
+        let rule: Rule = alert
+            .rule
+            .parse()
+            .map_err(|e| anyhow!("invalid alert rule `{}`: {e}", alert.name))?;
+
+        if !config.widgets.iter().any(|w| *w.id() == rule.widget) {
+            return Err(anyhow!(
+                "alert rule `{}` refers to unknown widget {}",
+                alert.name,
+                rule.widget
+            ));
+        }
+        config.alert_rules.push(rule);
+    }
+
+    for webhook in &config.webhooks {
+        if let Some(id) = webhook
+            .widgets
+            .iter()
+            .find(|id| !config.widgets.iter().any(|w| w.id() == *id))
+        {
+            return Err(anyhow!(
+                "webhook {} refers to unknown widget {id}",
+                webhook.url
+            ));
+        }
+    }
+
+    let mut names = HashSet::new();
+    for dashboard in &config.dashboards {
+        if !names.insert(&dashboard.name) {
+            return Err(anyhow!("dashboard {} is defined twice", dashboard.name));
+        }
+        if let Some(id) = dashboard
+            .widgets
+            .iter()
+            .find(|id| !config.widgets.iter().any(|w| w.id() == *id))
+        {
+            return Err(anyhow!(
+                "dashboard {} refers to unknown widget {id}",
+                dashboard.name
+            ));
+        }
+    }
 
     Ok(config)
 }
+
+/// Makes sure that all dependencies exist and that they do not form any cycles
+fn check_dependencies(widgets: &[WidgetEnum]) -> Result<(), anyhow::Error> {
+    let dependencies: HashMap<&WidgetId, &[WidgetId]> =
+        widgets.iter().map(|w| (w.id(), w.depends_on())).collect();
+
+    for widget in widgets {
+        if let Some(unknown) = widget
+            .depends_on()
+            .iter()
+            .find(|id| !dependencies.contains_key(id))
+        {
+            return Err(anyhow!(
+                "widget {} depends on unknown widget {unknown}",
+                widget.id()
+            ));
+        }
+    }
+
+    // depth first search, keeping the current path around to be able to report the cycle
+    fn visit<'a>(
+        id: &'a WidgetId,
+        dependencies: &HashMap<&'a WidgetId, &'a [WidgetId]>,
+        path: &mut Vec<&'a WidgetId>,
+        done: &mut HashSet<&'a WidgetId>,
+    ) -> Result<(), anyhow::Error> {
+        if done.contains(id) {
+            return Ok(());
+        }
+        if let Some(start) = path.iter().position(|p| *p == id) {
+            let cycle: Vec<String> = path[start..]
+                .iter()
+                .chain([&id])
+                .map(|id| id.to_string())
+                .collect();
+            return Err(anyhow!(
+                "widget dependencies form a cycle: {}",
+                cycle.join(" -> ")
+            ));
+        }
+
+        path.push(id);
+        for dependency in dependencies[id] {
+            visit(dependency, dependencies, path, done)?;
+        }
+        path.pop();
+        done.insert(id);
+
+        Ok(())
+    }
+
+    let mut done = HashSet::new();
+    for widget in widgets {
+        visit(widget.id(), &dependencies, &mut Vec::new(), &mut done)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A configuration of weather widgets, given as `(id, depends_on)`
+    fn widgets(widgets: &[(&str, &[&str])]) -> String {
+        let mut config = String::from("widgets:\n");
+        for (id, depends_on) in widgets {
+            config.push_str(&format!(
+                "  - !Weather\n    id: {id}\n    depends_on: [{}]\n    config:\n      location: [59.3, 18.1]\n",
+                depends_on.join(", ")
+            ));
+        }
+        config
+    }
+
+    fn error(config: &str) -> String {
+        parse_config(config).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn accepts_dependency_chain() {
+        let config = parse_config(&widgets(&[("a", &["b"]), ("b", &["c"]), ("c", &[])])).unwrap();
+        assert_eq!(config.widgets.len(), 3);
+    }
+
+    #[test]
+    fn rejects_cycles() {
+        assert_eq!(
+            error(&widgets(&[("a", &["b"]), ("b", &["a"])])),
+            "widget dependencies form a cycle: a -> b -> a"
+        );
+        assert_eq!(
+            error(&widgets(&[("a", &[]), ("b", &["b"])])),
+            "widget dependencies form a cycle: b -> b"
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_dependencies() {
+        assert_eq!(
+            error(&widgets(&[("a", &["b"]), ("b", &["missing"])])),
+            "widget b depends on unknown widget missing"
+        );
+    }
+}
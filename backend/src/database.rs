@@ -6,6 +6,7 @@ use common::WidgetId;
 use common::backend::{BackendRun, RunId};
 // TODO: make a database specific version of the BackendRun that has the run ID in it (its an implementation specification and not needed for other logic)
 
+#[derive(Debug)]
 pub enum DatabaseError {
     InvalidRunId,
     InvalidWidgetId,
@@ -26,6 +27,12 @@ pub trait Database {
 
     /// Returns the most recent (completed) run for the specified widget
     fn get_last_run(&self, widget_id: WidgetId) -> DatabaseResult<BackendRun>;
+
+    /// Returns the most recent run for the specified widget that did not fail
+    fn get_last_successful_run(&self, widget_id: WidgetId) -> DatabaseResult<BackendRun>;
+
+    /// Checks that the database can be used
+    fn ping(&self) -> DatabaseResult<()>;
 }
 
 pub struct InMemoryDatabase {
@@ -51,7 +58,7 @@ impl Database for InMemoryDatabase {
             .map(|runs| runs.iter().filter(|&run| run.id == run_id).collect())?;
 
         match hits.len() {
-            0 => Err(DatabaseError::NoneAvailable),
+            0 => Err(DatabaseError::InvalidRunId),
             1 => Ok((*hits.first().unwrap()).clone()),
             _ => panic!("Should not have multiple runs with same ID!"),
         }
@@ -82,4 +89,22 @@ impl Database for InMemoryDatabase {
             .ok_or(DatabaseError::InvalidWidgetId)
             .and_then(|runs| runs.last().ok_or(DatabaseError::NoneAvailable).cloned())
     }
+
+    fn get_last_successful_run(&self, widget_id: WidgetId) -> DatabaseResult<BackendRun> {
+        self.runs
+            .get(&widget_id)
+            .ok_or(DatabaseError::InvalidWidgetId)
+            .and_then(|runs| {
+                runs.iter()
+                    .rev()
+                    .find(|run| run.result.is_ok())
+                    .ok_or(DatabaseError::NoneAvailable)
+                    .cloned()
+            })
+    }
+
+    fn ping(&self) -> DatabaseResult<()> {
+        // nothing that can go wrong when everything is stored in memory
+        Ok(())
+    }
 }
@@ -0,0 +1,197 @@
+//! Computes how up to date the output of a widget is from its recent runs and configuration
+use chrono::prelude::*;
+use common::{
+    freshness::{Freshness, FreshnessState},
+    WidgetEnum,
+};
+
+use crate::{database::Database, scheduler};
+
+/// How long a scheduled run may take before the output is considered stale
+pub const SCHEDULE_GRACE: chrono::TimeDelta = chrono::TimeDelta::minutes(5);
+
+/// Returns when the output of a run that ended at `produced` starts aging and becomes stale
+fn deadlines(
+    widget: &WidgetEnum,
+    produced: DateTime<Utc>,
+) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    if let Some(minutes) = widget.max_age_minutes() {
+        let max_age = chrono::TimeDelta::minutes(minutes as i64);
+        return Some((produced + max_age / 2, produced + max_age));
+    }
+
+    // a heartbeat is aging when the next ping is due and stale when it is later than the grace period
+    if let WidgetEnum::Heartbeat(heartbeat) = widget {
+        let due = produced + chrono::TimeDelta::minutes(heartbeat.config.period_minutes as i64);
+        let grace = chrono::TimeDelta::minutes(heartbeat.config.grace_minutes as i64);
+        return Some((due, due + grace));
+    }
+
+    // the output is aging when the next scheduled run is due and stale if that run does not succeed
+    let cron = scheduler::parse_cron(&widget.schedule()?.cron).ok()?;
+    let due = scheduler::next_occurrence(&cron, produced)?;
+    Some((due, due + SCHEDULE_GRACE))
+}
+
+/// Computes the freshness of a widget from its runs in the database
+pub fn compute(db: &dyn Database, widget: &WidgetEnum, now: DateTime<Utc>) -> Freshness {
+    let last_run = db.get_last_run(widget.id().clone()).ok();
+    let produced = db
+        .get_last_successful_run(widget.id().clone())
+        .ok()
+        .map(|run| run.ended);
+    let deadlines = produced.and_then(|produced| deadlines(widget, produced));
+
+    let state = match (last_run, produced, deadlines) {
+        (Some(run), _, _) if run.result.is_err() => FreshnessState::Failed,
+        (_, None, _) => FreshnessState::Stale,
+        (_, Some(_), Some((_, stale_at))) if now >= stale_at => FreshnessState::Stale,
+        (_, Some(_), Some((aging_at, _))) if now >= aging_at => FreshnessState::Aging,
+        _ => FreshnessState::Fresh,
+    };
+
+    Freshness {
+        widget: widget.id().clone(),
+        state,
+        last_success: produced,
+        age_seconds: produced.map(|produced| (now - produced).num_seconds()),
+        stale_at: deadlines.map(|(_, stale_at)| stale_at),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::backend::BackendError;
+
+    use super::*;
+    use crate::{database::InMemoryDatabase, widget::test_run};
+
+    fn widget(yaml: &str) -> WidgetEnum {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    fn clothing(extra: &str) -> WidgetEnum {
+        widget(&format!(
+            "!Clothing
+id: clothing
+{extra}
+config:
+  weather: weather"
+        ))
+    }
+
+    fn time(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 1, hour, minute, 0).unwrap()
+    }
+
+    /// Stores a run of the widget that ended at `ended`
+    fn insert(
+        db: &mut InMemoryDatabase,
+        widget: &WidgetEnum,
+        ended: DateTime<Utc>,
+        result: Result<Option<String>, BackendError>,
+    ) {
+        let run = test_run(&widget.id().to_string(), ended, result);
+        db.insert_run(widget.id().clone(), run).unwrap();
+    }
+
+    #[test]
+    fn max_age_takes_precedence() {
+        let widget = clothing(
+            "max_age_minutes: 30
+schedule:
+  cron: \"0 0 * * *\"",
+        );
+        assert_eq!(
+            deadlines(&widget, time(12, 0)),
+            Some((time(12, 15), time(12, 30)))
+        );
+    }
+
+    #[test]
+    fn scheduled_widget_is_due_at_next_run() {
+        let scheduled = clothing(
+            "schedule:
+  cron: \"0 * * * *\"",
+        );
+        // cron is evaluated in the local time zone of the backend
+        let local = |hour, minute| {
+            Local
+                .with_ymd_and_hms(2024, 1, 1, hour, minute, 0)
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        assert_eq!(
+            deadlines(&scheduled, local(12, 20)),
+            Some((local(13, 0), local(13, 0) + SCHEDULE_GRACE))
+        );
+
+        assert_eq!(deadlines(&clothing(""), time(12, 20)), None);
+    }
+
+    #[test]
+    fn freshness_follows_runs() {
+        let widget = clothing("max_age_minutes: 60");
+        let mut db = InMemoryDatabase::new();
+        let state = |db: &InMemoryDatabase, now| compute(db, &widget, now).state;
+
+        // never run
+        assert_eq!(state(&db, time(12, 0)), FreshnessState::Stale);
+
+        insert(&mut db, &widget, time(12, 0), Ok(Some("{}".into())));
+        assert_eq!(state(&db, time(12, 29)), FreshnessState::Fresh);
+        assert_eq!(state(&db, time(12, 30)), FreshnessState::Aging);
+        assert_eq!(state(&db, time(13, 0)), FreshnessState::Stale);
+
+        let freshness = compute(&db, &widget, time(13, 10));
+        assert_eq!(freshness.last_success, Some(time(12, 0)));
+        assert_eq!(freshness.age_seconds, Some(70 * 60));
+        assert_eq!(freshness.stale_at, Some(time(13, 0)));
+
+        // a failed run is shown as such, even though the output is still fresh
+        insert(
+            &mut db,
+            &widget,
+            time(12, 10),
+            Err(BackendError::Transient("offline".into())),
+        );
+        assert_eq!(state(&db, time(12, 11)), FreshnessState::Failed);
+    }
+
+    fn heartbeat() -> WidgetEnum {
+        widget(
+            "!Heartbeat
+id: backup
+config:
+  token: secret
+  period_minutes: 60
+  grace_minutes: 10",
+        )
+    }
+
+    #[test]
+    fn heartbeat_is_due_after_period_and_stale_after_grace() {
+        assert_eq!(
+            deadlines(&heartbeat(), time(12, 0)),
+            Some((time(13, 0), time(13, 10)))
+        );
+    }
+
+    #[test]
+    fn heartbeat_freshness_follows_pings() {
+        let heartbeat = heartbeat();
+        let mut db = InMemoryDatabase::new();
+        let state = |db: &InMemoryDatabase, now| compute(db, &heartbeat, now).state;
+
+        // never pinged
+        assert_eq!(state(&db, time(12, 0)), FreshnessState::Stale);
+
+        insert(&mut db, &heartbeat, time(12, 0), Ok(None));
+        assert_eq!(state(&db, time(12, 59)), FreshnessState::Fresh);
+        assert_eq!(state(&db, time(13, 5)), FreshnessState::Aging);
+        assert_eq!(state(&db, time(13, 10)), FreshnessState::Stale);
+
+        insert(&mut db, &heartbeat, time(13, 30), Ok(None));
+        assert_eq!(state(&db, time(13, 31)), FreshnessState::Fresh);
+    }
+}
@@ -0,0 +1,267 @@
+//! Liveness and readiness probes for orchestrators
+use std::{collections::BTreeMap, path::Path, sync::atomic::Ordering, time::Duration};
+
+use chrono::prelude::*;
+use common::WidgetId;
+use serde::Serialize;
+
+use crate::{api::AppState, freshness::SCHEDULE_GRACE, scheduler};
+
+/// How long to wait for the database lock before considering it unreachable
+const DATABASE_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Serialize)]
+pub struct ComponentStatus {
+    pub ok: bool,
+    pub detail: String,
+}
+
+impl ComponentStatus {
+    fn new(ok: bool, detail: impl Into<String>) -> Self {
+        Self {
+            ok,
+            detail: detail.into(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct WidgetStatus {
+    pub id: WidgetId,
+    pub schedule: Option<String>,
+    /// When the last successful run ended
+    pub last_success: Option<DateTime<Utc>>,
+    /// The most recent scheduled time the widget should have been run at
+    pub expected_since: Option<DateTime<Utc>>,
+    /// Not run yet, but its first scheduled run since the backend started is not due yet either
+    pub pending: bool,
+    /// No successful run since the last time it should have run according to the schedule
+    pub stale: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Readiness {
+    pub ready: bool,
+    pub components: BTreeMap<&'static str, ComponentStatus>,
+    /// Informational only, stale widgets do not affect readiness
+    pub widgets: Vec<WidgetStatus>,
+}
+
+pub async fn check_readiness(state: &AppState, static_dir: &Path) -> Readiness {
+    let mut components = BTreeMap::new();
+
+    components.insert(
+        "config",
+        ComponentStatus::new(true, format!("{} widgets", state.widgets.len())),
+    );
+
+    let database = match tokio::time::timeout(DATABASE_TIMEOUT, state.db.read()).await {
+        Ok(db) => match db.ping() {
+            Ok(()) => ComponentStatus::new(true, "reachable"),
+            Err(_) => ComponentStatus::new(false, "ping failed"),
+        },
+        Err(_) => ComponentStatus::new(false, "timed out waiting for database lock"),
+    };
+    components.insert("database", database);
+
+    let scheduler_running = state.scheduler_running.load(Ordering::SeqCst);
+    components.insert(
+        "scheduler",
+        ComponentStatus::new(
+            scheduler_running,
+            if scheduler_running {
+                "running"
+            } else {
+                "not running"
+            },
+        ),
+    );
+
+    let accepting = !state.shutdown.is_cancelled();
+    components.insert(
+        "runs",
+        ComponentStatus::new(
+            accepting,
+            if accepting {
+                "accepting new runs"
+            } else {
+                "shutting down"
+            },
+        ),
+    );
+
+    let index = static_dir.join("index.html");
+    let static_files = match tokio::fs::try_exists(&index).await {
+        Ok(true) => ComponentStatus::new(true, format!("{} present", index.display())),
+        _ => ComponentStatus::new(false, format!("{} missing", index.display())),
+    };
+    components.insert("static_files", static_files);
+
+    let widgets = widget_statuses(state, Utc::now()).await;
+
+    Readiness {
+        ready: components.values().all(|c| c.ok),
+        components,
+        widgets,
+    }
+}
+
+async fn widget_statuses(state: &AppState, now: DateTime<Utc>) -> Vec<WidgetStatus> {
+    let db = state.db.read().await;
+
+    state
+        .widgets
+        .iter()
+        .map(|widget| {
+            let id = widget.id().clone();
+            let last_success = db
+                .get_last_successful_run(id.clone())
+                .ok()
+                .map(|run| run.ended);
+
+            let schedule = widget.schedule().map(|s| s.cron.clone());
+            let cron = schedule
+                .as_deref()
+                .and_then(|cron| scheduler::parse_cron(cron).ok());
+            let expected_since = cron
+                .as_ref()
+                .and_then(|cron| scheduler::previous_occurrence(cron, now - SCHEDULE_GRACE));
+            let first_due = cron
+                .as_ref()
+                .and_then(|cron| scheduler::next_occurrence(cron, state.started))
+                .map(|first| first + SCHEDULE_GRACE);
+
+            let pending = db.get_last_run(id.clone()).is_err()
+                && first_due.is_some_and(|first_due| now < first_due);
+            let stale = match (expected_since, last_success) {
+                _ if pending => false,
+                (Some(expected), Some(last)) => last < expected,
+                (Some(_), None) => true,
+                (None, _) => false,
+            };
+
+            WidgetStatus {
+                id,
+                schedule,
+                last_success,
+                expected_since,
+                pending,
+                stale,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+    use common::backend::BackendError;
+
+    use super::*;
+    use crate::widget::test_run;
+
+    const CONFIG: &str = "
+widgets:
+  - !Weather
+    id: yearly
+    schedule:
+      cron: \"0 0 1 1 *\"
+    config:
+      location: [59.3, 18.1]
+  - !Weather
+    id: manual
+    config:
+      location: [59.3, 18.1]
+";
+
+    fn status<'a>(statuses: &'a [WidgetStatus], id: &str) -> &'a WidgetStatus {
+        statuses.iter().find(|s| s.id.to_string() == id).unwrap()
+    }
+
+    async fn insert(
+        state: &AppState,
+        ended: DateTime<Utc>,
+        result: Result<Option<String>, BackendError>,
+    ) {
+        let run = test_run("yearly", ended, result);
+        let mut db = state.db.write().await;
+        db.insert_run(run.widget.clone(), run).unwrap();
+    }
+
+    #[tokio::test]
+    async fn ready_when_all_components_are() {
+        let state = AppState::for_test(CONFIG);
+        let dir = std::env::temp_dir().join(format!("health_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let readiness = check_readiness(&state, &dir).await;
+        assert!(!readiness.ready);
+        assert!(!readiness.components["scheduler"].ok);
+        assert!(!readiness.components["static_files"].ok);
+        assert!(readiness.components["database"].ok);
+        assert_eq!(readiness.components["config"].detail, "2 widgets");
+
+        std::fs::write(dir.join("index.html"), "").unwrap();
+        state.scheduler_running.store(true, Ordering::SeqCst);
+        let readiness = check_readiness(&state, &dir).await;
+        assert!(readiness.ready);
+        assert_eq!(readiness.widgets.len(), 2);
+
+        state.shutdown.cancel();
+        let readiness = check_readiness(&state, &dir).await;
+        assert!(!readiness.ready);
+        assert_eq!(readiness.components["runs"].detail, "shutting down");
+    }
+
+    #[tokio::test]
+    async fn pending_until_first_scheduled_run_is_due() {
+        let state = AppState::for_test(CONFIG);
+        let cron = scheduler::parse_cron("0 0 1 1 *").unwrap();
+        let first = scheduler::next_occurrence(&cron, state.started).unwrap();
+
+        let statuses = widget_statuses(&state, state.started + TimeDelta::minutes(1)).await;
+        assert!(status(&statuses, "yearly").pending);
+        assert!(!status(&statuses, "yearly").stale);
+        // widgets without a schedule are never pending nor stale
+        assert!(!status(&statuses, "manual").pending);
+        assert!(!status(&statuses, "manual").stale);
+
+        let after_first = first + SCHEDULE_GRACE + TimeDelta::minutes(1);
+        let statuses = widget_statuses(&state, after_first).await;
+        let yearly = status(&statuses, "yearly");
+        assert!(!yearly.pending);
+        assert!(yearly.stale);
+        assert_eq!(yearly.expected_since, Some(first));
+    }
+
+    #[tokio::test]
+    async fn stale_without_success_since_expected() {
+        let state = AppState::for_test(CONFIG);
+        let cron = scheduler::parse_cron("0 0 1 1 *").unwrap();
+        let first = scheduler::next_occurrence(&cron, state.started).unwrap();
+        let after_first = first + SCHEDULE_GRACE + TimeDelta::minutes(1);
+
+        // a failed run is no longer pending
+        insert(
+            &state,
+            state.started,
+            Err(BackendError::Transient("offline".into())),
+        )
+        .await;
+        let statuses = widget_statuses(&state, state.started + TimeDelta::minutes(1)).await;
+        assert!(!status(&statuses, "yearly").pending);
+        assert!(status(&statuses, "yearly").stale);
+
+        insert(&state, first, Ok(None)).await;
+        let statuses = widget_statuses(&state, after_first).await;
+        let yearly = status(&statuses, "yearly");
+        assert!(!yearly.stale);
+        assert_eq!(yearly.last_success, Some(first));
+
+        // an earlier success does not count once the next run is expected
+        let next = scheduler::next_occurrence(&cron, first).unwrap();
+        let statuses = widget_statuses(&state, next + SCHEDULE_GRACE + TimeDelta::minutes(1)).await;
+        assert!(status(&statuses, "yearly").stale);
+    }
+}
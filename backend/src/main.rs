@@ -2,9 +2,18 @@
 
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+mod alert;
 mod api;
 mod config;
 mod database;
+mod freshness;
+mod health;
+mod metrics;
+mod notification;
+mod scheduler;
+mod secrets;
+mod shutdown;
+mod webhook;
 mod widget;
 
 #[tokio::main]
@@ -12,7 +21,7 @@ async fn main() -> anyhow::Result<()> {
     tracing_subscriber::registry()
         .with(
             tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "example_key_value_store=debug,tower_http=debug".into()),
+                .unwrap_or_else(|_| "backend=debug,tower_http=debug".into()),
         )
         .with(tracing_subscriber::fmt::layer())
         .init();
@@ -0,0 +1,205 @@
+//! Prometheus metrics for widget runs and the HTTP API.
+//!
+//! Everything is registered in a private [`Registry`] and exposed in the text format on `/metrics`.
+//! A widget that has not succeeded within the last hour can be alerted on with e.g.:
+//!
+//! ```text
+//! time() - dashboard_widget_last_success_timestamp_seconds > 3600
+//! ```
+use std::time::Duration;
+
+use common::backend::BackendRun;
+use prometheus::{
+    Encoder, GaugeVec, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+
+pub struct Metrics {
+    registry: Registry,
+    runs_total: IntCounterVec,
+    run_duration: HistogramVec,
+    last_success: GaugeVec,
+    queue_depth: IntGauge,
+    http_requests_total: IntCounterVec,
+    http_request_duration: HistogramVec,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry =
+            Registry::new_custom(Some("dashboard".into()), None).expect("valid registry prefix");
+
+        let runs_total = IntCounterVec::new(
+            Opts::new("widget_runs_total", "Number of completed widget runs"),
+            &["widget", "initiator", "outcome"],
+        )
+        .unwrap();
+        let run_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "widget_run_duration_seconds",
+                "Time spent executing a widget run",
+            ),
+            &["widget"],
+        )
+        .unwrap();
+        let last_success = GaugeVec::new(
+            Opts::new(
+                "widget_last_success_timestamp_seconds",
+                "Unix timestamp of when the widget last finished a successful run",
+            ),
+            &["widget"],
+        )
+        .unwrap();
+        let queue_depth = IntGauge::new(
+            "widget_run_queue_depth",
+            "Number of widget runs that have been requested but not yet stored",
+        )
+        .unwrap();
+        let http_requests_total = IntCounterVec::new(
+            Opts::new("http_requests_total", "Number of handled HTTP requests"),
+            &["method", "path", "status"],
+        )
+        .unwrap();
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "Time spent handling HTTP requests",
+            ),
+            &["method", "path"],
+        )
+        .unwrap();
+
+        registry.register(Box::new(runs_total.clone())).unwrap();
+        registry.register(Box::new(run_duration.clone())).unwrap();
+        registry.register(Box::new(last_success.clone())).unwrap();
+        registry.register(Box::new(queue_depth.clone())).unwrap();
+        registry
+            .register(Box::new(http_requests_total.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(http_request_duration.clone()))
+            .unwrap();
+
+        Self {
+            registry,
+            runs_total,
+            run_duration,
+            last_success,
+            queue_depth,
+            http_requests_total,
+            http_request_duration,
+        }
+    }
+
+    /// Record a finished run
+    pub fn observe_run(&self, run: &BackendRun) {
+        let widget = run.widget.to_string();
+        let initiator = format!("{:?}", run.initiated);
+        let outcome = if run.result.is_ok() {
+            "success"
+        } else {
+            "failure"
+        };
+
+        self.runs_total
+            .with_label_values(&[widget.as_str(), initiator.as_str(), outcome])
+            .inc();
+
+        let duration = (run.ended - run.started).to_std().unwrap_or_default();
+        self.run_duration
+            .with_label_values(&[widget.as_str()])
+            .observe(duration.as_secs_f64());
+
+        if run.result.is_ok() {
+            self.last_success
+                .with_label_values(&[widget.as_str()])
+                .set(run.ended.timestamp_millis() as f64 / 1000.0);
+        }
+    }
+
+    /// Tracks a requested run until the returned guard is dropped
+    pub fn enqueue_run(&self) -> QueueGuard<'_> {
+        self.queue_depth.inc();
+        QueueGuard(&self.queue_depth)
+    }
+
+    /// Record a handled HTTP request. The `path` should be the route template to keep the label cardinality bounded
+    pub fn observe_http(&self, method: &str, path: &str, status: &str, elapsed: Duration) {
+        self.http_requests_total
+            .with_label_values(&[method, path, status])
+            .inc();
+        self.http_request_duration
+            .with_label_values(&[method, path])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    /// Encode all metrics in the Prometheus text format
+    pub fn encode(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .expect("encoding metrics");
+        String::from_utf8(buffer).expect("metrics are valid utf8")
+    }
+}
+
+/// Decrements the queue depth when dropped
+pub struct QueueGuard<'a>(&'a IntGauge);
+
+impl Drop for QueueGuard<'_> {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, TimeZone, Utc};
+    use common::backend::BackendError;
+
+    use super::*;
+    use crate::widget::test_run;
+
+    #[test]
+    fn encodes_runs() {
+        let metrics = Metrics::new();
+        let ended = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+
+        let mut success = test_run("weather", ended, Ok(None));
+        success.started = ended - TimeDelta::milliseconds(200);
+        metrics.observe_run(&success);
+        let failure = test_run(
+            "weather",
+            ended,
+            Err(BackendError::Transient("offline".into())),
+        );
+        metrics.observe_run(&failure);
+
+        let text = metrics.encode();
+        for line in [
+            r#"dashboard_widget_runs_total{initiator="Schedule",outcome="success",widget="weather"} 1"#,
+            r#"dashboard_widget_runs_total{initiator="Schedule",outcome="failure",widget="weather"} 1"#,
+            r#"dashboard_widget_run_duration_seconds_count{widget="weather"} 2"#,
+            r#"dashboard_widget_run_duration_seconds_sum{widget="weather"} 0.2"#,
+            r#"dashboard_widget_run_duration_seconds_bucket{widget="weather",le="0.1"} 1"#,
+            r#"dashboard_widget_run_duration_seconds_bucket{widget="weather",le="0.25"} 2"#,
+            r#"dashboard_widget_last_success_timestamp_seconds{widget="weather"} 1704110400"#,
+            "dashboard_widget_run_queue_depth 0",
+        ] {
+            assert!(text.lines().any(|l| l == line), "missing {line} in\n{text}");
+        }
+    }
+
+    #[test]
+    fn tracks_queue_depth() {
+        let metrics = Metrics::new();
+        let guard = metrics.enqueue_run();
+        assert!(metrics
+            .encode()
+            .contains("dashboard_widget_run_queue_depth 1\n"));
+        drop(guard);
+        assert!(metrics
+            .encode()
+            .contains("dashboard_widget_run_queue_depth 0\n"));
+    }
+}
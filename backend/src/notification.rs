@@ -0,0 +1,510 @@
+//! Storage, de-duplication and delivery of the notifications raised by widgets
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Write},
+    net::TcpStream,
+    sync::Arc,
+};
+
+use anyhow::{anyhow, bail};
+use chrono::prelude::*;
+use common::{
+    notification::{Level, Notification, NotificationId},
+    WidgetId,
+};
+use serde::Deserialize;
+use tokio::sync::RwLock;
+
+use crate::{
+    webhook::{delivery_agent, DELIVERY_TIMEOUT},
+    widget::RaisedNotification,
+};
+
+/// At most this many notifications are kept, the oldest acknowledged ones are removed first
+const MAX_NOTIFICATIONS: usize = 1000;
+
+#[derive(Debug, Deserialize)]
+pub struct NotificationConfig {
+    /// The same notification is not delivered again within this many minutes
+    #[serde(default = "default_cooldown_minutes")]
+    pub cooldown_minutes: i64,
+
+    /// Where to deliver the notifications, the frontend always shows them regardless
+    #[serde(default)]
+    pub sinks: Vec<SinkConfig>,
+}
+
+fn default_cooldown_minutes() -> i64 {
+    15
+}
+
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        Self {
+            cooldown_minutes: default_cooldown_minutes(),
+            sinks: Vec::new(),
+        }
+    }
+}
+
+fn default_min_level() -> Level {
+    Level::Warning
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub enum SinkConfig {
+    /// POST the notification as JSON to an URL
+    Webhook {
+        url: String,
+        #[serde(default = "default_min_level")]
+        min_level: Level,
+    },
+    /// Send an email through an SMTP relay that does not require authentication or TLS (e.g. a local one)
+    Smtp {
+        /// Address of the relay, e.g. `localhost:25`
+        server: String,
+        from: String,
+        to: Vec<String>,
+        #[serde(default = "default_min_level")]
+        min_level: Level,
+    },
+}
+
+/// Something that can deliver notifications to the outside world
+pub trait NotificationSink: Send + Sync {
+    fn name(&self) -> String;
+
+    /// Notifications less severe than this are not delivered to this sink
+    fn min_level(&self) -> Level;
+
+    /// Deliver the notification, this is allowed to block
+    fn deliver(&self, notification: &Notification) -> anyhow::Result<()>;
+}
+
+pub struct WebhookSink {
+    url: String,
+    min_level: Level,
+    agent: ureq::Agent,
+}
+
+impl WebhookSink {
+    pub fn new(url: String, min_level: Level) -> Self {
+        Self {
+            url,
+            min_level,
+            agent: delivery_agent(),
+        }
+    }
+}
+
+impl NotificationSink for WebhookSink {
+    fn name(&self) -> String {
+        format!("webhook {}", self.url)
+    }
+
+    fn min_level(&self) -> Level {
+        self.min_level
+    }
+
+    fn deliver(&self, notification: &Notification) -> anyhow::Result<()> {
+        self.agent.post(&self.url).send_json(notification)?;
+        Ok(())
+    }
+}
+
+pub struct SmtpSink {
+    server: String,
+    from: String,
+    to: Vec<String>,
+    min_level: Level,
+}
+
+impl SmtpSink {
+    fn read_reply(reader: &mut impl BufRead, expected: u16) -> anyhow::Result<()> {
+        // replies can span multiple lines, all but the last one have a '-' after the code
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 {
+                bail!("connection closed by server");
+            }
+            let code: u16 = line
+                .get(..3)
+                .and_then(|c| c.parse().ok())
+                .ok_or_else(|| anyhow!("malformed reply: {}", line.trim_end()))?;
+
+            if line.as_bytes().get(3) == Some(&b'-') {
+                continue;
+            }
+            if code != expected {
+                bail!("unexpected reply: {}", line.trim_end());
+            }
+            return Ok(());
+        }
+    }
+
+    fn command(
+        stream: &mut TcpStream,
+        reader: &mut impl BufRead,
+        command: &str,
+        expected: u16,
+    ) -> anyhow::Result<()> {
+        stream.write_all(command.as_bytes())?;
+        stream.write_all(b"\r\n")?;
+        Self::read_reply(reader, expected)
+    }
+
+    fn message(&self, notification: &Notification) -> String {
+        let mut message = format!(
+            "From: {}\r\nTo: {}\r\nSubject: [{:?}] {}: {}\r\nDate: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n",
+            self.from,
+            self.to.join(", "),
+            notification.level,
+            notification.widget,
+            // a line break would end the header and let the title add headers of its own
+            notification.title.replace(['\r', '\n'], " "),
+            notification.created.to_rfc2822(),
+        );
+
+        for line in notification.body.lines() {
+            // lines starting with a dot need to be escaped so they are not mistaken for the end of the data
+            if line.starts_with('.') {
+                message.push('.');
+            }
+            message.push_str(line);
+            message.push_str("\r\n");
+        }
+        message.push('.');
+
+        message
+    }
+}
+
+impl NotificationSink for SmtpSink {
+    fn name(&self) -> String {
+        format!("smtp {}", self.server)
+    }
+
+    fn min_level(&self) -> Level {
+        self.min_level
+    }
+
+    fn deliver(&self, notification: &Notification) -> anyhow::Result<()> {
+        let mut stream = TcpStream::connect(&self.server)?;
+        stream.set_read_timeout(Some(DELIVERY_TIMEOUT))?;
+        stream.set_write_timeout(Some(DELIVERY_TIMEOUT))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+
+        Self::read_reply(&mut reader, 220)?;
+        Self::command(&mut stream, &mut reader, "HELO dashboard", 250)?;
+        Self::command(
+            &mut stream,
+            &mut reader,
+            &format!("MAIL FROM:<{}>", self.from),
+            250,
+        )?;
+        for to in &self.to {
+            Self::command(&mut stream, &mut reader, &format!("RCPT TO:<{to}>"), 250)?;
+        }
+        Self::command(&mut stream, &mut reader, "DATA", 354)?;
+        Self::command(&mut stream, &mut reader, &self.message(notification), 250)?;
+        Self::command(&mut stream, &mut reader, "QUIT", 221)?;
+
+        Ok(())
+    }
+}
+
+/// Notifications are considered the same if they have the same origin, level and title
+type DedupKey = (WidgetId, Level, String);
+
+pub struct NotificationStore {
+    notifications: Vec<Notification>,
+    next_id: usize,
+    last_delivered: HashMap<DedupKey, DateTime<Utc>>,
+}
+
+impl NotificationStore {
+    pub fn new() -> Self {
+        Self {
+            notifications: Vec::new(),
+            next_id: 0,
+            last_delivered: HashMap::new(),
+        }
+    }
+
+    /// Stores a raised notification and returns it if it should be delivered.
+    ///
+    /// If the same notification is still unacknowledged it is updated instead of creating a new one,
+    /// and a new notification is only delivered if the same one was not delivered within `cooldown`.
+    pub fn insert(
+        &mut self,
+        widget: &WidgetId,
+        raised: RaisedNotification,
+        now: DateTime<Utc>,
+        cooldown: chrono::TimeDelta,
+    ) -> Option<Notification> {
+        if let Some(existing) = self.notifications.iter_mut().find(|n| {
+            !n.acknowledged
+                && n.widget == *widget
+                && n.level == raised.level
+                && n.title == raised.title
+        }) {
+            existing.body = raised.body;
+            existing.last_seen = now;
+            existing.occurrences += 1;
+            return None;
+        }
+
+        let notification = Notification {
+            id: NotificationId(self.next_id),
+            widget: widget.clone(),
+            level: raised.level,
+            title: raised.title,
+            body: raised.body,
+            created: now,
+            last_seen: now,
+            occurrences: 1,
+            acknowledged: false,
+        };
+        self.next_id += 1;
+        self.notifications.push(notification.clone());
+        self.prune();
+
+        // deliveries before the cooldown no longer matter
+        self.last_delivered.retain(|_, last| now - *last < cooldown);
+        let key = (
+            widget.clone(),
+            notification.level,
+            notification.title.clone(),
+        );
+        match self.last_delivered.get(&key) {
+            Some(last) if now - *last < cooldown => None,
+            _ => {
+                self.last_delivered.insert(key, now);
+                Some(notification)
+            }
+        }
+    }
+
+    /// Removes the oldest notifications, acknowledged ones first, until at most [`MAX_NOTIFICATIONS`] are left
+    fn prune(&mut self) {
+        while self.notifications.len() > MAX_NOTIFICATIONS {
+            let oldest = self
+                .notifications
+                .iter()
+                .position(|n| n.acknowledged)
+                .unwrap_or(0);
+            self.notifications.remove(oldest);
+        }
+    }
+
+    /// Returns the notifications, optionally only for one widget, newest first
+    pub fn list(&self, widget: Option<&WidgetId>) -> Vec<Notification> {
+        self.notifications
+            .iter()
+            .rev()
+            .filter(|n| widget.is_none_or(|w| n.widget == *w))
+            .cloned()
+            .collect()
+    }
+
+    pub fn acknowledge(&mut self, id: NotificationId) -> Option<Notification> {
+        let notification = self.notifications.iter_mut().find(|n| n.id == id)?;
+        notification.acknowledged = true;
+        Some(notification.clone())
+    }
+}
+
+/// Stores the raised notifications and delivers them to the configured sinks
+pub struct Notifier {
+    pub store: RwLock<NotificationStore>,
+    sinks: Vec<Arc<dyn NotificationSink>>,
+    cooldown: chrono::TimeDelta,
+}
+
+impl Notifier {
+    pub fn new(config: &NotificationConfig) -> Self {
+        let sinks = config
+            .sinks
+            .iter()
+            .map(|sink| -> Arc<dyn NotificationSink> {
+                match sink.clone() {
+                    SinkConfig::Webhook { url, min_level } => {
+                        Arc::new(WebhookSink::new(url, min_level))
+                    }
+                    SinkConfig::Smtp {
+                        server,
+                        from,
+                        to,
+                        min_level,
+                    } => Arc::new(SmtpSink {
+                        server,
+                        from,
+                        to,
+                        min_level,
+                    }),
+                }
+            })
+            .collect();
+
+        Self {
+            store: RwLock::new(NotificationStore::new()),
+            sinks,
+            cooldown: chrono::TimeDelta::minutes(config.cooldown_minutes),
+        }
+    }
+
+    /// Stores the notifications raised during a run, returns the ones that should be delivered
+    pub async fn publish(
+        &self,
+        widget: &WidgetId,
+        raised: Vec<RaisedNotification>,
+    ) -> Vec<Notification> {
+        let mut store = self.store.write().await;
+        let now = Utc::now();
+
+        raised
+            .into_iter()
+            .filter_map(|r| store.insert(widget, r, now, self.cooldown))
+            .collect()
+    }
+
+    /// Delivers a notification to all sinks that accept its level
+    pub async fn deliver(&self, notification: Notification) {
+        let notification = Arc::new(notification);
+
+        for sink in self
+            .sinks
+            .iter()
+            .filter(|s| notification.level >= s.min_level())
+        {
+            let name = sink.name();
+            let sink = sink.clone();
+            let n = notification.clone();
+            let result = tokio::task::spawn_blocking(move || sink.deliver(&n)).await;
+
+            match result {
+                Ok(Ok(())) => {}
+                Ok(Err(err)) => tracing::warn!(
+                    "could not deliver notification {:?} to {name}: {err}",
+                    notification.id,
+                ),
+                Err(err) => tracing::warn!("notification delivery panicked: {err}"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use super::*;
+
+    fn raised(title: &str) -> RaisedNotification {
+        RaisedNotification {
+            level: Level::Warning,
+            title: title.to_string(),
+            body: "body".to_string(),
+        }
+    }
+
+    fn widget() -> WidgetId {
+        "disk".parse().unwrap()
+    }
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap()
+    }
+
+    const COOLDOWN: TimeDelta = TimeDelta::minutes(15);
+
+    #[test]
+    fn updates_unacknowledged_duplicate() {
+        let mut store = NotificationStore::new();
+        let first = store.insert(&widget(), raised("disk full"), now(), COOLDOWN);
+        assert!(first.is_some());
+
+        let later = now() + TimeDelta::minutes(1);
+        let duplicate = store.insert(&widget(), raised("disk full"), later, COOLDOWN);
+        assert!(duplicate.is_none());
+
+        let list = store.list(None);
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].occurrences, 2);
+        assert_eq!(list[0].last_seen, later);
+        assert_eq!(list[0].created, now());
+
+        // a different title is a different notification
+        assert!(store
+            .insert(&widget(), raised("disk slow"), later, COOLDOWN)
+            .is_some());
+        assert_eq!(store.list(None).len(), 2);
+    }
+
+    #[test]
+    fn delivers_again_after_cooldown() {
+        let mut store = NotificationStore::new();
+        let first = store
+            .insert(&widget(), raised("disk full"), now(), COOLDOWN)
+            .unwrap();
+        store.acknowledge(first.id);
+
+        // acknowledged, so a new notification is stored but not delivered within the cooldown
+        let within = now() + TimeDelta::minutes(10);
+        assert!(store
+            .insert(&widget(), raised("disk full"), within, COOLDOWN)
+            .is_none());
+        assert_eq!(store.list(None).len(), 2);
+        store.acknowledge(store.list(None)[0].id);
+
+        let after = now() + TimeDelta::minutes(15);
+        let delivered = store.insert(&widget(), raised("disk full"), after, COOLDOWN);
+        assert_eq!(delivered.map(|n| n.created), Some(after));
+    }
+
+    #[test]
+    fn removes_acknowledged_notifications_first() {
+        let mut store = NotificationStore::new();
+        for i in 0..MAX_NOTIFICATIONS {
+            let notification = store.insert(&widget(), raised(&format!("{i}")), now(), COOLDOWN);
+            if i == 5 {
+                store.acknowledge(notification.unwrap().id);
+            }
+        }
+        store.insert(&widget(), raised("one too many"), now(), COOLDOWN);
+
+        let list = store.list(None);
+        assert_eq!(list.len(), MAX_NOTIFICATIONS);
+        assert!(list.iter().all(|n| n.title != "5"));
+        assert_eq!(list[MAX_NOTIFICATIONS - 1].title, "0");
+
+        // without acknowledged ones the oldest is removed
+        store.insert(&widget(), raised("another one"), now(), COOLDOWN);
+        assert_eq!(store.list(None)[MAX_NOTIFICATIONS - 1].title, "1");
+    }
+
+    #[test]
+    fn strips_line_breaks_from_subject() {
+        let sink = SmtpSink {
+            server: "localhost:25".to_string(),
+            from: "dashboard@example.com".to_string(),
+            to: vec!["ops@example.com".to_string()],
+            min_level: Level::Warning,
+        };
+        let mut store = NotificationStore::new();
+        let notification = store
+            .insert(
+                &widget(),
+                raised("disk full\r\nBcc: someone@example.com"),
+                now(),
+                COOLDOWN,
+            )
+            .unwrap();
+
+        let message = sink.message(&notification);
+        assert!(
+            message.contains("Subject: [Warning] disk: disk full  Bcc: someone@example.com\r\n")
+        );
+        assert!(!message.contains("\r\nBcc:"));
+    }
+}
@@ -0,0 +1,211 @@
+//! Runs widgets automatically according to their [`Schedule`](common::Schedule) or when requested
+//! by other parts of the backend (e.g. chained runs of dependent widgets)
+use std::sync::{atomic::Ordering, Arc};
+
+use chrono::prelude::*;
+use common::{
+    backend::{Initiator, RunId},
+    WidgetId,
+};
+use croner::{errors::CronError, parser::CronParser, Cron};
+use tokio::sync::mpsc::UnboundedReceiver;
+
+use crate::api::AppState;
+
+pub fn parse_cron(pattern: &str) -> Result<Cron, CronError> {
+    CronParser::new().parse(pattern)
+}
+
+/// Returns the most recent time before `now` that the schedule should have fired
+pub fn previous_occurrence(cron: &Cron, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    cron.find_previous_occurrence(&now.with_timezone(&Local), false)
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+/// Returns the next time after `now` that the schedule should fire
+pub fn next_occurrence(cron: &Cron, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    cron.find_next_occurrence(&now.with_timezone(&Local), false)
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+/// Flags the scheduler as stopped when dropped (also when the task panics)
+struct RunningGuard<'a>(&'a AppState);
+
+impl Drop for RunningGuard<'_> {
+    fn drop(&mut self) {
+        self.0.scheduler_running.store(false, Ordering::SeqCst);
+    }
+}
+
+/// A run that should be started by the scheduler
+#[derive(Debug)]
+pub struct RunRequest {
+    pub widget: WidgetId,
+    pub initiator: Initiator,
+    /// Set if this is another attempt of a failed scheduled run
+    pub retry: Option<Retry>,
+}
+
+/// Another attempt of a scheduled run that failed with a transient error
+#[derive(Debug, Clone, Copy)]
+pub struct Retry {
+    /// The first attempt
+    pub of: RunId,
+    pub number: u32,
+    /// The attempt is not started before this time
+    pub at: DateTime<Utc>,
+}
+
+fn spawn_run(state: &Arc<AppState>, id: WidgetId, initiator: Initiator, retry: Option<Retry>) {
+    let state = state.clone();
+    tokio::spawn(async move {
+        tracing::debug!("running widget {id} ({initiator:?})");
+        if let Err(err) = state.execute(&id, initiator, retry).await {
+            tracing::warn!("{initiator:?} run of widget {id} failed: {err:?}");
+        }
+    });
+}
+
+/// Background task that triggers the scheduled widgets and the requested runs until the server
+/// shuts down
+pub async fn run_scheduler(state: Arc<AppState>, mut requests: UnboundedReceiver<RunRequest>) {
+    state.scheduler_running.store(true, Ordering::SeqCst);
+    let _guard = RunningGuard(&state);
+
+    // the schedules were validated when loading the configuration
+    let schedules: Vec<(WidgetId, Cron)> = state
+        .widgets
+        .iter()
+        .filter_map(|w| {
+            w.schedule()
+                .map(|s| (w.id().clone(), parse_cron(&s.cron).expect("valid cron")))
+        })
+        .collect();
+
+    // retries waiting for their time to come
+    let mut retries: Vec<RunRequest> = Vec::new();
+
+    loop {
+        let now = Utc::now();
+        let upcoming: Vec<(&WidgetId, DateTime<Utc>)> = schedules
+            .iter()
+            .filter_map(|(id, cron)| next_occurrence(cron, now).map(|t| (id, t)))
+            .collect();
+        let next_scheduled = upcoming.iter().map(|(_, t)| *t).min();
+        let next = retries
+            .iter()
+            .filter_map(|r| r.retry.map(|r| r.at))
+            .chain(next_scheduled)
+            .min();
+
+        let wait = async {
+            match next {
+                Some(next) => tokio::time::sleep((next - now).to_std().unwrap_or_default()).await,
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            _ = wait => {
+                for (id, _) in upcoming.into_iter().filter(|(_, t)| Some(*t) == next) {
+                    // a new scheduled run replaces the retries of the previous one
+                    retries.retain(|r| r.widget != *id);
+                    spawn_run(&state, id.clone(), Initiator::Schedule, None);
+                }
+
+                let now = Utc::now();
+                let (due, waiting) = retries
+                    .drain(..)
+                    .partition(|r| r.retry.is_some_and(|r| r.at <= now));
+                retries = waiting;
+                for request in due {
+                    spawn_run(&state, request.widget, request.initiator, request.retry);
+                }
+            }
+            Some(request) = requests.recv() => {
+                if request.retry.is_some() {
+                    retries.push(request);
+                } else {
+                    spawn_run(&state, request.widget, request.initiator, None);
+                }
+            }
+            _ = state.shutdown.cancelled() => {
+                if !retries.is_empty() {
+                    tracing::info!("dropping {} pending retries", retries.len());
+                }
+                tracing::debug!("scheduler stopped");
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::config::parse_config;
+
+    #[tokio::test]
+    async fn reschedules_retries_until_attempts_run_out() {
+        let config = "
+widgets:
+  - !Command
+    id: flaky
+    schedule:
+      cron: \"0 0 1 1 *\"
+      retry:
+        max_attempts: 3
+        initial_delay_seconds: 0
+    config:
+      argv: [\"false\"]
+";
+        let (state, requests) = AppState::new(parse_config(config).unwrap()).unwrap();
+        let scheduler = tokio::spawn(run_scheduler(state.clone(), requests));
+        let flaky: WidgetId = "flaky".parse().unwrap();
+
+        let first = state
+            .execute(&flaky, Initiator::Schedule, None)
+            .await
+            .unwrap();
+
+        // the retries are started by the scheduler as soon as they are due
+        let runs = async {
+            loop {
+                let runs = state.db.read().await.get_runs(flaky.clone()).unwrap();
+                if runs.len() >= 3 {
+                    return runs;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(10), runs)
+            .await
+            .expect("retries were not run");
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let runs = state.db.read().await.get_runs(flaky.clone()).unwrap();
+        let attempts: Vec<_> = runs
+            .iter()
+            .map(|r| {
+                r.attempt
+                    .map(|a| (a.retry_of, a.number, a.next_retry.is_some()))
+            })
+            .collect();
+        assert_eq!(
+            attempts,
+            [
+                Some((None, 1, true)),
+                Some((Some(first), 2, true)),
+                Some((Some(first), 3, false)),
+            ]
+        );
+        assert!(matches!(runs[2].initiated, Initiator::Retry));
+
+        state.shutdown.cancel();
+        scheduler.await.unwrap();
+    }
+}
@@ -0,0 +1,54 @@
+//! Secrets such as API keys and tokens. They are defined once at the top of the config and a
+//! widget only gets the ones listed in its `secrets`, through `BackendContext::secret`.
+use std::{collections::HashMap, fmt, fs};
+
+use anyhow::anyhow;
+use serde::Deserialize;
+
+/// Where the value of a secret comes from
+#[derive(Debug, Deserialize, Clone)]
+pub enum SecretSource {
+    /// An environment variable
+    Env(String),
+    /// The contents of a file, without trailing whitespace (e.g. a Docker or systemd secret)
+    File(String),
+    /// Written directly in the config
+    Value(String),
+}
+
+/// The resolved values of the secrets, by name
+pub struct Secrets(HashMap<String, String>);
+
+impl Secrets {
+    /// Reads all secrets, so that a missing one is noticed at startup rather than on a run
+    pub fn resolve(sources: &HashMap<String, SecretSource>) -> anyhow::Result<Self> {
+        let mut secrets = HashMap::new();
+        for (name, source) in sources {
+            let value = match source {
+                SecretSource::Env(var) => std::env::var(var)
+                    .map_err(|e| anyhow!("could not read secret {name} from ${var}: {e}"))?,
+                SecretSource::File(path) => fs::read_to_string(path)
+                    .map(|v| v.trim_end().to_string())
+                    .map_err(|e| anyhow!("could not read secret {name} from {path}: {e}"))?,
+                SecretSource::Value(value) => value.clone(),
+            };
+            secrets.insert(name.clone(), value);
+        }
+        Ok(Self(secrets))
+    }
+
+    /// The secrets with the given names, names that are not defined are left out
+    pub fn granted(&self, names: &[String]) -> HashMap<String, String> {
+        names
+            .iter()
+            .filter_map(|name| Some((name.clone(), self.0.get(name)?.clone())))
+            .collect()
+    }
+}
+
+// never print the values
+impl fmt::Debug for Secrets {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.0.keys()).finish()
+    }
+}
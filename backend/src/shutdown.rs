@@ -0,0 +1,128 @@
+//! Graceful shutdown: stop accepting new runs and drain the in-flight runs and deliveries.
+//!
+//! The runs and the backend state of the widgets are only kept in memory, so there is nothing to
+//! flush before exiting and they are lost.
+use std::time::Duration;
+
+use crate::api::AppState;
+
+/// How long the in-flight runs and deliveries are allowed to take before they are abandoned
+pub const DRAIN_DEADLINE: Duration = Duration::from_secs(30);
+
+/// How long to wait for the last responses to be sent after draining
+pub const RESPONSE_GRACE: Duration = Duration::from_secs(1);
+
+/// Completes when the process receives Ctrl+C or SIGTERM
+pub async fn signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install signal handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => tracing::info!("received Ctrl+C"),
+        _ = terminate => tracing::info!("received SIGTERM"),
+    }
+}
+
+/// Stops new runs from being started and waits (up to [`DRAIN_DEADLINE`] in total) for the runs
+/// and then the webhook, alert and notification deliveries in progress
+pub async fn drain(state: &AppState) {
+    state.shutdown.cancel();
+    state.tasks.close();
+
+    let deadline = tokio::time::Instant::now() + DRAIN_DEADLINE;
+    let runs = state.tasks.len();
+    tracing::info!("shutting down, waiting for {runs} in-flight runs");
+    if tokio::time::timeout_at(deadline, state.tasks.wait())
+        .await
+        .is_err()
+    {
+        tracing::warn!("in-flight runs did not finish within {DRAIN_DEADLINE:?}");
+    }
+    let abandoned_runs = state.tasks.len();
+
+    // finished runs can start deliveries, so only close the tracker once the runs are done
+    state.deliveries.close();
+    let deliveries = state.deliveries.len();
+    tracing::info!("waiting for {deliveries} in-flight deliveries");
+    if tokio::time::timeout_at(deadline, state.deliveries.wait())
+        .await
+        .is_err()
+    {
+        tracing::warn!("deliveries did not finish within {DRAIN_DEADLINE:?}");
+    }
+    let abandoned_deliveries = state.deliveries.len();
+
+    tracing::info!(
+        "shutdown complete: {} runs finished, {abandoned_runs} abandoned, {} deliveries finished, {abandoned_deliveries} abandoned",
+        runs.saturating_sub(abandoned_runs),
+        deliveries.saturating_sub(abandoned_deliveries),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
+
+    use tokio::time::{sleep, Instant};
+
+    use super::*;
+
+    const CONFIG: &str = "widgets: []";
+
+    #[tokio::test(start_paused = true)]
+    async fn waits_for_runs_and_then_deliveries() {
+        let state = AppState::for_test(CONFIG);
+        let delivered = Arc::new(AtomicBool::new(false));
+
+        // a run that finishes after a while and then starts a delivery
+        let (run_state, run_delivered) = (state.clone(), delivered.clone());
+        state.tasks.spawn(async move {
+            sleep(Duration::from_secs(5)).await;
+            run_state.deliveries.spawn(async move {
+                sleep(Duration::from_secs(5)).await;
+                run_delivered.store(true, Ordering::SeqCst);
+            });
+        });
+
+        let start = Instant::now();
+        drain(&state).await;
+
+        assert!(state.shutdown.is_cancelled());
+        assert!(state.tasks.is_closed() && state.tasks.is_empty());
+        assert!(state.deliveries.is_closed() && state.deliveries.is_empty());
+        assert!(delivered.load(Ordering::SeqCst));
+        assert_eq!(start.elapsed(), Duration::from_secs(10));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn abandons_work_after_deadline() {
+        let state = AppState::for_test(CONFIG);
+        state.tasks.spawn(sleep(Duration::from_secs(20)));
+        // the deadline is shared, the deliveries only get what the runs left
+        state.deliveries.spawn(sleep(Duration::from_secs(60)));
+
+        let start = Instant::now();
+        drain(&state).await;
+
+        assert_eq!(start.elapsed(), DRAIN_DEADLINE);
+        assert!(state.tasks.is_empty());
+        assert_eq!(state.deliveries.len(), 1);
+    }
+}
@@ -0,0 +1,410 @@
+//! Outgoing webhooks that POST the [`BackendRun`] when a widget run completes.
+//!
+//! If a webhook has a `secret`, the body is signed with HMAC-SHA256 and the signature is sent in the
+//! `X-Dashboard-Signature` header as `sha256=<hex digest>`.
+use std::{collections::VecDeque, sync::Arc, time::Duration};
+
+use chrono::prelude::*;
+use common::{backend::BackendRun, WidgetId};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tokio::sync::RwLock;
+
+/// Timeout for a single delivery attempt, also used for notifications and alert events
+pub const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Delay before the first retry, doubled for every following attempt
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+
+/// The retry delay does not grow beyond this
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(300);
+
+/// How many deliveries are kept in the delivery log
+const LOG_CAPACITY: usize = 500;
+
+pub const SIGNATURE_HEADER: &str = "X-Dashboard-Signature";
+
+/// Which completed runs a webhook is called for
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Trigger {
+    /// Every completed run
+    #[default]
+    Completion,
+    /// Only failed runs
+    Failure,
+    /// Only when a run succeeds after a failure or the other way around
+    StateChange,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct WebhookConfig {
+    pub url: String,
+
+    #[serde(default)]
+    pub on: Trigger,
+
+    /// Only call the webhook for these widgets, all widgets if empty
+    #[serde(default)]
+    pub widgets: Vec<WidgetId>,
+
+    /// Key used to sign the body
+    #[serde(default)]
+    pub secret: Option<String>,
+
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+}
+
+fn default_max_attempts() -> u32 {
+    3
+}
+
+impl WebhookConfig {
+    fn wants(&self, run: &BackendRun, state_changed: bool) -> bool {
+        if !self.widgets.is_empty() && !self.widgets.contains(&run.widget) {
+            return false;
+        }
+
+        match self.on {
+            Trigger::Completion => true,
+            Trigger::Failure => run.result.is_err(),
+            Trigger::StateChange => state_changed,
+        }
+    }
+}
+
+/// An entry in the delivery log
+#[derive(Debug, Serialize, Clone)]
+pub struct Delivery {
+    pub url: String,
+    pub widget: WidgetId,
+    pub run: common::backend::RunId,
+    pub attempts: u32,
+    pub success: bool,
+    /// HTTP status code of the last attempt, if a response was received
+    pub status: Option<u16>,
+    pub error: Option<String>,
+    pub finished: DateTime<Utc>,
+}
+
+/// Computes the value of the signature header for a body
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+
+    let digest: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    format!("sha256={digest}")
+}
+
+/// The HTTP agent used to deliver runs, notifications and alert events to webhooks
+pub fn delivery_agent() -> ureq::Agent {
+    ureq::Agent::config_builder()
+        .timeout_global(Some(DELIVERY_TIMEOUT))
+        .build()
+        .into()
+}
+
+/// The delay after the given (1 based) attempt failed
+fn retry_delay(attempt: u32) -> Duration {
+    RETRY_BASE_DELAY
+        .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+        .min(RETRY_MAX_DELAY)
+}
+
+pub struct Webhooks {
+    configs: Vec<WebhookConfig>,
+    log: RwLock<VecDeque<Delivery>>,
+    agent: ureq::Agent,
+}
+
+impl Webhooks {
+    pub fn new(configs: Vec<WebhookConfig>) -> Self {
+        Self {
+            configs,
+            log: RwLock::new(VecDeque::new()),
+            agent: delivery_agent(),
+        }
+    }
+
+    /// Returns the index of the webhooks that should be called for the run
+    pub fn matching(&self, run: &BackendRun, state_changed: bool) -> Vec<usize> {
+        self.configs
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.wants(run, state_changed))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// The delivery log, most recent first
+    pub async fn deliveries(&self) -> Vec<Delivery> {
+        self.log.read().await.iter().rev().cloned().collect()
+    }
+
+    /// Delivers the run to a webhook, retrying with exponential backoff
+    pub async fn deliver(self: Arc<Self>, index: usize, run: BackendRun) {
+        let config = &self.configs[index];
+        let body = serde_json::to_vec(&run).expect("serializing run");
+        let signature = config.secret.as_deref().map(|s| sign(s, &body));
+
+        let mut attempts = 0;
+        let (status, error) = loop {
+            attempts += 1;
+
+            let agent = self.agent.clone();
+            let url = config.url.clone();
+            let body = body.clone();
+            let signature = signature.clone();
+            let result = tokio::task::spawn_blocking(move || {
+                let mut request = agent.post(&url).header("Content-Type", "application/json");
+                if let Some(signature) = signature {
+                    request = request.header(SIGNATURE_HEADER, signature);
+                }
+                request.send(&body[..])
+            })
+            .await
+            .expect("webhook delivery panicked");
+
+            let (status, error) = match result {
+                Ok(response) => (Some(response.status().as_u16()), None),
+                Err(ureq::Error::StatusCode(code)) => {
+                    (Some(code), Some(format!("status code {code}")))
+                }
+                Err(err) => (None, Some(err.to_string())),
+            };
+
+            if error.is_none() || attempts >= config.max_attempts {
+                break (status, error);
+            }
+
+            let delay = retry_delay(attempts);
+            tracing::debug!(
+                "webhook {} failed (attempt {attempts}), retrying in {delay:?}",
+                config.url
+            );
+            tokio::time::sleep(delay).await;
+        };
+
+        if let Some(error) = &error {
+            tracing::warn!(
+                "could not deliver run {:?} of {} to {}: {error}",
+                run.id,
+                run.widget,
+                config.url
+            );
+        }
+
+        let mut log = self.log.write().await;
+        if log.len() >= LOG_CAPACITY {
+            log.pop_front();
+        }
+        log.push_back(Delivery {
+            url: config.url.clone(),
+            widget: run.widget.clone(),
+            run: run.id,
+            attempts,
+            success: error.is_none(),
+            status,
+            error,
+            finished: Utc::now(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use common::backend::BackendError;
+
+    use super::*;
+    use crate::widget::test_run;
+
+    /// A request received by [`serve`], with lowercase header names
+    struct Request {
+        headers: Vec<(String, String)>,
+        body: Vec<u8>,
+    }
+
+    impl Request {
+        fn header(&self, name: &str) -> Option<&str> {
+            self.headers
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, v)| v.as_str())
+        }
+    }
+
+    /// Answers one request per status code on a local port and returns the received requests
+    fn serve(statuses: Vec<u16>) -> (String, thread::JoinHandle<Vec<Request>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+
+        let handle = thread::spawn(move || {
+            statuses
+                .into_iter()
+                .map(|status| {
+                    let (mut stream, _) = listener.accept().unwrap();
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                    let mut headers = Vec::new();
+                    loop {
+                        let mut line = String::new();
+                        reader.read_line(&mut line).unwrap();
+                        if line.trim_end().is_empty() {
+                            break;
+                        }
+                        if let Some((name, value)) = line.split_once(':') {
+                            headers.push((name.to_lowercase(), value.trim().to_string()));
+                        }
+                    }
+                    let mut request = Request {
+                        headers,
+                        body: Vec::new(),
+                    };
+                    let length = request.header("content-length").unwrap().parse().unwrap();
+                    request.body.resize(length, 0);
+                    reader.read_exact(&mut request.body).unwrap();
+
+                    write!(
+                        stream,
+                        "HTTP/1.1 {status} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                    )
+                    .unwrap();
+                    request
+                })
+                .collect()
+        });
+
+        (url, handle)
+    }
+
+    fn config(url: &str) -> WebhookConfig {
+        WebhookConfig {
+            url: url.to_string(),
+            on: Trigger::Completion,
+            widgets: Vec::new(),
+            secret: None,
+            max_attempts: 3,
+        }
+    }
+
+    fn run(widget: &str, result: Result<Option<String>, BackendError>) -> BackendRun {
+        let time = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+        test_run(widget, time, result)
+    }
+
+    #[tokio::test]
+    async fn delivers_signed_run() {
+        let (url, server) = serve(vec![200]);
+        let webhooks = Arc::new(Webhooks::new(vec![WebhookConfig {
+            secret: Some("s3cret".to_string()),
+            ..config(&url)
+        }]));
+        let run = run("weather", Ok(Some("{}".to_string())));
+
+        webhooks.clone().deliver(0, run.clone()).await;
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests[0].body, serde_json::to_vec(&run).unwrap());
+        assert_eq!(requests[0].header("content-type"), Some("application/json"));
+        assert_eq!(
+            requests[0].header(&SIGNATURE_HEADER.to_lowercase()),
+            Some(sign("s3cret", &requests[0].body).as_str())
+        );
+
+        let deliveries = webhooks.deliveries().await;
+        assert_eq!(deliveries.len(), 1);
+        assert!(deliveries[0].success);
+        assert_eq!(deliveries[0].attempts, 1);
+        assert_eq!(deliveries[0].status, Some(200));
+    }
+
+    #[test]
+    fn signs_with_hmac_sha256() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[tokio::test]
+    async fn retries_failed_deliveries() {
+        let (url, server) = serve(vec![500, 204]);
+        let webhooks = Arc::new(Webhooks::new(vec![config(&url)]));
+
+        webhooks.clone().deliver(0, run("weather", Ok(None))).await;
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].header("x-dashboard-signature").is_none());
+        let delivery = &webhooks.deliveries().await[0];
+        assert!(delivery.success);
+        assert_eq!(delivery.attempts, 2);
+        assert_eq!(delivery.status, Some(204));
+        assert_eq!(delivery.error, None);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_attempts() {
+        let (url, server) = serve(vec![503, 502]);
+        let webhooks = Arc::new(Webhooks::new(vec![WebhookConfig {
+            max_attempts: 2,
+            ..config(&url)
+        }]));
+
+        webhooks.clone().deliver(0, run("weather", Ok(None))).await;
+
+        assert_eq!(server.join().unwrap().len(), 2);
+        let delivery = &webhooks.deliveries().await[0];
+        assert!(!delivery.success);
+        assert_eq!(delivery.attempts, 2);
+        assert_eq!(delivery.status, Some(502));
+        assert_eq!(delivery.error.as_deref(), Some("status code 502"));
+    }
+
+    #[test]
+    fn caps_retry_delay() {
+        assert_eq!(retry_delay(1), Duration::from_secs(1));
+        assert_eq!(retry_delay(4), Duration::from_secs(8));
+        assert_eq!(retry_delay(10), RETRY_MAX_DELAY);
+        assert_eq!(retry_delay(u32::MAX), RETRY_MAX_DELAY);
+    }
+
+    #[test]
+    fn filters_runs() {
+        let webhooks = Webhooks::new(vec![
+            config("http://all"),
+            WebhookConfig {
+                on: Trigger::Failure,
+                ..config("http://failures")
+            },
+            WebhookConfig {
+                on: Trigger::StateChange,
+                ..config("http://changes")
+            },
+            WebhookConfig {
+                widgets: vec!["weather".parse().unwrap()],
+                ..config("http://weather")
+            },
+        ]);
+        let failed = |widget| run(widget, Err(BackendError::Transient("offline".into())));
+
+        assert_eq!(webhooks.matching(&run("weather", Ok(None)), false), [0, 3]);
+        assert_eq!(webhooks.matching(&run("disk", Ok(None)), true), [0, 2]);
+        assert_eq!(webhooks.matching(&failed("disk"), false), [0, 1]);
+        assert_eq!(webhooks.matching(&failed("weather"), true), [0, 1, 2, 3]);
+    }
+}
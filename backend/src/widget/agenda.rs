@@ -0,0 +1,575 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{prelude::*, TimeDelta};
+use common::{
+    agenda::{Config, Event, Output},
+    backend::BackendError,
+};
+use rrule::{RRule, RRuleSet, Tz, Unvalidated};
+
+use super::{read_source, BackendContext, WidgetBackend};
+
+/// Maximum number of occurrences of a recurring event within the window
+const MAX_OCCURRENCES: u16 = 1000;
+
+/// A content line of a calendar, e.g. `DTSTART;TZID=Europe/Berlin:20240101T100000`
+#[derive(Debug)]
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// The properties of a VEVENT component
+#[derive(Debug)]
+struct RawEvent(Vec<Property>);
+
+impl RawEvent {
+    fn get(&self, name: &str) -> Option<&Property> {
+        self.0.iter().find(|p| p.name == name)
+    }
+
+    fn all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Property> {
+        self.0.iter().filter(move |p| p.name == name)
+    }
+
+    fn text(&self, name: &str) -> Option<String> {
+        self.get(name).map(|p| unescape(&p.value))
+    }
+}
+
+/// A date or date-time value
+#[derive(Debug, Clone, Copy)]
+enum Time {
+    Date(NaiveDate),
+    DateTime(DateTime<Tz>),
+}
+
+impl Time {
+    /// All-day values start at midnight in `tz`
+    fn resolve(self, tz: Tz) -> DateTime<Tz> {
+        match self {
+            Time::Date(date) => localize(tz, date.and_time(NaiveTime::MIN)),
+            Time::DateTime(time) => time,
+        }
+    }
+}
+
+fn parse_property(line: &str) -> Option<Property> {
+    // the value starts after the first colon that is not part of a quoted parameter value
+    let mut quoted = false;
+    let (colon, _) = line.char_indices().find(|&(_, c)| {
+        if c == '"' {
+            quoted = !quoted;
+        }
+        c == ':' && !quoted
+    })?;
+
+    let mut parts = line[..colon].split(';');
+    let name = parts.next()?.to_ascii_uppercase();
+    let params = parts
+        .filter_map(|p| {
+            let (key, value) = p.split_once('=')?;
+            Some((
+                key.to_ascii_uppercase(),
+                value.trim_matches('"').to_string(),
+            ))
+        })
+        .collect();
+
+    Some(Property {
+        name,
+        params,
+        value: line[colon + 1..].to_string(),
+    })
+}
+
+/// Returns the events of a calendar, without the properties of nested components such as alarms
+fn parse_events(text: &str) -> Vec<RawEvent> {
+    // long lines are folded by inserting a line break followed by a space or tab
+    let unfolded = text
+        .replace("\r\n", "\n")
+        .replace("\n ", "")
+        .replace("\n\t", "");
+
+    let mut events = Vec::new();
+    let mut current: Option<Vec<Property>> = None;
+    let mut nested = 0usize;
+
+    for property in unfolded.lines().filter_map(parse_property) {
+        match (
+            property.name.as_str(),
+            property.value.to_ascii_uppercase().as_str(),
+        ) {
+            ("BEGIN", "VEVENT") => {
+                current = Some(Vec::new());
+                nested = 0;
+            }
+            ("END", "VEVENT") => events.extend(current.take().map(RawEvent)),
+            ("BEGIN", _) if current.is_some() => nested += 1,
+            ("END", _) if current.is_some() => nested = nested.saturating_sub(1),
+            _ => {
+                if let (Some(properties), 0) = (&mut current, nested) {
+                    properties.push(property);
+                }
+            }
+        }
+    }
+
+    events
+}
+
+fn unescape(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => text.push('\n'),
+            Some(c) => text.push(c),
+            None => {}
+        }
+    }
+    text
+}
+
+/// Looks up an IANA time zone. Some clients prefix the name, e.g.
+/// `/mozilla.org/20050126_1/Europe/Berlin`, so the suffixes after each `/` are tried as well.
+pub fn parse_timezone(name: &str) -> Option<Tz> {
+    std::iter::once(name)
+        .chain(name.match_indices('/').map(|(i, _)| &name[i + 1..]))
+        .find_map(|n| n.parse::<chrono_tz::Tz>().ok())
+        .map(Tz::Tz)
+}
+
+fn localize(tz: Tz, time: NaiveDateTime) -> DateTime<Tz> {
+    // local times skipped by a daylight saving change are moved forward
+    tz.from_local_datetime(&time)
+        .earliest()
+        .or_else(|| {
+            tz.from_local_datetime(&(time + TimeDelta::hours(1)))
+                .earliest()
+        })
+        .unwrap_or_else(|| tz.from_utc_datetime(&time))
+}
+
+/// Parses the (comma separated) values of a date or date-time property. Values without a
+/// `TZID` or `Z` suffix are in `tz`.
+fn parse_times(property: &Property, tz: Tz) -> Result<Vec<Time>, String> {
+    let zone = match property.param("TZID") {
+        Some(id) => parse_timezone(id).unwrap_or_else(|| {
+            tracing::warn!("unknown time zone {id}, using {} instead", tz.name());
+            tz
+        }),
+        None => tz,
+    };
+    let is_date = property.param("VALUE") == Some("DATE");
+
+    property
+        .value
+        .split(',')
+        .map(|value| {
+            let invalid = |_| format!("invalid time {value} in {}", property.name);
+            if is_date || value.len() == 8 {
+                NaiveDate::parse_from_str(value, "%Y%m%d")
+                    .map(Time::Date)
+                    .map_err(invalid)
+            } else if let Some(utc) = value.strip_suffix('Z') {
+                NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S")
+                    .map(|t| Time::DateTime(Tz::UTC.from_utc_datetime(&t)))
+                    .map_err(invalid)
+            } else {
+                NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+                    .map(|t| Time::DateTime(localize(zone, t)))
+                    .map_err(invalid)
+            }
+        })
+        .collect()
+}
+
+fn parse_time(property: &Property, tz: Tz) -> Result<Time, String> {
+    parse_times(property, tz)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| format!("missing value of {}", property.name))
+}
+
+/// Parses a duration such as `PT1H30M` or `P1D`
+fn parse_duration(value: &str) -> Option<TimeDelta> {
+    let (negative, value) = match value.strip_prefix('-') {
+        Some(value) => (true, value),
+        None => (false, value.strip_prefix('+').unwrap_or(value)),
+    };
+
+    let mut total = TimeDelta::zero();
+    let mut number = String::new();
+    for c in value.strip_prefix('P')?.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => {}
+            'W' | 'D' | 'H' | 'M' | 'S' => {
+                let n: i64 = number.parse().ok()?;
+                number.clear();
+                total += match c {
+                    'W' => TimeDelta::weeks(n),
+                    'D' => TimeDelta::days(n),
+                    'H' => TimeDelta::hours(n),
+                    'M' => TimeDelta::minutes(n),
+                    _ => TimeDelta::seconds(n),
+                };
+            }
+            _ => return None,
+        }
+    }
+
+    Some(if negative { -total } else { total })
+}
+
+/// Parses an RRULE of an event starting at `start`.
+///
+/// rrule only accepts an UNTIL in UTC when DTSTART has a named time zone, so a date or a local
+/// UNTIL is converted to UTC first. A date includes the whole day in the zone of DTSTART.
+fn parse_rule(value: &str, start: DateTime<Tz>) -> Result<RRule, String> {
+    let (until, parts): (Vec<&str>, Vec<&str>) = value.split(';').partition(|part| {
+        part.split_once('=')
+            .is_some_and(|(name, _)| name.eq_ignore_ascii_case("UNTIL"))
+    });
+    let mut rule: RRule<Unvalidated> = parts.join(";").parse().map_err(|e| format!("{e}"))?;
+
+    if let Some((_, until)) = until.first().and_then(|u| u.split_once('=')) {
+        let zone = start.timezone();
+        let invalid = |_| format!("invalid UNTIL {until}");
+        let until = if until.len() == 8 {
+            let next_day =
+                NaiveDate::parse_from_str(until, "%Y%m%d").map_err(invalid)? + chrono::Days::new(1);
+            localize(zone, next_day.and_time(NaiveTime::MIN)) - TimeDelta::seconds(1)
+        } else if let Some(utc) = until.strip_suffix('Z') {
+            Tz::UTC.from_utc_datetime(
+                &NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").map_err(invalid)?,
+            )
+        } else {
+            localize(
+                zone,
+                NaiveDateTime::parse_from_str(until, "%Y%m%dT%H%M%S").map_err(invalid)?,
+            )
+        };
+        rule = rule.until(until.with_timezone(&Tz::UTC));
+    }
+
+    rule.validate(start).map_err(|e| e.to_string())
+}
+
+/// Expands an event into its occurrences that overlap `from..to`, leaving out the occurrences in
+/// `overridden` (which are separate events with a `RECURRENCE-ID`)
+fn expand(
+    event: &RawEvent,
+    calendar: &str,
+    tz: Tz,
+    (from, to): (DateTime<Utc>, DateTime<Utc>),
+    overridden: &HashSet<DateTime<Utc>>,
+) -> Result<Vec<Event>, String> {
+    if event
+        .get("STATUS")
+        .is_some_and(|s| s.value.eq_ignore_ascii_case("CANCELLED"))
+    {
+        return Ok(Vec::new());
+    }
+
+    let dtstart = parse_time(event.get("DTSTART").ok_or("missing DTSTART")?, tz)?;
+    let all_day = matches!(dtstart, Time::Date(_));
+    let start = dtstart.resolve(tz);
+    let end = match (event.get("DTEND"), event.get("DURATION")) {
+        (Some(end), _) => parse_time(end, tz)?.resolve(tz),
+        (None, Some(duration)) => {
+            start
+                + parse_duration(&duration.value)
+                    .ok_or_else(|| format!("invalid duration {}", duration.value))?
+        }
+        (None, None) if all_day => start + TimeDelta::days(1),
+        (None, None) => start,
+    };
+    let duration = end - start;
+    // all-day events span whole days, even when a daylight saving change makes a day shorter
+    let days = end.date_naive() - start.date_naive();
+
+    let starts = if event.get("RRULE").is_none() && event.get("RDATE").is_none() {
+        vec![start]
+    } else {
+        // values without a time zone are in the zone of DTSTART
+        let zone = start.timezone();
+        let mut set = RRuleSet::new(start);
+        for rule in event.all("RRULE") {
+            set = set.rrule(parse_rule(&rule.value, start)?);
+        }
+        if event.get("RRULE").is_none() {
+            // DTSTART is always the first occurrence, rrule only adds it for a RRULE
+            set = set.rdate(start);
+        }
+        for rdate in event.all("RDATE") {
+            for time in parse_times(rdate, zone)? {
+                set = set.rdate(time.resolve(zone));
+            }
+        }
+        for exdate in event.all("EXDATE") {
+            for time in parse_times(exdate, zone)? {
+                set = set.exdate(time.resolve(zone));
+            }
+        }
+
+        // occurrences that started before the window can still be going on
+        set.after((from - duration).with_timezone(&tz))
+            .before(to.with_timezone(&tz))
+            .all(MAX_OCCURRENCES)
+            .dates
+    };
+
+    let summary = event.text("SUMMARY").unwrap_or_else(|| "(no title)".into());
+    let location = event.text("LOCATION").filter(|l| !l.is_empty());
+
+    Ok(starts
+        .into_iter()
+        .filter(|start| !overridden.contains(&start.with_timezone(&Utc)))
+        .map(|start| {
+            let end = if all_day {
+                localize(tz, (start.date_naive() + days).and_time(NaiveTime::MIN))
+            } else {
+                start + duration
+            };
+            Event {
+                summary: summary.clone(),
+                location: location.clone(),
+                start: start.with_timezone(&Utc),
+                end: end.with_timezone(&Utc),
+                all_day,
+                calendar: calendar.into(),
+            }
+        })
+        .filter(|e| e.start < to && (e.end > from || e.start >= from))
+        .collect())
+}
+
+/// Returns all events of a calendar that overlap the window
+fn calendar_events(
+    text: &str,
+    calendar: &str,
+    tz: Tz,
+    window: (DateTime<Utc>, DateTime<Utc>),
+) -> Vec<Event> {
+    let events = parse_events(text);
+
+    // modified occurrences of recurring events are separate events with the same UID
+    let mut overridden: HashMap<String, HashSet<DateTime<Utc>>> = HashMap::new();
+    for event in &events {
+        if let (Some(uid), Some(recurrence_id)) = (event.text("UID"), event.get("RECURRENCE-ID")) {
+            if let Ok(time) = parse_time(recurrence_id, tz) {
+                overridden
+                    .entry(uid)
+                    .or_default()
+                    .insert(time.resolve(tz).with_timezone(&Utc));
+            }
+        }
+    }
+
+    let none = HashSet::new();
+    events
+        .iter()
+        .flat_map(|event| {
+            let uid = event.text("UID").unwrap_or_default();
+            let overridden = match event.get("RECURRENCE-ID") {
+                None => overridden.get(&uid).unwrap_or(&none),
+                Some(_) => &none,
+            };
+
+            expand(event, calendar, tz, window, overridden).unwrap_or_else(|e| {
+                tracing::warn!("skipping event {uid} in {calendar}: {e}");
+                Vec::new()
+            })
+        })
+        .collect()
+}
+
+fn load(calendar: &str) -> Result<String, BackendError> {
+    // webcal:// is how calendar subscriptions are often shared, it is plain HTTPS
+    match calendar.strip_prefix("webcal://") {
+        Some(rest) => read_source(&format!("https://{rest}")),
+        None => read_source(calendar),
+    }
+}
+
+impl WidgetBackend for Config {
+    type Output = Output;
+
+    fn run(&self, _ctx: &mut BackendContext<'_>) -> Result<Option<Self::Output>, BackendError> {
+        let tz = match &self.timezone {
+            Some(name) => parse_timezone(name)
+                .ok_or_else(|| BackendError::Permanent(format!("unknown time zone {name}")))?,
+            None => Tz::LOCAL,
+        };
+
+        let now = Utc::now();
+        let today = now.with_timezone(&tz).date_naive();
+        let day_start = localize(tz, today.and_time(NaiveTime::MIN)).with_timezone(&Utc);
+        let day_end = localize(tz, (today + chrono::Days::new(1)).and_time(NaiveTime::MIN))
+            .with_timezone(&Utc);
+        let window = (
+            day_start,
+            day_end.max(now + TimeDelta::days(self.days.into())),
+        );
+
+        let mut events = Vec::new();
+        for calendar in &self.calendars {
+            let text = load(calendar)?;
+            events.extend(calendar_events(&text, calendar, tz, window));
+        }
+        events.sort_by(|a, b| (a.start, &a.summary).cmp(&(b.start, &b.summary)));
+
+        let overlaps = |e: &Event, from: DateTime<Utc>, to: DateTime<Utc>| {
+            e.start < to && (e.end > from || e.start >= from)
+        };
+
+        Ok(Some(Output {
+            today: events
+                .iter()
+                .filter(|e| overlaps(e, day_start, day_end))
+                .cloned()
+                .collect(),
+            upcoming: events
+                .into_iter()
+                .filter(|e| overlaps(e, now, window.1))
+                .take(self.count)
+                .collect(),
+            day_start,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> String {
+        let path = format!("{}/fixtures/agenda/{name}", env!("CARGO_MANIFEST_DIR"));
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    fn utc(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, day, hour, minute, 0).unwrap()
+    }
+
+    /// The events of the fixture in the two weeks from 4 March in New York, sorted by start
+    fn events(tz: &str) -> Vec<Event> {
+        let tz = parse_timezone(tz).unwrap();
+        let window = (utc(4, 5, 0), utc(18, 4, 0));
+        let mut events = calendar_events(&fixture("team.ics"), "team", tz, window);
+        events.sort_by(|a, b| (a.start, &a.summary).cmp(&(b.start, &b.summary)));
+        events
+    }
+
+    fn starts(events: &[Event], summary: &str) -> Vec<DateTime<Utc>> {
+        events
+            .iter()
+            .filter(|e| e.summary == summary)
+            .map(|e| e.start)
+            .collect()
+    }
+
+    #[test]
+    fn expands_recurring_events_in_their_time_zone() {
+        let events = events("America/New_York");
+
+        // weekdays at 9:30 in Berlin, EXDATE without TZID is in Berlin as well
+        let standups: Vec<_> = [4, 5, 8, 11, 12, 13, 14, 15]
+            .into_iter()
+            .map(|day| utc(day, 8, 30))
+            .collect();
+        assert_eq!(starts(&events, "Stand-up of the platform team"), standups);
+
+        let standup = events.iter().find(|e| e.start == utc(4, 8, 30)).unwrap();
+        assert_eq!(standup.end, utc(4, 8, 45));
+        assert!(!standup.all_day);
+        assert_eq!(standup.calendar, "team");
+    }
+
+    #[test]
+    fn replaces_overridden_occurrences() {
+        let events = events("America/New_York");
+
+        assert_eq!(starts(&events, "Stand-up (moved)"), [utc(7, 10, 0)]);
+        assert!(events.iter().all(|e| e.start != utc(7, 8, 30)));
+        assert!(events.iter().all(|e| e.summary != "Cancelled"));
+    }
+
+    #[test]
+    fn expands_all_day_events_until_a_date() {
+        for tz in ["America/New_York", "UTC"] {
+            let events = events(tz);
+            let midnight = |day| {
+                let tz = parse_timezone(tz).unwrap();
+                localize(tz, NaiveDate::from_ymd_opt(2024, 3, day).unwrap().into())
+                    .with_timezone(&Utc)
+            };
+
+            // the UNTIL date itself is included
+            assert_eq!(
+                starts(&events, "Sprint start"),
+                [midnight(4), midnight(11)],
+                "in {tz}"
+            );
+            let offsite = events.iter().find(|e| e.summary == "Offsite").unwrap();
+            assert!(offsite.all_day);
+            assert_eq!((offsite.start, offsite.end), (midnight(8), midnight(10)));
+        }
+    }
+
+    #[test]
+    fn uses_duration_and_rdate() {
+        let events = events("America/New_York");
+
+        assert_eq!(starts(&events, "Review"), [utc(5, 15, 0), utc(12, 15, 0)]);
+        let review = events.iter().find(|e| e.summary == "Review").unwrap();
+        assert_eq!(review.end, utc(5, 16, 30));
+        assert_eq!(review.location.as_deref(), Some("Room 1, second floor"));
+    }
+
+    #[test]
+    fn parses_until() {
+        let berlin = parse_timezone("Europe/Berlin").unwrap();
+        let start = localize(berlin, utc(4, 9, 30).naive_utc());
+        let until = |rule: &str| parse_rule(rule, start).unwrap().get_until().copied();
+
+        assert_eq!(
+            until("FREQ=DAILY;UNTIL=20240310"),
+            Some(Tz::UTC.with_ymd_and_hms(2024, 3, 10, 22, 59, 59).unwrap())
+        );
+        assert_eq!(
+            until("FREQ=DAILY;UNTIL=20240310T120000"),
+            Some(Tz::UTC.with_ymd_and_hms(2024, 3, 10, 11, 0, 0).unwrap())
+        );
+        assert_eq!(
+            until("FREQ=DAILY;UNTIL=20240310T120000Z"),
+            Some(Tz::UTC.with_ymd_and_hms(2024, 3, 10, 12, 0, 0).unwrap())
+        );
+        assert_eq!(until("FREQ=DAILY;COUNT=3"), None);
+        assert!(parse_rule("FREQ=DAILY;UNTIL=2024", start).is_err());
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("PT1H30M"), Some(TimeDelta::minutes(90)));
+        assert_eq!(parse_duration("P1W2D"), Some(TimeDelta::days(9)));
+        assert_eq!(parse_duration("-PT15M"), Some(TimeDelta::minutes(-15)));
+        assert_eq!(parse_duration("1H"), None);
+    }
+}
@@ -0,0 +1,392 @@
+//! Positions of the sun and the moon, using the formulas of the NOAA solar calculator and the
+//! low precision ones from Meeus' Astronomical Algorithms. The times of the sun are accurate to
+//! about a minute outside of the polar regions.
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, NaiveTime, TimeDelta, TimeZone, Utc};
+use common::{
+    astronomy::{Config, Moon, MoonPhase, Output, Polar},
+    backend::BackendError,
+};
+
+use super::{agenda::parse_timezone, BackendContext, WidgetBackend};
+
+/// Altitude of the center of the sun at sunrise and sunset, accounting for refraction and the
+/// radius of the sun
+const SUNRISE_ALTITUDE: f64 = -0.833;
+
+/// Altitude of the center of the sun at the start and end of civil twilight
+const CIVIL_TWILIGHT_ALTITUDE: f64 = -6.0;
+
+/// Average number of days from one new moon to the next
+const SYNODIC_MONTH: f64 = 29.530588853;
+
+/// Julian centuries since J2000.0
+fn julian_century(time: DateTime<Utc>) -> f64 {
+    let julian_day = time.timestamp_millis() as f64 / 86_400_000.0 + 2440587.5;
+    (julian_day - 2451545.0) / 36525.0
+}
+
+/// Returns the declination of the sun in degrees and the equation of time in minutes
+fn sun_position(time: DateTime<Utc>) -> (f64, f64) {
+    let t = julian_century(time);
+
+    let mean_longitude = (280.46646 + t * (36000.76983 + t * 0.0003032)).rem_euclid(360.0);
+    let mean_anomaly = (357.52911 + t * (35999.05029 - t * 0.0001537)).to_radians();
+    let eccentricity = 0.016708634 - t * (0.000042037 + t * 0.0000001267);
+
+    let center = mean_anomaly.sin() * (1.914602 - t * (0.004817 + t * 0.000014))
+        + (2.0 * mean_anomaly).sin() * (0.019993 - t * 0.000101)
+        + (3.0 * mean_anomaly).sin() * 0.000289;
+    let omega = (125.04 - 1934.136 * t).to_radians();
+    let apparent_longitude =
+        (mean_longitude + center - 0.00569 - 0.00478 * omega.sin()).to_radians();
+
+    let mean_obliquity =
+        23.0 + (26.0 + (21.448 - t * (46.815 + t * (0.00059 - t * 0.001813))) / 60.0) / 60.0;
+    let obliquity = (mean_obliquity + 0.00256 * omega.cos()).to_radians();
+    let declination = (obliquity.sin() * apparent_longitude.sin()).asin();
+
+    let y = (obliquity / 2.0).tan().powi(2);
+    let l0 = mean_longitude.to_radians();
+    let e = eccentricity;
+    let equation_of_time = 4.0
+        * (y * (2.0 * l0).sin() - 2.0 * e * mean_anomaly.sin()
+            + 4.0 * e * y * mean_anomaly.sin() * (2.0 * l0).cos()
+            - 0.5 * y * y * (4.0 * l0).sin()
+            - 1.25 * e * e * (2.0 * mean_anomaly).sin())
+        .to_degrees();
+
+    (declination.to_degrees(), equation_of_time)
+}
+
+/// The hour angle in degrees at which the sun is at `altitude`, or on which side of it the sun
+/// stays all day
+fn hour_angle(latitude: f64, declination: f64, altitude: f64) -> Result<f64, Polar> {
+    let (latitude, declination) = (latitude.to_radians(), declination.to_radians());
+    let cos = (altitude.to_radians().sin() - latitude.sin() * declination.sin())
+        / (latitude.cos() * declination.cos());
+
+    match cos {
+        c if c > 1.0 => Err(Polar::Night),
+        c if c < -1.0 => Err(Polar::Day),
+        c => Ok(c.acos().to_degrees()),
+    }
+}
+
+fn minutes_after(midnight: DateTime<Utc>, minutes: f64) -> DateTime<Utc> {
+    midnight + TimeDelta::seconds((minutes * 60.0).round() as i64)
+}
+
+/// Solar noon of the UTC day that starts at `midnight`
+fn solar_noon(midnight: DateTime<Utc>, longitude: f64) -> DateTime<Utc> {
+    let mut noon = minutes_after(midnight, 720.0 - 4.0 * longitude);
+    for _ in 0..2 {
+        let (_, equation_of_time) = sun_position(noon);
+        noon = minutes_after(midnight, 720.0 - 4.0 * longitude - equation_of_time);
+    }
+    noon
+}
+
+/// When the sun passes `altitude` before (`rising`) or after solar noon. The position of the sun
+/// is refined at the time found, since it moves noticeably over half a day.
+fn crossing(
+    midnight: DateTime<Utc>,
+    [latitude, longitude]: [f64; 2],
+    altitude: f64,
+    rising: bool,
+) -> Result<DateTime<Utc>, Polar> {
+    let side = if rising { 1.0 } else { -1.0 };
+    let mut time = solar_noon(midnight, longitude);
+    for _ in 0..3 {
+        let (declination, equation_of_time) = sun_position(time);
+        let angle = hour_angle(latitude, declination, altitude)?;
+        time = minutes_after(
+            midnight,
+            720.0 - 4.0 * (longitude + side * angle) - equation_of_time,
+        );
+    }
+    Ok(time)
+}
+
+/// Computes the times of the sun on `date` in the time zone of the location, and the phase of
+/// the moon at `now`
+pub fn compute<Tz: TimeZone>(
+    location: [f64; 2],
+    date: NaiveDate,
+    tz: &Tz,
+    now: DateTime<Utc>,
+) -> Output {
+    // the formulas work on UTC days, use the one whose solar noon is closest to local noon so
+    // that the times belong to `date` even far from the meridian of the time zone
+    let noon = date.and_time(NaiveTime::from_hms_opt(12, 0, 0).expect("noon is valid"));
+    let local_noon = tz
+        .from_local_datetime(&noon)
+        .earliest()
+        .map(|t| t.with_timezone(&Utc))
+        .unwrap_or_else(|| noon.and_utc());
+    let midnight = [-1, 0, 1]
+        .map(|days| {
+            local_noon.date_naive().and_time(NaiveTime::MIN).and_utc() + TimeDelta::days(days)
+        })
+        .into_iter()
+        .min_by_key(|midnight| (solar_noon(*midnight, location[1]) - local_noon).abs())
+        .expect("there are candidates");
+
+    let sunrise = crossing(midnight, location, SUNRISE_ALTITUDE, true);
+    let sunset = crossing(midnight, location, SUNRISE_ALTITUDE, false);
+    let day_length = match (sunrise, sunset) {
+        (Ok(sunrise), Ok(sunset)) => (sunset - sunrise).num_seconds(),
+        (Err(Polar::Day), _) | (_, Err(Polar::Day)) => 24 * 60 * 60,
+        _ => 0,
+    };
+    let polar = match (sunrise, sunset) {
+        (Err(rise), Err(set)) if rise == set => Some(rise),
+        _ => None,
+    };
+
+    let local =
+        |time: Result<DateTime<Utc>, Polar>| time.ok().map(|t| t.with_timezone(tz).fixed_offset());
+    Output {
+        date,
+        dawn: local(crossing(midnight, location, CIVIL_TWILIGHT_ALTITUDE, true)),
+        sunrise: local(sunrise),
+        solar_noon: solar_noon(midnight, location[1])
+            .with_timezone(tz)
+            .fixed_offset(),
+        sunset: local(sunset),
+        dusk: local(crossing(midnight, location, CIVIL_TWILIGHT_ALTITUDE, false)),
+        day_length_seconds: day_length,
+        polar,
+        moon: moon(now),
+    }
+}
+
+/// Computes the phase of the moon from the elongation of the moon from the sun (Meeus chapter 48)
+pub fn moon(time: DateTime<Utc>) -> Moon {
+    let t = julian_century(time);
+
+    // mean elongation of the moon, mean anomaly of the sun and mean anomaly of the moon
+    let elongation = 297.8501921 + 445267.1114034 * t;
+    let d = elongation.to_radians();
+    let m = (357.5291092 + 35999.0502909 * t).to_radians();
+    let m_moon = (134.9633964 + 477198.8675055 * t).to_radians();
+
+    let phase_angle = 180.0 - elongation - 6.289 * m_moon.sin() + 2.100 * m.sin()
+        - 1.274 * (2.0 * d - m_moon).sin()
+        - 0.658 * (2.0 * d).sin()
+        - 0.214 * (2.0 * m_moon).sin()
+        - 0.110 * d.sin();
+    let illumination = (1.0 + phase_angle.to_radians().cos()) / 2.0;
+
+    // from 0° at new moon over 180° at full moon to 360° at the next new moon
+    let cycle = (180.0 - phase_angle).rem_euclid(360.0);
+    let phase = match ((cycle + 22.5) / 45.0) as usize % 8 {
+        0 => MoonPhase::New,
+        1 => MoonPhase::WaxingCrescent,
+        2 => MoonPhase::FirstQuarter,
+        3 => MoonPhase::WaxingGibbous,
+        4 => MoonPhase::Full,
+        5 => MoonPhase::WaningGibbous,
+        6 => MoonPhase::LastQuarter,
+        _ => MoonPhase::WaningCrescent,
+    };
+
+    Moon {
+        phase,
+        illumination,
+        age_days: cycle / 360.0 * SYNODIC_MONTH,
+    }
+}
+
+impl WidgetBackend for Config {
+    type Output = Output;
+
+    fn run(&self, ctx: &mut BackendContext<'_>) -> Result<Option<Self::Output>, BackendError> {
+        let now = Utc::now();
+        let output = match &self.timezone {
+            Some(name) => {
+                let tz = parse_timezone(name)
+                    .ok_or_else(|| BackendError::Permanent(format!("unknown time zone {name}")))?;
+                compute(self.location, now.with_timezone(&tz).date_naive(), &tz, now)
+            }
+            None => compute(
+                self.location,
+                now.with_timezone(&Local).date_naive(),
+                &Local,
+                now,
+            ),
+        };
+
+        let format = |time: Option<DateTime<FixedOffset>>| {
+            time.map_or("-".into(), |t| t.format("%H:%M").to_string())
+        };
+        ctx.log(format!(
+            "{}: sunrise {}, sunset {}, {}",
+            output.date,
+            format(output.sunrise),
+            format(output.sunset),
+            output.moon.phase.name()
+        ));
+        Ok(Some(output))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono_tz::{Arctic, Australia, Europe, Pacific, UTC};
+
+    use super::*;
+
+    const LONDON: [f64; 2] = [51.5074, -0.1278];
+    const SYDNEY: [f64; 2] = [-33.8688, 151.2093];
+    const TROMSO: [f64; 2] = [69.6492, 18.9553];
+    const LONGYEARBYEEN: [f64; 2] = [78.2232, 15.6267];
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    fn time(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    /// Checks a time against a published one, including the UTC offset. Published times are
+    /// rounded to the minute and the formulas are accurate to about a minute.
+    #[track_caller]
+    fn assert_near(actual: Option<DateTime<FixedOffset>>, expected: &str) {
+        let expected = DateTime::parse_from_rfc3339(expected).unwrap();
+        let actual = actual.expect("time is missing");
+        assert_eq!(
+            actual.offset(),
+            expected.offset(),
+            "{actual} has the wrong offset"
+        );
+        assert!(
+            (actual - expected).abs() <= TimeDelta::minutes(2),
+            "{actual} is not {expected}"
+        );
+    }
+
+    #[test]
+    fn london_solstices() {
+        let summer = compute(LONDON, date("2024-06-20"), &Europe::London, Utc::now());
+        assert_near(summer.sunrise, "2024-06-20T04:43:00+01:00");
+        assert_near(summer.sunset, "2024-06-20T21:21:00+01:00");
+        assert_near(Some(summer.solar_noon), "2024-06-20T13:02:00+01:00");
+        assert!((summer.day_length_seconds - (16 * 60 + 38) * 60).abs() <= 90);
+
+        let winter = compute(LONDON, date("2024-12-21"), &Europe::London, Utc::now());
+        assert_near(winter.dawn, "2024-12-21T07:24:00+00:00");
+        assert_near(winter.sunrise, "2024-12-21T08:04:00+00:00");
+        assert_near(winter.sunset, "2024-12-21T15:53:00+00:00");
+        assert_near(winter.dusk, "2024-12-21T16:34:00+00:00");
+        assert_eq!(winter.polar, None);
+    }
+
+    #[test]
+    fn daylight_saving_changes() {
+        // the clocks go forward at 01:00 UTC on the 31st, the sun does not
+        let before = compute(LONDON, date("2024-03-30"), &Europe::London, Utc::now());
+        let after = compute(LONDON, date("2024-03-31"), &Europe::London, Utc::now());
+        assert_near(before.sunrise, "2024-03-30T05:39:00+00:00");
+        assert_near(after.sunrise, "2024-03-31T06:37:00+01:00");
+        assert_near(after.sunset, "2024-03-31T19:32:00+01:00");
+        let earlier = before.sunrise.unwrap() - after.sunrise.unwrap() + TimeDelta::days(1);
+        assert!(earlier > TimeDelta::minutes(1) && earlier < TimeDelta::minutes(3));
+
+        // and back at 01:00 UTC on the 27th of October
+        let before = compute(LONDON, date("2024-10-26"), &Europe::London, Utc::now());
+        let after = compute(LONDON, date("2024-10-27"), &Europe::London, Utc::now());
+        assert_near(before.sunset, "2024-10-26T17:44:00+01:00");
+        assert_near(after.sunset, "2024-10-27T16:43:00+00:00");
+        assert_eq!(after.date, date("2024-10-27"));
+    }
+
+    #[test]
+    fn southern_hemisphere_summer() {
+        let output = compute(SYDNEY, date("2024-12-21"), &Australia::Sydney, Utc::now());
+        assert_near(output.sunrise, "2024-12-21T05:41:00+11:00");
+        assert_near(output.sunset, "2024-12-21T20:05:00+11:00");
+    }
+
+    #[test]
+    fn time_zone_far_from_the_meridian() {
+        // UTC+14 at a longitude of UTC-10.5, local noon is on the previous UTC day
+        let output = compute(
+            [1.87, -157.4],
+            date("2024-06-01"),
+            &Pacific::Kiritimati,
+            Utc::now(),
+        );
+        for time in [output.sunrise, Some(output.solar_noon), output.sunset] {
+            assert_eq!(time.unwrap().date_naive(), date("2024-06-01"));
+        }
+    }
+
+    #[test]
+    fn equinox_at_the_equator() {
+        let output = compute([0.0, 0.0], date("2024-03-20"), &UTC, Utc::now());
+        // refraction and the size of the sun make the day a few minutes longer than 12 hours
+        assert!((output.day_length_seconds - (12 * 60 + 7) * 60).abs() <= 60);
+        assert_near(Some(output.solar_noon), "2024-03-20T12:07:00+00:00");
+    }
+
+    #[test]
+    fn polar_night_with_twilight() {
+        let output = compute(TROMSO, date("2024-12-21"), &Europe::Oslo, Utc::now());
+        assert_eq!(output.polar, Some(Polar::Night));
+        assert_eq!(output.sunrise, None);
+        assert_eq!(output.sunset, None);
+        assert_eq!(output.day_length_seconds, 0);
+        // the sun gets within 6° of the horizon around noon
+        assert!(output.dawn.unwrap() < output.solar_noon);
+        assert!(output.dusk.unwrap() > output.solar_noon);
+    }
+
+    #[test]
+    fn polar_night_without_twilight() {
+        let output = compute(
+            LONGYEARBYEEN,
+            date("2024-12-21"),
+            &Arctic::Longyearbyen,
+            Utc::now(),
+        );
+        assert_eq!(output.polar, Some(Polar::Night));
+        assert_eq!(output.dawn, None);
+        assert_eq!(output.dusk, None);
+    }
+
+    #[test]
+    fn midnight_sun() {
+        let output = compute(TROMSO, date("2024-06-21"), &Europe::Oslo, Utc::now());
+        assert_eq!(output.polar, Some(Polar::Day));
+        assert_eq!(output.sunrise, None);
+        assert_eq!(output.dawn, None);
+        assert_eq!(output.day_length_seconds, 24 * 60 * 60);
+
+        // the midnight sun in Tromsø lasts from the 20th of May to the 22nd of July
+        let before = compute(TROMSO, date("2024-05-15"), &Europe::Oslo, Utc::now());
+        assert_eq!(before.polar, None);
+        assert!(before.sunset.is_some());
+    }
+
+    #[test]
+    fn moon_phases() {
+        // the total solar eclipse of the 8th of April 2024 was at new moon
+        let new = moon(time("2024-04-08T18:21:00Z"));
+        assert_eq!(new.phase, MoonPhase::New);
+        assert!(new.illumination < 0.01);
+        assert!(new.age_days < 0.5 || new.age_days > SYNODIC_MONTH - 0.5);
+
+        let first_quarter = moon(time("2024-01-18T03:53:00Z"));
+        assert_eq!(first_quarter.phase, MoonPhase::FirstQuarter);
+        assert!((first_quarter.illumination - 0.5).abs() < 0.02);
+
+        // the partial lunar eclipse of the 18th of September 2024 was at full moon
+        let full = moon(time("2024-09-18T02:34:00Z"));
+        assert_eq!(full.phase, MoonPhase::Full);
+        assert!(full.illumination > 0.99);
+
+        let waning = moon(time("2024-09-21T00:00:00Z"));
+        assert_eq!(waning.phase, MoonPhase::WaningGibbous);
+    }
+}
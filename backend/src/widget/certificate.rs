@@ -0,0 +1,370 @@
+use std::{
+    net::{TcpStream, ToSocketAddrs},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::prelude::*;
+use common::{
+    backend::BackendError,
+    certificate::{Certificate, CertificateStatus, Config, Output, Target},
+    notification::Level,
+};
+use rustls::{
+    client::{
+        danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+        WebPkiServerVerifier,
+    },
+    crypto::{ring, CryptoProvider},
+    pki_types::{CertificateDer, ServerName, UnixTime},
+    ClientConfig, ClientConnection, DigitallySignedStruct, RootCertStore, SignatureScheme,
+};
+use x509_parser::{extensions::GeneralName, prelude::*};
+
+use super::{BackendContext, WidgetBackend};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Accepts any certificate so that the chain can be inspected, but records whether the
+/// WebPKI verifier would have trusted it
+#[derive(Debug)]
+struct RecordingVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    trust: Mutex<Option<Result<(), String>>>,
+}
+
+impl ServerCertVerifier for RecordingVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let result = self
+            .inner
+            .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+            .map(|_| ())
+            .map_err(|e| e.to_string());
+        *self.trust.lock().unwrap() = Some(result);
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+/// Level of a certificate expiring in `days_remaining` days
+fn level(config: &Config, days_remaining: i64) -> Level {
+    if days_remaining < config.critical_days {
+        Level::Critical
+    } else if days_remaining < config.warning_days {
+        Level::Warning
+    } else {
+        Level::Info
+    }
+}
+
+/// Performs a TLS handshake and returns the presented chain and whether it is trusted
+fn fetch_chain(
+    target: &Target,
+) -> Result<(Vec<CertificateDer<'static>>, Result<(), String>), String> {
+    let provider = Arc::new(ring::default_provider());
+    let roots = RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    let verifier = Arc::new(RecordingVerifier {
+        inner: WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+            .build()
+            .map_err(|e| e.to_string())?,
+        trust: Mutex::new(None),
+    });
+
+    let config = ClientConfig::builder_with_provider(provider as Arc<CryptoProvider>)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?
+        .dangerous()
+        .with_custom_certificate_verifier(verifier.clone())
+        .with_no_client_auth();
+
+    let name = target.server_name.as_ref().unwrap_or(&target.host);
+    let server_name = ServerName::try_from(name.clone()).map_err(|e| e.to_string())?;
+
+    let address = (target.host.as_str(), target.port)
+        .to_socket_addrs()
+        .map_err(|e| e.to_string())?
+        .next()
+        .ok_or_else(|| format!("{} does not resolve to an address", target.host))?;
+    let mut stream = TcpStream::connect_timeout(&address, TIMEOUT).map_err(|e| e.to_string())?;
+    stream
+        .set_read_timeout(Some(TIMEOUT))
+        .and_then(|_| stream.set_write_timeout(Some(TIMEOUT)))
+        .map_err(|e| e.to_string())?;
+
+    let mut connection =
+        ClientConnection::new(Arc::new(config), server_name).map_err(|e| e.to_string())?;
+    while connection.is_handshaking() {
+        connection
+            .complete_io(&mut stream)
+            .map_err(|e| format!("TLS handshake failed: {e}"))?;
+    }
+
+    let chain = connection
+        .peer_certificates()
+        .ok_or("the server did not present a certificate")?
+        .iter()
+        .map(|c| c.clone().into_owned())
+        .collect();
+    let trust = verifier
+        .trust
+        .lock()
+        .unwrap()
+        .take()
+        .unwrap_or_else(|| Err("the certificate was not verified".into()));
+
+    Ok((chain, trust))
+}
+
+fn parse(
+    chain: &[CertificateDer<'_>],
+    trust: Result<(), String>,
+    now: DateTime<Utc>,
+) -> Result<Certificate, String> {
+    let leaf = chain.first().ok_or("the certificate chain is empty")?;
+    let (_, cert) = X509Certificate::from_der(leaf).map_err(|e| e.to_string())?;
+
+    let not_after = DateTime::from_timestamp(cert.validity().not_after.timestamp(), 0)
+        .ok_or("the expiry date is out of range")?;
+
+    let sans = match cert.subject_alternative_name() {
+        Ok(Some(extension)) => extension
+            .value
+            .general_names
+            .iter()
+            .filter_map(|name| match name {
+                GeneralName::DNSName(dns) => Some(dns.to_string()),
+                GeneralName::IPAddress(&[a, b, c, d]) => Some(format!("{a}.{b}.{c}.{d}")),
+                GeneralName::IPAddress(bytes) => <[u8; 16]>::try_from(*bytes)
+                    .ok()
+                    .map(|ip| std::net::Ipv6Addr::from(ip).to_string()),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    Ok(Certificate {
+        subject: cert.subject().to_string(),
+        issuer: cert.issuer().to_string(),
+        not_after,
+        days_remaining: (not_after - now).num_days(),
+        sans,
+        chain_length: chain.len(),
+        trusted: trust.is_ok(),
+        trust_error: trust.err(),
+    })
+}
+
+fn check(config: &Config, target: &Target, now: DateTime<Utc>) -> CertificateStatus {
+    let certificate = fetch_chain(target).and_then(|(chain, trust)| parse(&chain, trust, now));
+
+    match certificate {
+        Ok(certificate) => CertificateStatus {
+            host: target.host.clone(),
+            port: target.port,
+            level: level(config, certificate.days_remaining),
+            certificate: Some(certificate),
+            error: None,
+        },
+        // not being able to check a certificate is worth a look, but does not mean it expires
+        Err(error) => CertificateStatus {
+            host: target.host.clone(),
+            port: target.port,
+            level: Level::Warning,
+            certificate: None,
+            error: Some(error),
+        },
+    }
+}
+
+impl WidgetBackend for Config {
+    type Output = Output;
+
+    fn run(&self, ctx: &mut BackendContext<'_>) -> Result<Option<Self::Output>, BackendError> {
+        let now = Utc::now();
+        let certificates: Vec<_> = self.targets.iter().map(|t| check(self, t, now)).collect();
+
+        for status in &certificates {
+            let name = format!("{}:{}", status.host, status.port);
+            match (&status.certificate, &status.error) {
+                (Some(cert), _) if status.level > Level::Info => {
+                    let when = if cert.days_remaining < 0 {
+                        "has expired"
+                    } else {
+                        "expires soon"
+                    };
+                    ctx.notify(
+                        status.level,
+                        format!("Certificate of {name} {when}"),
+                        format!(
+                            "Valid until {} ({} days), issued by {}",
+                            cert.not_after.format("%Y-%m-%d %H:%M UTC"),
+                            cert.days_remaining,
+                            cert.issuer
+                        ),
+                    );
+                }
+                (None, Some(error)) => ctx.notify(
+                    status.level,
+                    format!("Could not check the certificate of {name}"),
+                    error.clone(),
+                ),
+                _ => {}
+            }
+        }
+
+        Ok(Some(Output { certificates }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::TcpListener, thread};
+
+    use chrono::TimeDelta;
+    use rcgen::{CertificateParams, KeyPair};
+    use rustls::{
+        pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer},
+        ServerConfig, ServerConnection,
+    };
+
+    use super::*;
+
+    /// Starts a local TLS server with a self-signed certificate for `localhost` that expires in
+    /// `days` days, and returns its port
+    fn serve(days: i64) -> u16 {
+        let key = KeyPair::generate().unwrap();
+        let mut params =
+            CertificateParams::new(vec!["localhost".into(), "127.0.0.1".into()]).unwrap();
+        let date =
+            |d: DateTime<Utc>| rcgen::date_time_ymd(d.year(), d.month() as u8, d.day() as u8);
+        params.not_before = date(Utc::now() - TimeDelta::days(days.abs() + 30));
+        params.not_after = date(Utc::now() + TimeDelta::days(days));
+        let cert = params.self_signed(&key).unwrap();
+
+        let config = Arc::new(
+            ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+                .with_safe_default_protocol_versions()
+                .unwrap()
+                .with_no_client_auth()
+                .with_single_cert(
+                    vec![cert.der().clone()],
+                    PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key.serialize_der())),
+                )
+                .unwrap(),
+        );
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else {
+                    continue;
+                };
+                let mut connection = ServerConnection::new(config.clone()).unwrap();
+                while connection.is_handshaking() {
+                    if connection.complete_io(&mut stream).is_err() {
+                        break;
+                    }
+                }
+                // flush the rest of the handshake
+                let _ = connection.complete_io(&mut stream);
+            }
+        });
+
+        port
+    }
+
+    fn config(port: u16) -> Config {
+        Config {
+            targets: vec![Target {
+                host: "127.0.0.1".into(),
+                port,
+                server_name: Some("localhost".into()),
+            }],
+            warning_days: 21,
+            critical_days: 7,
+        }
+    }
+
+    fn check_first(config: &Config) -> CertificateStatus {
+        check(config, &config.targets[0], Utc::now())
+    }
+
+    #[test]
+    fn reads_certificate() {
+        let status = check_first(&config(serve(100)));
+        assert_eq!(status.error, None);
+        assert_eq!(status.level, Level::Info);
+
+        let cert = status.certificate.unwrap();
+        assert!((99..=100).contains(&cert.days_remaining), "{cert:?}");
+        assert_eq!(cert.sans, vec!["localhost", "127.0.0.1"]);
+        assert_eq!(cert.chain_length, 1);
+        // self-signed certificates are not trusted by the Mozilla roots
+        assert!(!cert.trusted);
+        assert!(cert.trust_error.is_some());
+    }
+
+    #[test]
+    fn levels_follow_thresholds() {
+        let status = check_first(&config(serve(14)));
+        assert_eq!(status.level, Level::Warning);
+
+        let status = check_first(&config(serve(3)));
+        assert_eq!(status.level, Level::Critical);
+    }
+
+    #[test]
+    fn expired_certificate_is_critical() {
+        let status = check_first(&config(serve(-5)));
+        assert_eq!(status.level, Level::Critical);
+        assert!(status.certificate.unwrap().days_remaining < 0);
+    }
+
+    #[test]
+    fn error_when_connection_fails() {
+        // bind to get a free port and close it again
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        let status = check_first(&config(port));
+        assert_eq!(status.level, Level::Warning);
+        assert_eq!(status.certificate, None);
+        assert!(status.error.is_some());
+    }
+}
@@ -0,0 +1,32 @@
+use common::{
+    clothing::{Config, Output},
+    weather,
+};
+
+use super::{BackendContext, WidgetBackend};
+
+impl WidgetBackend for Config {
+    type Output = Output;
+
+    fn run(
+        &self,
+        ctx: &mut BackendContext<'_>,
+    ) -> Result<Option<Self::Output>, super::BackendError> {
+        // nothing to advise on until the weather widget has produced something
+        let Some(weather) = ctx.upstream::<weather::Output>(&self.weather) else {
+            return Ok(None);
+        };
+
+        let advice = match weather.temperature {
+            t if t < 0.0 => "Winter jacket, gloves and a hat",
+            t if t < 10.0 => "A warm jacket",
+            t if t < 18.0 => "A light jacket or a sweater",
+            _ => "T-shirt weather",
+        };
+
+        Ok(Some(Output {
+            temperature: weather.temperature,
+            advice: advice.into(),
+        }))
+    }
+}
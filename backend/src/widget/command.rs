@@ -0,0 +1,341 @@
+use std::{
+    io::{self, Read},
+    os::unix::process::CommandExt,
+    process::{Command, ExitStatus, Stdio},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use common::{
+    backend::BackendError,
+    command::{Config, Output, Parse},
+};
+use nix::{
+    sys::signal::{killpg, Signal},
+    unistd::Pid,
+};
+use serde_json::{Map, Number, Value};
+
+use super::{BackendContext, WidgetBackend};
+
+/// How often to check whether the command has exited
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// How much of stdout and of stderr is kept, the rest is dropped
+const MAX_OUTPUT_BYTES: u64 = 1024 * 1024;
+
+/// What the command printed to one of its pipes
+#[derive(Debug, Default)]
+struct Captured {
+    text: String,
+    /// Number of bytes that were dropped after the first [`MAX_OUTPUT_BYTES`]
+    dropped: u64,
+}
+
+/// What the command printed and how it exited
+struct Finished {
+    /// `None` if the command was killed because of the timeout
+    status: Option<ExitStatus>,
+    stdout: Captured,
+    stderr: Captured,
+}
+
+/// Reads up to `limit` bytes from the pipe and discards the rest
+fn read_pipe<R: Read + Send + 'static>(pipe: Option<R>, limit: u64) -> JoinHandle<Captured> {
+    thread::spawn(move || {
+        let mut buffer = Vec::new();
+        let mut dropped = 0;
+        if let Some(pipe) = pipe {
+            let mut pipe = pipe.take(limit);
+            let _ = pipe.read_to_end(&mut buffer);
+            // keep reading, the command would block once the pipe is full
+            dropped = io::copy(&mut pipe.into_inner(), &mut io::sink()).unwrap_or(0);
+        }
+        Captured {
+            text: String::from_utf8_lossy(&buffer).into_owned(),
+            dropped,
+        }
+    })
+}
+
+fn execute(config: &Config) -> Result<Finished, BackendError> {
+    let (program, args) = config
+        .argv
+        .split_first()
+        .ok_or_else(|| BackendError::Permanent("argv is empty".into()))?;
+
+    let mut command = Command::new(program);
+    command
+        .args(args)
+        .envs(&config.env)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        // own process group, so that processes started by the command are killed with it
+        .process_group(0);
+    if let Some(dir) = &config.working_dir {
+        command.current_dir(dir);
+    }
+
+    let mut child = command
+        .spawn()
+        .map_err(|e| BackendError::Permanent(format!("could not start {program}: {e}")))?;
+
+    // read both pipes while waiting, a full pipe would block the command
+    let stdout = read_pipe(child.stdout.take(), MAX_OUTPUT_BYTES);
+    let stderr = read_pipe(child.stderr.take(), MAX_OUTPUT_BYTES);
+
+    let group = Pid::from_raw(child.id() as i32);
+    let deadline = Instant::now() + Duration::from_secs(config.timeout_seconds);
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => {
+                // processes the command left running in the background would keep the pipes
+                // open, so they are killed as well
+                let _ = killpg(group, Signal::SIGKILL);
+                break Some(status);
+            }
+            Ok(None) if Instant::now() >= deadline => {
+                let _ = killpg(group, Signal::SIGKILL);
+                let _ = child.wait();
+                break None;
+            }
+            Ok(None) => thread::sleep(POLL_INTERVAL),
+            Err(e) => {
+                return Err(BackendError::Transient(format!(
+                    "could not wait for {program}: {e}"
+                )))
+            }
+        }
+    };
+
+    Ok(Finished {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+/// Turns a value from a `key=value` line into a number or boolean if it looks like one
+fn scalar(value: &str) -> Value {
+    match value {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ => value
+            .parse::<i64>()
+            .map(Value::from)
+            .ok()
+            .or_else(|| {
+                value
+                    .parse::<f64>()
+                    .ok()
+                    .and_then(Number::from_f64)
+                    .map(Value::Number)
+            })
+            .unwrap_or_else(|| Value::String(value.into())),
+    }
+}
+
+fn parse(parse: Parse, stdout: &str) -> Result<Option<Output>, String> {
+    let text = stdout.trim();
+    match parse {
+        Parse::None => Ok(None),
+        Parse::Text => Ok(Some(Value::String(text.into()))),
+        Parse::Json => serde_json::from_str(text)
+            .map(Some)
+            .map_err(|e| format!("stdout is not valid JSON: {e}")),
+        Parse::Number => text
+            .parse::<f64>()
+            .ok()
+            .and_then(Number::from_f64)
+            .map(|n| Some(Value::Number(n)))
+            .ok_or_else(|| format!("stdout is not a number: {text:?}")),
+        Parse::KeyValue => {
+            let mut object = Map::new();
+            for (number, line) in text.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let (key, value) = line
+                    .split_once('=')
+                    .ok_or_else(|| format!("line {} is not key=value: {line:?}", number + 1))?;
+                object.insert(key.trim().into(), scalar(value.trim()));
+            }
+            Ok(Some(Value::Object(object)))
+        }
+    }
+}
+
+impl WidgetBackend for Config {
+    type Output = Output;
+
+    fn run(&self, ctx: &mut BackendContext<'_>) -> Result<Option<Self::Output>, BackendError> {
+        ctx.log(format!("$ {}", self.argv.join(" ")));
+        let finished = execute(self)?;
+
+        let log_dropped = |ctx: &mut BackendContext<'_>, pipe: &str, captured: &Captured| {
+            if captured.dropped > 0 {
+                ctx.log(format!(
+                    "--- {pipe} truncated, {} more bytes dropped ---",
+                    captured.dropped
+                ));
+            }
+        };
+        if !finished.stdout.text.is_empty() {
+            ctx.log(&finished.stdout.text);
+        }
+        log_dropped(ctx, "stdout", &finished.stdout);
+        if !finished.stderr.text.is_empty() {
+            ctx.log("--- stderr ---");
+            ctx.log(&finished.stderr.text);
+        }
+        log_dropped(ctx, "stderr", &finished.stderr);
+
+        let Some(status) = finished.status else {
+            return Err(BackendError::Transient(format!(
+                "killed after {} seconds",
+                self.timeout_seconds
+            )));
+        };
+        ctx.log(format!("--- {status} ---"));
+
+        // scripts usually fail because of something they depend on, so failures are retried
+        if !status.success() {
+            return Err(BackendError::Transient(match status.code() {
+                Some(code) => format!("exited with code {code}"),
+                None => format!("exited because of a signal ({status})"),
+            }));
+        }
+
+        parse(self.parse, &finished.stdout.text).map_err(BackendError::Permanent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::widget::BackendStateStorage;
+
+    fn config(argv: &[&str]) -> Config {
+        Config {
+            argv: argv.iter().map(|a| a.to_string()).collect(),
+            parse: Parse::None,
+            working_dir: None,
+            env: BTreeMap::new(),
+            timeout_seconds: 5,
+        }
+    }
+
+    #[test]
+    fn parses_stdout() {
+        assert_eq!(parse(Parse::None, "ignored"), Ok(None));
+        assert_eq!(parse(Parse::Text, "  hello\n"), Ok(Some(json!("hello"))));
+        assert_eq!(
+            parse(Parse::Json, r#"{"ok": true, "items": [1, 2]}"#),
+            Ok(Some(json!({ "ok": true, "items": [1, 2] })))
+        );
+        assert!(parse(Parse::Json, "{")
+            .unwrap_err()
+            .starts_with("stdout is not valid JSON"));
+        assert_eq!(parse(Parse::Number, "42.5\n"), Ok(Some(json!(42.5))));
+        assert_eq!(
+            parse(Parse::Number, "many"),
+            Err(r#"stdout is not a number: "many""#.to_string())
+        );
+    }
+
+    #[test]
+    fn parses_key_value_lines() {
+        let stdout =
+            "# a comment\nusers = 3\n\nload=0.5\nhealthy=true\nversion = 1.2.3\nname=a=b\n";
+        assert_eq!(
+            parse(Parse::KeyValue, stdout),
+            Ok(Some(json!({
+                "users": 3,
+                "load": 0.5,
+                "healthy": true,
+                "version": "1.2.3",
+                "name": "a=b",
+            })))
+        );
+        assert_eq!(
+            parse(Parse::KeyValue, "a=1\nnot a pair"),
+            Err(r#"line 2 is not key=value: "not a pair""#.to_string())
+        );
+    }
+
+    #[test]
+    fn converts_scalars() {
+        assert_eq!(scalar("true"), json!(true));
+        assert_eq!(scalar("false"), json!(false));
+        assert_eq!(scalar("-12"), json!(-12));
+        assert_eq!(scalar("1e3"), json!(1000.0));
+        assert_eq!(scalar("NaN"), json!("NaN"));
+        assert_eq!(scalar("True"), json!("True"));
+        assert_eq!(scalar(""), json!(""));
+    }
+
+    #[test]
+    fn captures_output_and_status() {
+        let finished = execute(&config(&["sh", "-c", "echo out; echo err >&2; exit 3"])).unwrap();
+        assert_eq!(finished.stdout.text, "out\n");
+        assert_eq!(finished.stderr.text, "err\n");
+        assert_eq!(finished.status.and_then(|s| s.code()), Some(3));
+    }
+
+    #[test]
+    fn does_not_wait_for_background_processes() {
+        let start = Instant::now();
+        let finished = execute(&config(&["sh", "-c", "sleep 30 & echo started"])).unwrap();
+
+        assert_eq!(finished.stdout.text, "started\n");
+        assert!(finished.status.is_some_and(|s| s.success()));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn kills_commands_after_timeout() {
+        let finished = execute(&Config {
+            timeout_seconds: 1,
+            ..config(&["sleep", "30"])
+        })
+        .unwrap();
+        assert!(finished.status.is_none());
+    }
+
+    #[test]
+    fn caps_pipe_output() {
+        let pipe = io::Cursor::new(b"0123456789".to_vec());
+        let captured = read_pipe(Some(pipe), 4).join().unwrap();
+        assert_eq!(captured.text, "0123");
+        assert_eq!(captured.dropped, 6);
+
+        let captured = read_pipe(None::<io::Empty>, 4).join().unwrap();
+        assert_eq!((captured.text.as_str(), captured.dropped), ("", 0));
+    }
+
+    #[test]
+    fn logs_dropped_output() {
+        // more than fits in a pipe, so the command only exits if the rest is drained
+        let config = config(&[
+            "sh",
+            "-c",
+            "head -c 1100000 /dev/zero | tr '\\0' a; echo done >&2",
+        ]);
+        let mut state = BackendStateStorage::new();
+        let mut ctx = BackendContext::for_test("script", &mut state);
+        config.run(&mut ctx).unwrap();
+
+        assert!(ctx.log.contains(
+            "--- stdout truncated, 51424 more bytes dropped ---\n--- stderr ---\ndone\n"
+        ));
+        // the command line comes first
+        let stdout = ctx.log.lines().nth(1).unwrap();
+        assert_eq!(stdout.len(), MAX_OUTPUT_BYTES as usize);
+    }
+}
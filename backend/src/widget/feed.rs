@@ -0,0 +1,227 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{prelude::*, TimeDelta};
+use common::{
+    backend::BackendError,
+    feed::{Config, Item, Output},
+};
+
+use super::{read_source, BackendContext, WidgetBackend};
+
+#[derive(Debug)]
+struct BackendState {
+    /// When each item currently in the feeds was first seen, `None` for the items that were
+    /// already there on the first run
+    first_seen: HashMap<String, Option<DateTime<Utc>>>,
+    started: bool,
+}
+
+/// An item as parsed from a feed, before it is compared with earlier runs
+#[derive(Debug)]
+struct Entry {
+    id: String,
+    title: String,
+    link: Option<String>,
+    date: Option<DateTime<Utc>>,
+    feed: String,
+}
+
+fn parse(source: &str, text: &str) -> Result<Vec<Entry>, BackendError> {
+    let feed = feed_rs::parser::parse(text.as_bytes())
+        .map_err(|e| BackendError::Permanent(format!("could not parse {source}: {e}")))?;
+    let feed_title = feed
+        .title
+        .map(|t| t.content)
+        .unwrap_or_else(|| source.to_string());
+
+    Ok(feed
+        .entries
+        .into_iter()
+        .map(|entry| {
+            // Atom entries can have several links, the alternate one points at the article
+            let link = entry
+                .links
+                .iter()
+                .find(|l| l.rel.as_deref().is_none_or(|rel| rel == "alternate"))
+                .or(entry.links.first())
+                .map(|l| l.href.clone());
+
+            Entry {
+                id: entry.id,
+                title: entry
+                    .title
+                    .map(|t| t.content.trim().to_string())
+                    .unwrap_or_else(|| "(no title)".into()),
+                link,
+                date: entry.published.or(entry.updated),
+                feed: feed_title.clone(),
+            }
+        })
+        .collect())
+}
+
+impl WidgetBackend for Config {
+    type Output = Output;
+
+    fn run(&self, ctx: &mut BackendContext<'_>) -> Result<Option<Self::Output>, BackendError> {
+        let mut entries = Vec::new();
+        for source in &self.feeds {
+            entries.extend(parse(source, &read_source(source)?)?);
+        }
+        entries.sort_by_key(|e| std::cmp::Reverse(e.date));
+
+        // the same story can be in several feeds, keep the first (newest) one
+        let mut ids = HashSet::new();
+        let mut links = HashSet::new();
+        entries.retain(|e| {
+            let new_link = e.link.as_ref().is_none_or(|l| links.insert(l.clone()));
+            ids.insert(e.id.clone()) && new_link
+        });
+
+        let now = Utc::now();
+        let state = ctx.get_state_or(BackendState {
+            first_seen: HashMap::new(),
+            started: false,
+        });
+        let seen_now = state.started.then_some(now);
+        let first_seen: HashMap<_, _> = entries
+            .iter()
+            .map(|e| {
+                let first_seen = state.first_seen.get(&e.id).copied().unwrap_or(seen_now);
+                (e.id.clone(), first_seen)
+            })
+            .collect();
+        // forget items that have dropped out of the feeds
+        state.first_seen = first_seen;
+        state.started = true;
+
+        let new_after = now - TimeDelta::hours(self.new_hours);
+        Ok(Some(Output {
+            items: entries
+                .into_iter()
+                .take(self.count)
+                .map(|e| {
+                    let first_seen = state.first_seen[&e.id];
+                    Item {
+                        title: e.title,
+                        link: e.link,
+                        date: e.date,
+                        feed: e.feed,
+                        first_seen,
+                        new: first_seen.is_some_and(|t| t >= new_after),
+                    }
+                })
+                .collect(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::widget::BackendStateStorage;
+
+    fn fixture(name: &str) -> String {
+        format!("{}/fixtures/feed/{name}", env!("CARGO_MANIFEST_DIR"))
+    }
+
+    fn config(feeds: &[&str]) -> Config {
+        Config {
+            feeds: feeds.iter().map(|f| fixture(f)).collect(),
+            count: 20,
+            new_hours: 24,
+        }
+    }
+
+    /// Runs the config with the given backend state, as the scheduler would
+    fn run(config: &Config, state: &mut BackendStateStorage) -> Output {
+        let mut ctx = BackendContext::for_test("news", state);
+        config.run(&mut ctx).unwrap().unwrap()
+    }
+
+    #[test]
+    fn parses_rss() {
+        let output = run(&config(&["rss.xml"]), &mut BackendStateStorage::new());
+
+        let titles: Vec<_> = output.items.iter().map(|i| i.title.as_str()).collect();
+        assert_eq!(titles, ["Second post", "First post", "Undated post"]);
+
+        let item = &output.items[0];
+        assert_eq!(item.feed, "Example News");
+        assert_eq!(
+            item.link.as_deref(),
+            Some("https://news.example.com/second")
+        );
+        assert_eq!(
+            item.date,
+            Some(Utc.with_ymd_and_hms(2024, 3, 2, 8, 30, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn parses_atom() {
+        let output = run(&config(&["atom.xml"]), &mut BackendStateStorage::new());
+
+        let titles: Vec<_> = output.items.iter().map(|i| i.title.as_str()).collect();
+        assert_eq!(titles, ["Release 1.1", "Release 1.0"]);
+
+        let item = &output.items[0];
+        assert_eq!(item.feed, "Example Releases");
+        // the alternate link is used rather than the first one
+        assert_eq!(
+            item.link.as_deref(),
+            Some("https://example.com/releases/1.1")
+        );
+        // entries without a published date fall back to when they were updated
+        assert_eq!(
+            item.date,
+            Some(Utc.with_ymd_and_hms(2024, 3, 5, 12, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn removes_duplicates() {
+        let output = run(
+            &config(&["rss.xml", "rss.xml", "atom.xml"]),
+            &mut BackendStateStorage::new(),
+        );
+        assert_eq!(output.items.len(), 5);
+    }
+
+    #[test]
+    fn highlights_new_items() {
+        let mut state = BackendStateStorage::new();
+
+        // nothing is new on the first run
+        let output = run(&config(&["rss.xml"]), &mut state);
+        assert!(output
+            .items
+            .iter()
+            .all(|i| !i.new && i.first_seen.is_none()));
+
+        let output = run(&config(&["rss-updated.xml"]), &mut state);
+        let new: Vec<_> = output
+            .items
+            .iter()
+            .filter(|i| i.new)
+            .map(|i| i.title.as_str())
+            .collect();
+        assert_eq!(new, ["Third post"]);
+
+        // still new on the next run
+        let output = run(&config(&["rss-updated.xml"]), &mut state);
+        assert!(output.items.iter().any(|i| i.new));
+    }
+
+    #[test]
+    fn limits_count() {
+        let output = run(
+            &Config {
+                count: 1,
+                ..config(&["rss.xml"])
+            },
+            &mut BackendStateStorage::new(),
+        );
+        assert_eq!(output.items.len(), 1);
+    }
+}
@@ -0,0 +1,224 @@
+use std::time::Duration;
+
+use common::{
+    backend::BackendError,
+    fetch::{Config, Field, Method, Output},
+};
+use serde_json::Value;
+use serde_json_path::JsonPath;
+
+use super::{BackendContext, WidgetBackend};
+
+/// Replaces each `${name}` in a header value with the secret called `name`
+fn substitute(value: &str, ctx: &BackendContext<'_>) -> Result<String, BackendError> {
+    let mut result = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        let end = rest[start..]
+            .find('}')
+            .map(|end| start + end)
+            .ok_or_else(|| BackendError::Permanent(format!("unterminated ${{ in {value:?}")))?;
+        result.push_str(&rest[..start]);
+        result.push_str(ctx.secret(&rest[start + 2..end])?);
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// Picks the value of each field out of the response. A path that matches nothing gives `null`,
+/// one that matches several values gives an array of them.
+fn extract(
+    fields: &[Field],
+    json: &Value,
+    ctx: &mut BackendContext<'_>,
+) -> Result<Output, BackendError> {
+    let mut output = Output::new();
+    for field in fields {
+        let path = JsonPath::parse(&field.path)
+            .map_err(|e| BackendError::Permanent(format!("invalid path {}: {e}", field.path)))?;
+
+        let value = match path.query(json).all().as_slice() {
+            [] => {
+                ctx.log(format!("{} ({}) matched nothing", field.name, field.path));
+                Value::Null
+            }
+            [value] => (*value).clone(),
+            values => values.iter().map(|v| (*v).clone()).collect(),
+        };
+        output.insert(field.name.clone(), value);
+    }
+    Ok(output)
+}
+
+impl WidgetBackend for Config {
+    type Output = Output;
+
+    fn run(&self, ctx: &mut BackendContext<'_>) -> Result<Option<Self::Output>, BackendError> {
+        let headers = self
+            .headers
+            .iter()
+            .map(|(name, value)| Ok((name.as_str(), substitute(value, ctx)?)))
+            .collect::<Result<Vec<_>, BackendError>>()?;
+
+        let agent: ureq::Agent = ureq::Agent::config_builder()
+            .timeout_global(Some(Duration::from_secs(self.timeout_seconds)))
+            .http_status_as_error(false)
+            .build()
+            .into();
+
+        let response = match self.method {
+            Method::Get => headers
+                .iter()
+                .fold(agent.get(&self.url), |r, (name, value)| {
+                    r.header(*name, value)
+                })
+                .call(),
+            Method::Post => {
+                let request = headers
+                    .iter()
+                    .fold(agent.post(&self.url), |r, (name, value)| {
+                        r.header(*name, value)
+                    });
+                match &self.body {
+                    Some(body) => request.send_json(body),
+                    None => request.send_empty(),
+                }
+            }
+        };
+        let mut response = response
+            .map_err(|e| BackendError::Transient(format!("could not fetch {}: {e}", self.url)))?;
+
+        let status = response.status();
+        ctx.log(format!("{:?} {} -> {status}", self.method, self.url));
+        if !status.is_success() {
+            return Err(BackendError::Transient(format!(
+                "{} responded with {status}",
+                self.url
+            )));
+        }
+
+        let json: Value = response
+            .body_mut()
+            .read_json()
+            .map_err(|e| BackendError::Permanent(format!("response is not JSON: {e}")))?;
+
+        extract(&self.fields, &json, ctx).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use common::fetch::Format;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        secrets::{SecretSource, Secrets},
+        widget::BackendStateStorage,
+    };
+
+    /// Runs `test` with a context that was granted the `declared` secrets, of which only `token`
+    /// and `other` are defined
+    fn with_context<T>(declared: &[&str], test: impl FnOnce(&mut BackendContext<'_>) -> T) -> T {
+        let defined = HashMap::from([
+            ("token".to_string(), SecretSource::Value("abc".into())),
+            ("other".to_string(), SecretSource::Value("xyz".into())),
+        ]);
+        let declared: Vec<String> = declared.iter().map(|d| d.to_string()).collect();
+
+        let mut state = BackendStateStorage::new();
+        let mut ctx = BackendContext::for_test("status", &mut state);
+        ctx.secrets = Secrets::resolve(&defined).unwrap().granted(&declared);
+        test(&mut ctx)
+    }
+
+    fn error(result: Result<impl std::fmt::Debug, BackendError>) -> String {
+        match result.unwrap_err() {
+            BackendError::Permanent(error) => error,
+            error => panic!("expected a permanent error, got {error:?}"),
+        }
+    }
+
+    #[test]
+    fn substitutes_secrets() {
+        with_context(&["token"], |ctx| {
+            assert_eq!(substitute("Bearer ${token}", ctx).unwrap(), "Bearer abc");
+            assert_eq!(substitute("${token}:${token}!", ctx).unwrap(), "abc:abc!");
+            assert_eq!(
+                substitute("no secrets $ {}", ctx).unwrap(),
+                "no secrets $ {}"
+            );
+            assert_eq!(
+                error(substitute("Bearer ${token", ctx)),
+                r#"unterminated ${ in "Bearer ${token""#
+            );
+        });
+    }
+
+    #[test]
+    fn rejects_unavailable_secrets() {
+        // `other` is defined but not declared by the widget, `missing` is declared but not defined
+        with_context(&["token", "missing"], |ctx| {
+            assert_eq!(
+                error(substitute("${other}", ctx)),
+                "secret other is not available to this widget"
+            );
+            assert_eq!(
+                error(substitute("${missing}", ctx)),
+                "secret missing is not available to this widget"
+            );
+        });
+    }
+
+    fn field(name: &str, path: &str) -> Field {
+        Field {
+            name: name.into(),
+            path: path.into(),
+            unit: None,
+            format: Format::Auto,
+            decimals: None,
+        }
+    }
+
+    #[test]
+    fn extracts_fields() {
+        let response = json!({
+            "healthy": true,
+            "database": { "latency_ms": 12.5, "pools": [3, 4] },
+            "services": [{ "name": "api" }, { "name": "worker" }],
+        });
+        let fields = [
+            field("healthy", "$.healthy"),
+            field("latency", "$.database.latency_ms"),
+            field("database", "$.database"),
+            field("pools", "$.database.pools"),
+            field("services", "$.services[*].name"),
+            field("missing", "$.disk.free"),
+        ];
+
+        let (output, log) = with_context(&[], |ctx| {
+            let output = extract(&fields, &response, ctx).unwrap();
+            (output, ctx.log.clone())
+        });
+
+        assert_eq!(output["healthy"], json!(true));
+        assert_eq!(output["latency"], json!(12.5));
+        // values that are not scalars are passed on as they are
+        assert_eq!(output["database"], response["database"]);
+        assert_eq!(output["pools"], json!([3, 4]));
+        assert_eq!(output["services"], json!(["api", "worker"]));
+        assert_eq!(output["missing"], Value::Null);
+        assert_eq!(log, "missing ($.disk.free) matched nothing\n");
+    }
+
+    #[test]
+    fn rejects_invalid_paths() {
+        with_context(&[], |ctx| {
+            let result = extract(&[field("bad", "database.latency")], &json!({}), ctx);
+            assert!(error(result).starts_with("invalid path database.latency"));
+        });
+    }
+}
@@ -0,0 +1,280 @@
+use std::{path::Path, process::Command};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use common::{
+    backend::BackendError,
+    git::{Commit, Config, Output, Repository, RepositoryStatus},
+};
+
+use super::{BackendContext, WidgetBackend};
+
+/// Runs a read-only git command in the repository and returns its trimmed stdout, or the error
+/// git printed
+fn git(path: &str, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(path)
+        .args(args)
+        // never ask for credentials or anything else
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .output()
+        .map_err(|e| format!("could not run git: {e}"))?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        // the first line says what went wrong, the rest are hints
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(stderr.lines().next().unwrap_or("git failed").to_string())
+    }
+}
+
+fn last_commit(path: &str) -> Result<Commit, String> {
+    let log = git(path, &["log", "-1", "--format=%h%x00%an%x00%ct%x00%s"])?;
+    let mut fields = log.splitn(4, '\0');
+    let mut next = || fields.next().unwrap_or_default().to_string();
+    let (hash, author, timestamp, message) = (next(), next(), next(), next());
+
+    let date = timestamp
+        .parse()
+        .ok()
+        .and_then(|t| DateTime::from_timestamp(t, 0))
+        .ok_or_else(|| format!("unexpected commit date {timestamp:?}"))?;
+    Ok(Commit {
+        hash,
+        author,
+        message,
+        date,
+    })
+}
+
+/// Counts the commits on HEAD that are not on `upstream` and the other way around
+fn ahead_behind(path: &str, upstream: &str) -> Result<(u64, u64), String> {
+    let counts = git(
+        path,
+        &[
+            "rev-list",
+            "--left-right",
+            "--count",
+            &format!("HEAD...{upstream}"),
+        ],
+    )?;
+    let parse = |count: Option<&str>| count.and_then(|c| c.parse().ok());
+    let mut counts = counts.split_whitespace();
+    parse(counts.next())
+        .zip(parse(counts.next()))
+        .ok_or_else(|| "unexpected output from git rev-list".to_string())
+}
+
+fn status(repository: &Repository, since: DateTime<Utc>) -> RepositoryStatus {
+    let path = repository.path.as_str();
+    let name = repository.name.clone().unwrap_or_else(|| {
+        Path::new(path)
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.to_string())
+    });
+    let mut status = RepositoryStatus {
+        name,
+        branch: None,
+        last_commit: None,
+        recent_commits: 0,
+        upstream: None,
+        ahead: None,
+        behind: None,
+        error: None,
+    };
+
+    // fails if HEAD is detached
+    status.branch = git(path, &["symbolic-ref", "--quiet", "--short", "HEAD"]).ok();
+
+    match last_commit(path) {
+        Ok(commit) => status.last_commit = Some(commit),
+        Err(e) => {
+            status.error = Some(e);
+            return status;
+        }
+    }
+
+    let mut errors = Vec::new();
+    let since = format!("--since={}", since.to_rfc3339());
+    match git(path, &["rev-list", "--count", &since, "HEAD"]).and_then(|count| {
+        count
+            .parse()
+            .map_err(|_| format!("unexpected output from git rev-list: {count:?}"))
+    }) {
+        Ok(count) => status.recent_commits = count,
+        Err(e) => errors.push(format!("could not count recent commits: {e}")),
+    }
+
+    let upstream = match &repository.upstream {
+        Some(upstream) => Some(upstream.clone()),
+        // a branch without an upstream has nothing to compare with
+        None => git(
+            path,
+            &[
+                "rev-parse",
+                "--abbrev-ref",
+                "--symbolic-full-name",
+                "@{upstream}",
+            ],
+        )
+        .ok(),
+    };
+    if let Some(upstream) = upstream {
+        match ahead_behind(path, &upstream) {
+            Ok((ahead, behind)) => {
+                status.ahead = Some(ahead);
+                status.behind = Some(behind);
+            }
+            Err(e) => errors.push(format!("could not compare with {upstream}: {e}")),
+        }
+        status.upstream = Some(upstream);
+    }
+
+    if !errors.is_empty() {
+        status.error = Some(errors.join(", "));
+    }
+    status
+}
+
+impl WidgetBackend for Config {
+    type Output = Output;
+
+    fn run(&self, ctx: &mut BackendContext<'_>) -> Result<Option<Self::Output>, BackendError> {
+        let since = Utc::now() - TimeDelta::hours(self.recent_hours);
+        let repositories: Vec<_> = self.repositories.iter().map(|r| status(r, since)).collect();
+
+        for (repository, status) in self.repositories.iter().zip(&repositories) {
+            match &status.error {
+                Some(error) => ctx.log(format!("{}: {error}", repository.path)),
+                None => ctx.log(format!(
+                    "{}: {} with {} recent commits",
+                    repository.path,
+                    status.branch.as_deref().unwrap_or("detached HEAD"),
+                    status.recent_commits
+                )),
+            }
+        }
+
+        Ok(Some(Output { repositories }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    /// Runs git in `dir` with a fixed author and committer date, panicking if it fails
+    fn run_git(dir: &Path, date: &str, args: &[&str]) {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(args)
+            .env("GIT_AUTHOR_NAME", "Alice")
+            .env("GIT_AUTHOR_EMAIL", "alice@example.com")
+            .env("GIT_COMMITTER_NAME", "Alice")
+            .env("GIT_COMMITTER_EMAIL", "alice@example.com")
+            .env("GIT_AUTHOR_DATE", date)
+            .env("GIT_COMMITTER_DATE", date)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {args:?} failed: {output:?}");
+    }
+
+    /// A repository on `main` with an old commit that `base` points at and a recent one on top
+    fn repository(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("git_{test}_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let now = Utc::now().to_rfc3339();
+        run_git(&dir, &now, &["init", "--quiet", "--initial-branch=main"]);
+        run_git(
+            &dir,
+            "2020-01-01T12:00:00Z",
+            &["commit", "--quiet", "--allow-empty", "-m", "Initial commit"],
+        );
+        run_git(&dir, &now, &["branch", "base"]);
+        run_git(
+            &dir,
+            &now,
+            &["commit", "--quiet", "--allow-empty", "-m", "Add a feature"],
+        );
+        dir
+    }
+
+    fn repo(path: &Path, upstream: Option<&str>) -> Repository {
+        Repository {
+            path: path.to_string_lossy().into_owned(),
+            name: None,
+            upstream: upstream.map(String::from),
+        }
+    }
+
+    fn since() -> DateTime<Utc> {
+        Utc::now() - TimeDelta::hours(24)
+    }
+
+    #[test]
+    fn reads_branch_and_last_commit() {
+        let dir = repository("last_commit");
+        let status = status(&repo(&dir, None), since());
+
+        assert_eq!(status.error, None);
+        assert_eq!(status.name, dir.file_name().unwrap().to_string_lossy());
+        assert_eq!(status.branch.as_deref(), Some("main"));
+        assert_eq!(status.recent_commits, 1);
+
+        let commit = status.last_commit.unwrap();
+        assert_eq!(commit.message, "Add a feature");
+        assert_eq!(commit.author, "Alice");
+        assert_eq!(commit.hash.len(), 7);
+        assert!(Utc::now() - commit.date < TimeDelta::minutes(1));
+
+        // no upstream is configured
+        assert_eq!((status.upstream, status.ahead), (None, None));
+    }
+
+    #[test]
+    fn compares_with_upstream() {
+        let dir = repository("upstream");
+        let status = status(&repo(&dir, Some("base")), since());
+        assert_eq!(status.upstream.as_deref(), Some("base"));
+        assert_eq!((status.ahead, status.behind), (Some(1), Some(0)));
+
+        run_git(
+            &dir,
+            "2020-01-02T12:00:00Z",
+            &["checkout", "--quiet", "base"],
+        );
+        run_git(
+            &dir,
+            "2020-01-02T12:00:00Z",
+            &["branch", "--quiet", "--set-upstream-to=main"],
+        );
+        let status = super::status(&repo(&dir, None), since());
+        assert_eq!(status.upstream.as_deref(), Some("main"));
+        assert_eq!((status.ahead, status.behind), (Some(0), Some(1)));
+        assert_eq!(status.recent_commits, 0);
+    }
+
+    #[test]
+    fn reports_errors() {
+        let dir = repository("errors");
+        let status = status(&repo(&dir, Some("missing")), since());
+        assert!(status.last_commit.is_some());
+        assert!(status
+            .error
+            .unwrap()
+            .starts_with("could not compare with missing: "));
+
+        let not_a_repository = std::env::temp_dir().join("git_not_a_repository");
+        std::fs::create_dir_all(&not_a_repository).unwrap();
+        let status = super::status(&repo(&not_a_repository, None), since());
+        assert!(status.last_commit.is_none());
+        assert!(status.error.unwrap().contains("not a git repository"));
+    }
+}
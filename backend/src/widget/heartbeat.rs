@@ -0,0 +1,31 @@
+//! Widgets whose runs are pings from external jobs, which are considered down when the pings stop
+use chrono::prelude::*;
+use common::{
+    backend::{BackendRun, Initiator, RunId},
+    heartbeat::{Output, Widget},
+};
+
+/// Longer messages are cut off
+const MAX_MESSAGE_CHARS: usize = 1000;
+
+/// Creates the run that represents a ping, with the body of the request as its message
+pub fn run(definition: &Widget, body: &str) -> BackendRun {
+    let now = Utc::now();
+    let message = body.trim();
+    let output = Output {
+        message: (!message.is_empty()).then(|| message.chars().take(MAX_MESSAGE_CHARS).collect()),
+    };
+
+    BackendRun {
+        id: RunId(0),
+        widget: definition.id.clone(),
+        initiated: Initiator::Heartbeat,
+        started: now,
+        ended: now,
+        log: "".into(),
+        result: Ok(Some(
+            serde_json::to_string(&output).expect("output can be serialized"),
+        )),
+        attempt: None,
+    }
+}
@@ -0,0 +1,423 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom},
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+};
+
+use common::{
+    backend::BackendError,
+    log_tail::{Config, Cursor, Line, Output},
+};
+use regex::Regex;
+
+use super::{BackendContext, WidgetBackend};
+
+/// Longer lines are cut off when they are shown
+const MAX_LINE_CHARS: usize = 1000;
+
+#[derive(Debug)]
+struct BackendState {
+    /// `None` until the first run, which starts at the end of the file
+    cursor: Option<Cursor>,
+    /// Where reading the file the log was rotated to continues, until it has been read to the end
+    rotated: Option<Cursor>,
+    lines: Vec<Line>,
+}
+
+/// What [`read_lines`] does with the bytes after the last newline
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Rest {
+    /// Read again by the next run, the line is still being written
+    Leave,
+    /// Also returned as a line if no newline was found within `max` bytes, a single line longer
+    /// than the limit would otherwise never be read
+    CutLongLine,
+    /// Returned as a line at the end of the file, nothing is written to it anymore
+    Take,
+}
+
+/// Reads the lines from `offset`, at most `max` bytes. Returns the lines and the offset after the
+/// last one.
+fn read_lines(path: &Path, offset: u64, max: u64, rest: Rest) -> io::Result<(Vec<String>, u64)> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    let mut buffer = Vec::new();
+    file.take(max).read_to_end(&mut buffer)?;
+
+    let full = buffer.len() as u64 == max;
+    let end = match buffer.iter().rposition(|b| *b == b'\n') {
+        _ if rest == Rest::Take && !full => buffer.len(),
+        Some(newline) => newline + 1,
+        None if full && rest != Rest::Leave => buffer.len(),
+        None => 0,
+    };
+
+    let lines = String::from_utf8_lossy(&buffer[..end])
+        .lines()
+        .map(String::from)
+        .collect();
+    Ok((lines, offset + end as u64))
+}
+
+/// Finds the file a log was rotated to (e.g. `app.log.1`), which still has the old inode
+fn find_rotated(path: &Path, inode: u64) -> Option<PathBuf> {
+    let name = path.file_name()?.to_str()?;
+    let dir = path.parent().filter(|p| !p.as_os_str().is_empty());
+
+    fs::read_dir(dir.unwrap_or(Path::new(".")))
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            let other = entry.file_name();
+            let other = other.to_string_lossy();
+            other.starts_with(name) && other != name
+        })
+        .find(|entry| entry.metadata().is_ok_and(|m| m.ino() == inode))
+        .map(|entry| entry.path())
+}
+
+impl WidgetBackend for Config {
+    type Output = Output;
+
+    fn run(&self, ctx: &mut BackendContext<'_>) -> Result<Option<Self::Output>, BackendError> {
+        let patterns = self
+            .patterns
+            .iter()
+            .map(|p| Regex::new(&p.regex).map(|r| (p.name.as_str(), r)))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| BackendError::Permanent(format!("invalid pattern: {e}")))?;
+
+        let path = Path::new(&self.path);
+        // the file can be missing for a moment while it is rotated
+        let metadata = fs::metadata(path)
+            .map_err(|e| BackendError::Transient(format!("could not read {}: {e}", self.path)))?;
+        let (inode, size) = (metadata.ino(), metadata.len());
+
+        // without state, continue where the latest successful run stopped
+        let restored = ctx.previous_output::<Output>();
+        let state = ctx.get_state_or(BackendState {
+            cursor: restored.as_ref().map(|o| o.cursor),
+            rotated: None,
+            lines: restored.map(|o| o.lines).unwrap_or_default(),
+        });
+
+        let read_error =
+            |e: io::Error| BackendError::Transient(format!("could not read {}: {e}", self.path));
+        let mut notes: Vec<String> = Vec::new();
+        let mut rotated = false;
+        let offset = match state.cursor {
+            // start at the end, the lines that are already there were not written since a run
+            None => size,
+            Some(cursor) if cursor.inode == inode && cursor.offset <= size => cursor.offset,
+            // truncated, e.g. by `copytruncate`
+            Some(cursor) if cursor.inode == inode => {
+                rotated = true;
+                0
+            }
+            // replaced by a new file, finish reading the old one first
+            Some(cursor) => {
+                rotated = true;
+                if state.rotated.is_some_and(|old| old.inode != cursor.inode) {
+                    notes.push(
+                        "rotated again, the rest of the previously rotated file is skipped".into(),
+                    );
+                }
+                state.rotated = Some(cursor);
+                0
+            }
+        };
+
+        let mut lines = Vec::new();
+        let mut budget = self.max_read_bytes;
+        if let Some(old) = state.rotated {
+            state.rotated = match find_rotated(path, old.inode) {
+                Some(old_path) => {
+                    let (old_lines, old_offset) =
+                        read_lines(&old_path, old.offset, budget, Rest::Take)
+                            .map_err(read_error)?;
+                    let old_size = fs::metadata(&old_path).map_err(read_error)?.len();
+                    lines = old_lines;
+                    budget -= old_offset - old.offset;
+                    (old_offset < old_size).then_some(Cursor {
+                        inode: old.inode,
+                        offset: old_offset,
+                    })
+                }
+                None => {
+                    notes.push("the rotated file is gone, the rest of it is skipped".into());
+                    None
+                }
+            };
+        }
+
+        // the new file is read once the rotated one has been read to the end
+        let rest = if budget == self.max_read_bytes {
+            Rest::CutLongLine
+        } else {
+            Rest::Leave
+        };
+        let (new_lines, offset) = match state.rotated {
+            Some(_) => (Vec::new(), offset),
+            None => read_lines(path, offset, budget, rest).map_err(read_error)?,
+        };
+        lines.extend(new_lines);
+
+        let mut counts: BTreeMap<String, u64> = patterns
+            .iter()
+            .map(|(name, _)| (name.to_string(), 0))
+            .collect();
+        for line in &lines {
+            let mut first = None;
+            for (name, regex) in &patterns {
+                if regex.is_match(line) {
+                    *counts.entry(name.to_string()).or_default() += 1;
+                    first = first.or(Some(*name));
+                }
+            }
+            if let Some(pattern) = first {
+                state.lines.push(Line {
+                    pattern: pattern.into(),
+                    text: line.chars().take(MAX_LINE_CHARS).collect(),
+                });
+            }
+        }
+
+        let excess = state.lines.len().saturating_sub(self.lines);
+        state.lines.drain(..excess);
+        let cursor = Cursor { inode, offset };
+        state.cursor = Some(cursor);
+
+        if let Some(old) = state.rotated {
+            notes.push(format!(
+                "the rotated file is read on from offset {} by the next run",
+                old.offset
+            ));
+        }
+
+        let output = Output {
+            counts,
+            lines: state.lines.clone(),
+            lines_read: lines.len() as u64,
+            rotated,
+            cursor,
+        };
+        ctx.log(format!(
+            "read {} lines up to offset {offset}{}",
+            output.lines_read,
+            if rotated { " after a rotation" } else { "" }
+        ));
+        for note in notes {
+            ctx.log(note);
+        }
+        Ok(Some(output))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::OpenOptions, io::Write};
+
+    use common::log_tail::Pattern;
+
+    use super::*;
+    use crate::widget::BackendStateStorage;
+
+    /// An empty directory for the log files of a test
+    fn directory(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("log_tail_{test}_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn append(path: &Path, text: &str) {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap();
+        file.write_all(text.as_bytes()).unwrap();
+    }
+
+    fn config(path: &Path) -> Config {
+        Config {
+            path: path.to_string_lossy().into_owned(),
+            patterns: vec![
+                Pattern {
+                    name: "errors".into(),
+                    regex: "(?i)error".into(),
+                },
+                Pattern {
+                    name: "oom".into(),
+                    regex: "Out of memory".into(),
+                },
+            ],
+            lines: 10,
+            max_read_bytes: 1024,
+        }
+    }
+
+    /// Runs the config with the given backend state, as the scheduler would
+    fn run(config: &Config, state: &mut BackendStateStorage) -> Output {
+        let mut ctx = BackendContext::for_test("syslog", state);
+        config.run(&mut ctx).unwrap().unwrap()
+    }
+
+    fn texts(output: &Output) -> Vec<&str> {
+        output.lines.iter().map(|l| l.text.as_str()).collect()
+    }
+
+    #[test]
+    fn counts_new_complete_lines() {
+        let path = directory("new_lines").join("app.log");
+        append(&path, "error before the first run\n");
+        let (config, mut state) = (config(&path), BackendStateStorage::new());
+
+        let output = run(&config, &mut state);
+        assert_eq!(output.lines_read, 0);
+        assert_eq!(output.counts["errors"], 0);
+
+        append(
+            &path,
+            "an error\nall fine\nError: Out of memory\nunfinished error",
+        );
+        let output = run(&config, &mut state);
+        assert_eq!(output.lines_read, 3);
+        assert_eq!(output.counts["errors"], 2);
+        assert_eq!(output.counts["oom"], 1);
+        // a line is shown with the first pattern it matches
+        assert_eq!(output.lines[1].pattern, "errors");
+        assert_eq!(texts(&output), ["an error", "Error: Out of memory"]);
+        assert!(!output.rotated);
+
+        append(&path, " line\n");
+        let output = run(&config, &mut state);
+        assert_eq!(output.lines_read, 1);
+        assert_eq!(output.counts["errors"], 1);
+        assert_eq!(texts(&output)[2], "unfinished error line");
+        assert_eq!(output.cursor.offset, fs::metadata(&path).unwrap().len());
+    }
+
+    #[test]
+    fn follows_truncation() {
+        let path = directory("truncation").join("app.log");
+        append(&path, "");
+        let (config, mut state) = (config(&path), BackendStateStorage::new());
+        run(&config, &mut state);
+
+        append(&path, "a long line that is read before the truncation\n");
+        assert_eq!(run(&config, &mut state).lines_read, 1);
+
+        // as with `copytruncate`, the file keeps its inode
+        fs::write(&path, "error after\n").unwrap();
+        let output = run(&config, &mut state);
+        assert!(output.rotated);
+        assert_eq!(output.lines_read, 1);
+        assert_eq!(output.counts["errors"], 1);
+        assert_eq!(output.cursor.offset, 12);
+    }
+
+    #[test]
+    fn finishes_rotated_file_first() {
+        let dir = directory("rotation");
+        let path = dir.join("app.log");
+        append(&path, "");
+        let (config, mut state) = (config(&path), BackendStateStorage::new());
+        run(&config, &mut state);
+
+        append(&path, "error written before the rotation\n");
+        fs::rename(&path, dir.join("app.log.1")).unwrap();
+        append(&path, "error in the new file\n");
+
+        let output = run(&config, &mut state);
+        assert!(output.rotated);
+        assert_eq!(output.counts["errors"], 2);
+        assert_eq!(
+            texts(&output),
+            ["error written before the rotation", "error in the new file"]
+        );
+        assert_eq!(output.cursor.inode, fs::metadata(&path).unwrap().ino());
+
+        append(&path, "no match\n");
+        let output = run(&config, &mut state);
+        assert!(!output.rotated);
+        assert_eq!(output.lines_read, 1);
+    }
+
+    #[test]
+    fn reads_at_most_max_read_bytes() {
+        let path = directory("budget").join("app.log");
+        append(&path, "");
+        let config = Config {
+            max_read_bytes: 20,
+            lines: 1,
+            ..config(&path)
+        };
+        let mut state = BackendStateStorage::new();
+        run(&config, &mut state);
+
+        append(&path, "error number one\nerror number two\n");
+        let output = run(&config, &mut state);
+        assert_eq!(output.lines_read, 1);
+        assert_eq!(output.cursor.offset, 17);
+
+        let output = run(&config, &mut state);
+        assert_eq!(output.lines_read, 1);
+        // only the last `lines` matching lines are kept
+        assert_eq!(texts(&output), ["error number two"]);
+    }
+
+    #[test]
+    fn reads_large_rotated_file_over_several_runs() {
+        let dir = directory("large_rotation");
+        let path = dir.join("app.log");
+        append(&path, "");
+        let config = Config {
+            max_read_bytes: 20,
+            ..config(&path)
+        };
+        let mut state = BackendStateStorage::new();
+        run(&config, &mut state);
+
+        // the last line of the rotated file is never finished
+        append(
+            &path,
+            "error number one\nerror number two\nerror number three",
+        );
+        fs::rename(&path, dir.join("app.log.1")).unwrap();
+        append(&path, "new error\n");
+
+        let mut ctx = BackendContext::for_test("syslog", &mut state);
+        let output = config.run(&mut ctx).unwrap().unwrap();
+        assert!(output.rotated);
+        assert_eq!(output.lines_read, 1);
+        assert_eq!(output.cursor.inode, fs::metadata(&path).unwrap().ino());
+        assert_eq!(output.cursor.offset, 0);
+        assert!(ctx
+            .log
+            .contains("the rotated file is read on from offset 17 by the next run"));
+
+        let output = run(&config, &mut state);
+        assert!(!output.rotated);
+        assert_eq!(output.lines_read, 1);
+
+        // the rest of the rotated file fits, but not the line of the new file
+        let output = run(&config, &mut state);
+        assert_eq!(output.lines_read, 1);
+        assert_eq!(output.cursor.offset, 0);
+
+        let output = run(&config, &mut state);
+        assert_eq!(output.lines_read, 1);
+        assert_eq!(
+            texts(&output),
+            [
+                "error number one",
+                "error number two",
+                "error number three",
+                "new error"
+            ]
+        );
+    }
+}
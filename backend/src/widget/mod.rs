@@ -1,12 +1,26 @@
-use std::{any::Any, collections::HashMap};
+use std::{any::Any, collections::HashMap, fs, time::Duration};
 
 use chrono::prelude::*;
 use common::{
     backend::{BackendError, BackendRun, Initiator, RunId},
+    notification::Level,
     State, WidgetDefinition, WidgetId,
 };
-use serde::Serialize;
+use serde::{de::DeserializeOwned, Serialize};
 
+pub mod agenda;
+pub mod astronomy;
+pub mod certificate;
+pub mod clothing;
+pub mod command;
+pub mod feed;
+pub mod fetch;
+pub mod git;
+pub mod heartbeat;
+pub mod log_tail;
+pub mod push;
+pub mod system;
+pub mod uptime;
 pub mod weather;
 
 pub struct BackendStateStorage(HashMap<WidgetId, Box<dyn Any + Send + Sync>>);
@@ -20,10 +34,15 @@ impl BackendStateStorage {
 /// Backend that does all the computing etc
 pub trait WidgetBackend {
     type Output: State;
-    fn run<'a>(
-        &self,
-        ctx: &'a mut BackendContext<'a>,
-    ) -> Result<Option<Self::Output>, BackendError>;
+    fn run(&self, ctx: &mut BackendContext<'_>) -> Result<Option<Self::Output>, BackendError>;
+}
+
+/// A notification raised by a widget during a run, before it has been stored
+#[derive(Debug, Clone)]
+pub struct RaisedNotification {
+    pub level: Level,
+    pub title: String,
+    pub body: String,
 }
 
 /// BackendContext is provided by the backend itself and has methods to for example retrieve secrets and create notifications, read configuration, store KV-like state across reruns?
@@ -32,10 +51,30 @@ pub struct BackendContext<'a> {
     /// The Id is needed to uniquely identify the backend/widget that is requesting the thing
     id: WidgetId,
     state: &'a mut BackendStateStorage,
+    notifications: Vec<RaisedNotification>,
+    /// Stored as the log of the run
+    log: String,
+    /// Latest successful output of the widgets this widget depends on
+    upstream: HashMap<WidgetId, String>,
+    /// The secrets listed in the definition of the widget
+    secrets: HashMap<String, String>,
+    /// Output of the latest successful run of this widget
+    previous: Option<String>,
 }
 
-impl<'a> BackendContext<'a> {
-    pub fn get_state_or<S: Sized + Sync + Send + 'static>(&'a mut self, or: S) -> &'a mut S {
+/// What the backend hands to a run besides the state of the widget
+pub struct RunInput {
+    pub initiator: Initiator,
+    /// Latest successful output of the widgets this widget depends on
+    pub upstream: HashMap<WidgetId, String>,
+    /// The secrets listed in the definition of the widget
+    pub secrets: HashMap<String, String>,
+    /// Output of the latest successful run of this widget
+    pub previous: Option<String>,
+}
+
+impl BackendContext<'_> {
+    pub fn get_state_or<S: Sized + Sync + Send + 'static>(&mut self, or: S) -> &mut S {
         self.state
             .0
             .entry(self.id.clone())
@@ -43,17 +82,121 @@ impl<'a> BackendContext<'a> {
             .downcast_mut::<S>()
             .expect("Could not downcast backend state")
     }
+
+    /// Returns the output of the latest successful run of a widget listed in `depends_on`,
+    /// `None` if it has not produced any (parsable) output yet
+    pub fn upstream<T: DeserializeOwned>(&self, id: &WidgetId) -> Option<T> {
+        self.upstream
+            .get(id)
+            .and_then(|output| serde_json::from_str(output).ok())
+    }
+
+    /// Returns the output of the latest successful run of this widget, `None` if it has not
+    /// produced any (parsable) output yet
+    pub fn previous_output<T: DeserializeOwned>(&self) -> Option<T> {
+        self.previous
+            .as_ref()
+            .and_then(|output| serde_json::from_str(output).ok())
+    }
+
+    /// Returns a secret, which has to be listed in the `secrets` of the widget
+    pub fn secret(&self, name: &str) -> Result<&str, BackendError> {
+        self.secrets.get(name).map(String::as_str).ok_or_else(|| {
+            BackendError::Permanent(format!("secret {name} is not available to this widget"))
+        })
+    }
+
+    /// Append a line to the log of the run, which is shown in the run history
+    pub fn log(&mut self, line: impl AsRef<str>) {
+        self.log.push_str(line.as_ref());
+        if !self.log.ends_with('\n') {
+            self.log.push('\n');
+        }
+    }
+
+    /// Raise a notification. It is stored and delivered once the run has finished. Raising the same
+    /// notification again while it is not acknowledged only bumps its occurrence count.
+    pub fn notify(&mut self, level: Level, title: impl Into<String>, body: impl Into<String>) {
+        self.notifications.push(RaisedNotification {
+            level,
+            title: title.into(),
+            body: body.into(),
+        });
+    }
+}
+
+#[cfg(test)]
+impl<'a> BackendContext<'a> {
+    /// A context as for a first run, without upstream outputs, secrets or a previous output
+    pub fn for_test(id: &str, state: &'a mut BackendStateStorage) -> Self {
+        Self {
+            id: id.parse().unwrap(),
+            state,
+            notifications: Vec::new(),
+            log: String::new(),
+            upstream: HashMap::new(),
+            secrets: HashMap::new(),
+            previous: None,
+        }
+    }
+}
+
+/// A scheduled run of `widget` that ended at `ended`, stored with ID 0
+#[cfg(test)]
+pub fn test_run(
+    widget: &str,
+    ended: DateTime<Utc>,
+    result: Result<Option<String>, BackendError>,
+) -> BackendRun {
+    BackendRun {
+        id: RunId(0),
+        widget: widget.parse().unwrap(),
+        initiated: Initiator::Schedule,
+        started: ended,
+        ended,
+        log: String::new(),
+        result,
+        attempt: None,
+    }
+}
+
+/// How long fetching a URL in [`read_source`] may take
+const SOURCE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Reads a text file from a local path or an `http(s)://` URL. Failing to fetch a URL is
+/// transient, failing to read a file is not.
+fn read_source(source: &str) -> Result<String, BackendError> {
+    if source.starts_with("http://") || source.starts_with("https://") {
+        let agent: ureq::Agent = ureq::Agent::config_builder()
+            .timeout_global(Some(SOURCE_TIMEOUT))
+            .build()
+            .into();
+        agent
+            .get(source)
+            .call()
+            .and_then(|mut response| response.body_mut().read_to_string())
+            .map_err(|e| BackendError::Transient(format!("could not fetch {source}: {e}")))
+    } else {
+        fs::read_to_string(source)
+            .map_err(|e| BackendError::Permanent(format!("could not read {source}: {e}")))
+    }
 }
 
 pub fn run<C: WidgetBackend + Serialize + PartialEq, S: State>(
     definition: &WidgetDefinition<C, S>,
     state: &mut BackendStateStorage,
-) -> BackendRun {
+    input: RunInput,
+) -> (BackendRun, Vec<RaisedNotification>) {
     let id = definition.id.clone();
 
     let mut ctx = BackendContext {
         id: id.clone(),
         state,
+        notifications: Vec::new(),
+        log: String::new(),
+        upstream: input.upstream,
+        secrets: input.secrets,
+        previous: input.previous,
     };
 
     let start = Utc::now();
@@ -63,13 +206,16 @@ pub fn run<C: WidgetBackend + Serialize + PartialEq, S: State>(
     // serialize the returned state
     let result = result.map(|r| r.map(|v| serde_json::to_string(&v).unwrap()));
 
-    BackendRun {
+    let run = BackendRun {
         id: RunId(0),
         widget: id,
-        initiated: Initiator::Manual,
+        initiated: input.initiator,
         started: start,
         ended: end,
-        log: "".into(),
+        log: ctx.log,
         result,
-    }
+        attempt: None,
+    };
+
+    (run, ctx.notifications)
 }
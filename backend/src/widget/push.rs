@@ -0,0 +1,99 @@
+//! Widgets whose runs are created by external systems pushing data to the ingest endpoint
+use chrono::prelude::*;
+use common::{
+    backend::{BackendRun, Initiator, RunId},
+    push::{Config, Widget},
+};
+use serde_json::Value;
+
+/// Checks the bearer token sent by an external system against the configured one, in constant time
+pub fn authorize(expected: &str, token: &str) -> bool {
+    let (expected, actual) = (expected.as_bytes(), token.as_bytes());
+
+    !expected.is_empty()
+        && expected.len() == actual.len()
+        && expected
+            .iter()
+            .zip(actual)
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+/// Validates the pushed data against the schema of the widget, if it has one
+pub fn validate(config: &Config, data: &Value) -> Result<(), String> {
+    let Some(schema) = &config.schema else {
+        return Ok(());
+    };
+
+    let validator = jsonschema::validator_for(schema).map_err(|e| e.to_string())?;
+    let errors: Vec<String> = validator.iter_errors(data).map(|e| e.to_string()).collect();
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join(", "))
+    }
+}
+
+/// Creates the run that represents the pushed data
+pub fn run(definition: &Widget, data: Value) -> BackendRun {
+    let now = Utc::now();
+
+    BackendRun {
+        id: RunId(0),
+        widget: definition.id.clone(),
+        initiated: Initiator::Webhook,
+        started: now,
+        ended: now,
+        log: "".into(),
+        result: Ok(Some(data.to_string())),
+        attempt: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn authorizes_matching_token() {
+        assert!(authorize("secret", "secret"));
+        assert!(!authorize("secret", "wrong!"));
+        assert!(!authorize("secret", "secre"));
+        assert!(!authorize("secret", ""));
+        // an empty token would let anyone push
+        assert!(!authorize("", ""));
+    }
+
+    #[test]
+    fn rejects_empty_token_in_config() {
+        let config = "
+widgets:
+  - !Push
+    id: ci
+    config:
+      token: \"\"
+";
+        let error = crate::config::parse_config(config).unwrap_err();
+        assert_eq!(error.to_string(), "push widget ci needs a token");
+    }
+
+    #[test]
+    fn validates_against_schema() {
+        let mut config: Config = serde_yaml::from_str("token: secret").unwrap();
+        assert_eq!(validate(&config, &json!("anything")), Ok(()));
+
+        config.schema = Some(json!({
+            "type": "object",
+            "required": ["status"],
+            "properties": { "status": { "type": "string" } },
+        }));
+        assert_eq!(validate(&config, &json!({ "status": "passed" })), Ok(()));
+        assert!(validate(&config, &json!({ "state": "passed" }))
+            .unwrap_err()
+            .contains("\"status\" is a required property"));
+        assert!(validate(&config, &json!({ "status": 1 })).is_err());
+    }
+}
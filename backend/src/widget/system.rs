@@ -0,0 +1,241 @@
+use std::{fs, thread, time::Duration};
+
+use common::{
+    backend::BackendError,
+    system::{Config, Disk, Output, Usage},
+};
+use nix::sys::statvfs::statvfs;
+
+use super::{BackendContext, WidgetBackend};
+
+/// Time between the two CPU samples when there is no sample from a previous run
+const FIRST_SAMPLE_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Cumulative CPU time from `/proc/stat`, in clock ticks
+#[derive(Debug, Clone, Copy)]
+struct CpuTimes {
+    idle: u64,
+    total: u64,
+}
+
+#[derive(Debug)]
+struct BackendState {
+    previous: Option<CpuTimes>,
+}
+
+fn read_proc(path: &str) -> Result<String, BackendError> {
+    fs::read_to_string(path)
+        .map_err(|e| BackendError::Permanent(format!("could not read {path}: {e}")))
+}
+
+fn parse_error(path: &str) -> BackendError {
+    BackendError::Permanent(format!("unexpected contents of {path}"))
+}
+
+/// Parses the cumulative CPU times of all cores from the contents of `/proc/stat`
+fn parse_stat(stat: &str) -> Result<CpuTimes, BackendError> {
+    // cpu  user nice system idle iowait irq softirq steal guest guest_nice
+    let fields: Vec<u64> = stat
+        .lines()
+        .find(|l| l.starts_with("cpu "))
+        .ok_or_else(|| parse_error("/proc/stat"))?
+        .split_whitespace()
+        .skip(1)
+        .map(|v| v.parse().map_err(|_| parse_error("/proc/stat")))
+        .collect::<Result<_, _>>()?;
+
+    if fields.len() < 4 {
+        return Err(parse_error("/proc/stat"));
+    }
+
+    // guest time is already included in user time
+    Ok(CpuTimes {
+        idle: fields[3] + fields.get(4).copied().unwrap_or(0),
+        total: fields.iter().take(8).sum(),
+    })
+}
+
+fn cpu_percent(previous: CpuTimes, current: CpuTimes) -> f64 {
+    let total = current.total.saturating_sub(previous.total);
+    let idle = current.idle.saturating_sub(previous.idle);
+
+    if total == 0 {
+        0.0
+    } else {
+        (total - idle.min(total)) as f64 / total as f64 * 100.0
+    }
+}
+
+/// Parses the 1, 5 and 15 minute load averages from the contents of `/proc/loadavg`
+fn parse_loadavg(loadavg: &str) -> Result<[f64; 3], BackendError> {
+    let mut values = loadavg.split_whitespace().map(|v| v.parse::<f64>());
+
+    let mut load = [0.0; 3];
+    for l in &mut load {
+        *l = values
+            .next()
+            .and_then(|v| v.ok())
+            .ok_or_else(|| parse_error("/proc/loadavg"))?;
+    }
+    Ok(load)
+}
+
+/// Parses the memory and swap usage from the contents of `/proc/meminfo`
+fn parse_meminfo(meminfo: &str) -> Result<(Usage, Usage), BackendError> {
+    // values are in kB
+    let field = |name: &str| -> Option<u64> {
+        meminfo
+            .lines()
+            .find_map(|l| l.strip_prefix(name)?.strip_prefix(':'))
+            .and_then(|v| v.split_whitespace().next()?.parse::<u64>().ok())
+            .map(|kb| kb * 1024)
+    };
+    let required = |name: &str| field(name).ok_or_else(|| parse_error("/proc/meminfo"));
+
+    let memory_total = required("MemTotal")?;
+    let swap_total = required("SwapTotal")?;
+    // kernels before 3.14 do not estimate the available memory, approximate it like `free` did
+    let available = match field("MemAvailable") {
+        Some(available) => available,
+        None => required("MemFree")? + field("Buffers").unwrap_or(0) + field("Cached").unwrap_or(0),
+    };
+
+    Ok((
+        Usage {
+            total_bytes: memory_total,
+            used_bytes: memory_total.saturating_sub(available),
+        },
+        Usage {
+            total_bytes: swap_total,
+            used_bytes: swap_total.saturating_sub(required("SwapFree")?),
+        },
+    ))
+}
+
+fn disk(mountpoint: &str) -> Result<Disk, BackendError> {
+    let stat = statvfs(mountpoint)
+        .map_err(|e| BackendError::Permanent(format!("could not stat {mountpoint}: {e}")))?;
+
+    // the space reserved for root is counted as used, like `df` does
+    let fragment = stat.fragment_size() as u64;
+    let total = stat.blocks() as u64 * fragment;
+    let available = stat.blocks_available() as u64 * fragment;
+
+    Ok(Disk {
+        mountpoint: mountpoint.into(),
+        usage: Usage {
+            total_bytes: total,
+            used_bytes: total.saturating_sub(available),
+        },
+    })
+}
+
+impl WidgetBackend for Config {
+    type Output = Output;
+
+    fn run(&self, ctx: &mut BackendContext<'_>) -> Result<Option<Self::Output>, BackendError> {
+        let state = ctx.get_state_or(BackendState { previous: None });
+
+        // utilisation is computed between two samples, so sample twice the first time
+        let previous = match state.previous {
+            Some(previous) => previous,
+            None => {
+                let first = parse_stat(&read_proc("/proc/stat")?)?;
+                thread::sleep(FIRST_SAMPLE_INTERVAL);
+                first
+            }
+        };
+        let current = parse_stat(&read_proc("/proc/stat")?)?;
+        state.previous = Some(current);
+
+        let (memory, swap) = parse_meminfo(&read_proc("/proc/meminfo")?)?;
+
+        Ok(Some(Output {
+            cpu_percent: cpu_percent(previous, current),
+            load: parse_loadavg(&read_proc("/proc/loadavg")?)?,
+            memory,
+            swap,
+            disks: self
+                .mountpoints
+                .iter()
+                .map(|m| disk(m))
+                .collect::<Result<_, _>>()?,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STAT: &str = "\
+cpu  100 20 50 800 30 0 0 0 10 0
+cpu0 50 10 25 400 15 0 0 0 5 0
+intr 12345
+";
+
+    const MEMINFO: &str = "\
+MemTotal:        8000000 kB
+MemFree:         1000000 kB
+MemAvailable:    6000000 kB
+Buffers:          500000 kB
+Cached:          2000000 kB
+SwapTotal:       2000000 kB
+SwapFree:        1500000 kB
+";
+
+    /// A million kB in bytes
+    const MILLION_KB: u64 = 1_000_000 * 1024;
+
+    #[test]
+    fn parses_stat() {
+        let times = parse_stat(STAT).unwrap();
+        // idle and iowait, guest time is not counted twice
+        assert_eq!(times.idle, 830);
+        assert_eq!(times.total, 1000);
+
+        assert!(parse_stat("cpu0 1 2 3 4\n").is_err());
+        assert!(parse_stat("cpu  1 2 3\n").is_err());
+    }
+
+    #[test]
+    fn computes_cpu_percent_between_samples() {
+        let previous = parse_stat(STAT).unwrap();
+        let current = parse_stat("cpu  160 20 70 900 40 0 0 0 10 0\n").unwrap();
+        // 80 of the 190 ticks in between were busy
+        assert!((cpu_percent(previous, current) - 80.0 / 190.0 * 100.0).abs() < 1e-9);
+        assert_eq!(cpu_percent(current, current), 0.0);
+    }
+
+    #[test]
+    fn parses_meminfo() {
+        let (memory, swap) = parse_meminfo(MEMINFO).unwrap();
+        assert_eq!(memory.total_bytes, 8 * MILLION_KB);
+        assert_eq!(memory.used_bytes, 2 * MILLION_KB);
+        assert_eq!(swap.total_bytes, 2 * MILLION_KB);
+        assert_eq!(swap.used_bytes, MILLION_KB / 2);
+
+        // without MemAvailable, free, buffers and cache count as available
+        let old_kernel: String = MEMINFO
+            .lines()
+            .filter(|l| !l.starts_with("MemAvailable"))
+            .map(|l| format!("{l}\n"))
+            .collect();
+        let (memory, _) = parse_meminfo(&old_kernel).unwrap();
+        assert_eq!(
+            memory.used_bytes,
+            8 * MILLION_KB - 3 * MILLION_KB - MILLION_KB / 2
+        );
+
+        assert!(parse_meminfo("MemTotal: 8000000 kB\n").is_err());
+    }
+
+    #[test]
+    fn parses_loadavg() {
+        assert_eq!(
+            parse_loadavg("0.52 0.58 0.59 1/467 12345\n").unwrap(),
+            [0.52, 0.58, 0.59]
+        );
+        assert!(parse_loadavg("0.52 0.58\n").is_err());
+    }
+}
@@ -0,0 +1,282 @@
+use std::time::{Duration, Instant};
+
+use chrono::prelude::*;
+use common::{
+    backend::{BackendError, BackendRun},
+    uptime::{Config, Output, Target, TargetResult, TargetUptime},
+};
+
+use super::{BackendContext, WidgetBackend};
+
+/// Whether the request failed because of TLS, e.g. an invalid certificate
+fn is_tls_error(err: &ureq::Error) -> bool {
+    match err {
+        ureq::Error::Tls(_) | ureq::Error::Rustls(_) => true,
+        // the handshake happens when the connection is first used, so its errors arrive as IO errors
+        ureq::Error::Io(err) => err.get_ref().is_some_and(|inner| {
+            inner.is::<rustls::Error>() || inner.source().is_some_and(|s| s.is::<rustls::Error>())
+        }),
+        _ => false,
+    }
+}
+
+fn probe(target: &Target) -> TargetResult {
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(Duration::from_secs(target.timeout_seconds)))
+        .http_status_as_error(false)
+        .build()
+        .into();
+    let https = target.url.starts_with("https://");
+
+    let start = Instant::now();
+    let mut response = match agent.get(&target.url).call() {
+        Ok(response) => response,
+        Err(err) => {
+            return TargetResult {
+                url: target.url.clone(),
+                up: false,
+                status: None,
+                latency_ms: start.elapsed().as_millis() as u64,
+                tls_valid: (https && is_tls_error(&err)).then_some(false),
+                body_matched: None,
+                error: Some(err.to_string()),
+            }
+        }
+    };
+    let latency_ms = start.elapsed().as_millis() as u64;
+
+    let status = response.status().as_u16();
+    let status_ok = match target.status {
+        Some(expected) => status == expected,
+        None => (200..300).contains(&status),
+    };
+
+    let body_matched = target.contains.as_ref().and_then(|text| {
+        let body = response.body_mut().read_to_string().ok()?;
+        Some(body.contains(text.as_str()))
+    });
+
+    let error = if !status_ok {
+        Some(format!("unexpected status code {status}"))
+    } else if target.contains.is_some() && body_matched != Some(true) {
+        Some("body does not contain the expected text".into())
+    } else {
+        None
+    };
+
+    TargetResult {
+        url: target.url.clone(),
+        up: error.is_none(),
+        status: Some(status),
+        latency_ms,
+        tls_valid: https.then_some(true),
+        body_matched,
+        error,
+    }
+}
+
+/// Computes the uptime of the targets from the runs that ended after `since`. Failed runs are
+/// ignored since they say nothing about the targets.
+pub fn uptime(config: &Config, runs: &[BackendRun], since: DateTime<Utc>) -> Vec<TargetUptime> {
+    let outputs: Vec<Output> = runs
+        .iter()
+        .filter(|run| run.ended >= since)
+        .filter_map(|run| serde_json::from_str(run.result.as_ref().ok()?.as_ref()?).ok())
+        .collect();
+
+    config
+        .targets
+        .iter()
+        .map(|target| {
+            let results = outputs
+                .iter()
+                .flat_map(|o| &o.targets)
+                .filter(|r| r.url == target.url);
+            let (checks, up) =
+                results.fold((0, 0), |(checks, up), r| (checks + 1, up + r.up as usize));
+
+            TargetUptime {
+                url: target.url.clone(),
+                checks,
+                up,
+                percent: (checks > 0).then(|| up as f64 / checks as f64 * 100.0),
+            }
+        })
+        .collect()
+}
+
+impl WidgetBackend for Config {
+    type Output = Output;
+
+    fn run(&self, _ctx: &mut BackendContext<'_>) -> Result<Option<Self::Output>, BackendError> {
+        // targets being down is what this widget reports, so that is not an error
+        Ok(Some(Output {
+            targets: self.targets.iter().map(probe).collect(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use super::*;
+    use crate::widget::test_run;
+
+    /// Starts a local HTTP server that answers every request with `response` and returns its URL
+    fn serve(response: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else {
+                    continue;
+                };
+
+                // read the request headers
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while reader.read_line(&mut line).is_ok_and(|n| n > 2) {
+                    line.clear();
+                }
+
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+
+        format!("http://{address}/")
+    }
+
+    fn target(url: &str) -> Target {
+        Target {
+            url: url.into(),
+            status: None,
+            contains: None,
+            timeout_seconds: 5,
+        }
+    }
+
+    const OK: &str =
+        "HTTP/1.1 200 OK\r\nContent-Length: 11\r\nConnection: close\r\n\r\nhello world";
+    const ERROR: &str =
+        "HTTP/1.1 500 Internal Server Error\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+
+    #[test]
+    fn up_when_status_is_successful() {
+        let result = probe(&target(&serve(OK)));
+
+        assert!(result.up, "{result:?}");
+        assert_eq!(result.status, Some(200));
+        assert_eq!(result.tls_valid, None);
+        assert_eq!(result.body_matched, None);
+        assert_eq!(result.error, None);
+    }
+
+    #[test]
+    fn body_has_to_contain_text() {
+        let url = serve(OK);
+
+        let result = probe(&Target {
+            contains: Some("world".into()),
+            ..target(&url)
+        });
+        assert!(result.up, "{result:?}");
+        assert_eq!(result.body_matched, Some(true));
+
+        let result = probe(&Target {
+            contains: Some("goodbye".into()),
+            ..target(&url)
+        });
+        assert!(!result.up);
+        assert_eq!(result.body_matched, Some(false));
+    }
+
+    #[test]
+    fn down_on_unexpected_status() {
+        let url = serve(ERROR);
+
+        let result = probe(&target(&url));
+        assert!(!result.up);
+        assert_eq!(result.status, Some(500));
+        assert!(result.error.unwrap().contains("500"));
+
+        let result = probe(&Target {
+            status: Some(500),
+            ..target(&url)
+        });
+        assert!(result.up, "{result:?}");
+    }
+
+    #[test]
+    fn down_when_connection_fails() {
+        // bind to get a free port and close it again
+        let address = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let result = probe(&target(&format!("http://{address}/")));
+        assert!(!result.up);
+        assert_eq!(result.status, None);
+        assert_eq!(result.tls_valid, None);
+        assert!(result.error.is_some());
+    }
+
+    #[test]
+    fn tls_is_invalid_when_handshake_fails() {
+        // a plain HTTP server can not complete a TLS handshake
+        let url = serve(OK).replace("http://", "https://");
+
+        let result = probe(&target(&url));
+        assert!(!result.up);
+        assert_eq!(result.tls_valid, Some(false), "{result:?}");
+    }
+
+    #[test]
+    fn uptime_from_history() {
+        let config = Config {
+            targets: vec![target("http://a/"), target("http://b/")],
+        };
+        let now = Utc::now();
+
+        let run = |ended: DateTime<Utc>, a: bool| {
+            let output = Output {
+                targets: vec![TargetResult {
+                    url: "http://a/".into(),
+                    up: a,
+                    status: Some(200),
+                    latency_ms: 1,
+                    tls_valid: None,
+                    body_matched: None,
+                    error: None,
+                }],
+            };
+            test_run(
+                "uptime",
+                ended,
+                Ok(Some(serde_json::to_string(&output).unwrap())),
+            )
+        };
+
+        let runs = vec![
+            // too old to count
+            run(now - chrono::TimeDelta::days(2), false),
+            run(now, true),
+            run(now, true),
+            run(now, true),
+            run(now, false),
+        ];
+
+        let uptime = uptime(&config, &runs, now - chrono::TimeDelta::days(1));
+        assert_eq!(uptime[0].checks, 4);
+        assert_eq!(uptime[0].up, 3);
+        assert_eq!(uptime[0].percent, Some(75.0));
+        assert_eq!(uptime[1].checks, 0);
+        assert_eq!(uptime[1].percent, None);
+    }
+}
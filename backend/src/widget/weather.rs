@@ -1,28 +1,100 @@
-use common::weather::{Config, Output};
+use common::{
+    notification::Level,
+    weather::{Condition, Config, Output},
+};
 
 use super::{BackendContext, WidgetBackend};
 
+/// The made up temperature goes up and down between these, one degree per run
+const DEMO_RANGE: (f64, f64) = (-5.0, 15.0);
+
 #[derive(Debug)]
 struct BackendState {
-    value: f64,
+    step: u64,
+}
+
+/// The made up temperature of a run
+fn demo_temperature(step: u64) -> f64 {
+    let (min, max) = DEMO_RANGE;
+    let period = 2 * (max - min) as u64;
+    let phase = (step % period) as f64;
+    min + phase.min(period as f64 - phase)
 }
 
 impl WidgetBackend for Config {
     type Output = Output;
 
-    fn run<'a>(
+    fn run(
         &self,
-        ctx: &'a mut BackendContext<'a>,
+        ctx: &mut BackendContext<'_>,
     ) -> Result<Option<Self::Output>, super::BackendError> {
-        let state: &mut BackendState =
-            ctx.get_state_or::<BackendState>(BackendState { value: 0.0 });
+        let state: &mut BackendState = ctx.get_state_or::<BackendState>(BackendState { step: 0 });
+
+        // there is no real weather source yet, so make something up that looks plausible
+        let temperature = demo_temperature(state.step);
+        let condition = match temperature {
+            t if t < 0.0 => Condition::Snow,
+            t => [
+                Condition::Clear,
+                Condition::PartlyCloudy,
+                Condition::Cloudy,
+                Condition::Rain,
+            ][t as usize % 4],
+        };
 
         let new = Output {
-            temperature: state.value,
+            temperature,
+            condition,
         };
 
-        state.value += 1.0;
+        state.step += 1;
+
+        if new.temperature < 0.0 {
+            ctx.notify(
+                Level::Warning,
+                "Temperature below zero",
+                format!(
+                    "{:.1} °C at {}",
+                    new.temperature,
+                    self.name
+                        .clone()
+                        .unwrap_or_else(|| format!("{}, {}", self.location[0], self.location[1]))
+                ),
+            );
+        }
 
         Ok(Some(new))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::widget::BackendStateStorage;
+
+    #[test]
+    fn demo_temperature_goes_below_zero() {
+        assert_eq!(demo_temperature(0), -5.0);
+        assert_eq!(demo_temperature(20), 15.0);
+        assert_eq!(demo_temperature(21), 14.0);
+        assert_eq!(demo_temperature(40), -5.0);
+    }
+
+    #[test]
+    fn notifies_about_snow() {
+        let config: Config = serde_yaml::from_str("location: [59.3, 18.1]\nname: Home").unwrap();
+        let mut state = BackendStateStorage::new();
+        let mut ctx = BackendContext::for_test("weather", &mut state);
+
+        let output = config.run(&mut ctx).unwrap().unwrap();
+        assert_eq!(output.condition, Condition::Snow);
+        assert_eq!(ctx.notifications.len(), 1);
+        assert_eq!(ctx.notifications[0].body, "-5.0 °C at Home");
+
+        for _ in 0..5 {
+            config.run(&mut ctx).unwrap();
+        }
+        // above zero from the sixth run on
+        assert_eq!(ctx.notifications.len(), 5);
+    }
+}
@@ -0,0 +1,36 @@
+//! Threshold alert rules evaluated against the output of widget runs
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{notification::Level, WidgetId};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum AlertState {
+    /// The condition is not met (or not for enough consecutive runs yet)
+    Ok,
+    Firing,
+}
+
+/// The current state of an alert rule
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AlertStatus {
+    pub name: String,
+    /// The rule as written in the configuration
+    pub rule: String,
+    pub widget: WidgetId,
+    pub level: Level,
+    pub state: AlertState,
+    /// When the alert entered its current state, `None` if it has never been evaluated
+    pub since: Option<DateTime<Utc>>,
+    /// The value extracted from the most recent evaluated run
+    pub last_value: Option<serde_json::Value>,
+    /// Number of consecutive runs for which the condition was met
+    pub consecutive: usize,
+}
+
+/// Sent to the alert webhooks whenever an alert changes state
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AlertEvent {
+    pub state: AlertState,
+    pub alert: AlertStatus,
+}
@@ -1,3 +1,5 @@
+use std::fmt::Display;
+
 use chrono::prelude::*;
 use serde::{Deserialize, Serialize};
 
@@ -10,11 +12,51 @@ pub struct RunId(pub usize);
 pub enum Initiator {
     Schedule,
     Manual,
+    /// Data pushed to the ingest endpoint by an external system
+    Webhook,
+    /// A widget this widget depends on finished a successful run
+    Dependency,
+    /// A scheduled run failed with a transient error and is tried again
+    Retry,
+    /// An external job pinged the heartbeat endpoint
+    Heartbeat,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+/// Why a widget run failed
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub enum BackendError {
-    // TODO
+    /// Expected to go away by itself (e.g. a timeout or an unavailable service), scheduled runs
+    /// are retried according to their retry policy
+    Transient(String),
+    /// Trying again will not help (e.g. invalid configuration or unexpected data)
+    Permanent(String),
+}
+
+impl BackendError {
+    pub fn is_transient(&self) -> bool {
+        matches!(self, BackendError::Transient(_))
+    }
+}
+
+impl Display for BackendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BackendError::Transient(message) => write!(f, "{message} (transient)"),
+            BackendError::Permanent(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+/// Where a run is in the sequence of attempts of a scheduled run that has a retry policy
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct Attempt {
+    /// The first attempt, `None` for the first attempt itself
+    pub retry_of: Option<RunId>,
+    /// Starts at 1
+    pub number: u32,
+    pub max_attempts: u32,
+    /// When the next attempt is made, only set if this one failed transiently and attempts remain
+    pub next_retry: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -26,4 +68,18 @@ pub struct BackendRun {
     pub ended: DateTime<Utc>,
     pub log: String,
     pub result: Result<Option<String>, BackendError>,
+    /// Only set for scheduled runs (and their retries) of widgets with a retry policy
+    #[serde(default)]
+    pub attempt: Option<Attempt>,
+}
+
+/// One page of the run history of a widget, newest first
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RunPage {
+    pub runs: Vec<BackendRun>,
+    /// Zero based
+    pub page: usize,
+    pub per_page: usize,
+    /// Total number of runs of the widget
+    pub total: usize,
 }
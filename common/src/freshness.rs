@@ -0,0 +1,28 @@
+//! How up to date the data shown by a widget is
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::WidgetId;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum FreshnessState {
+    Fresh,
+    /// Getting close to being stale, e.g. a scheduled run is due but has not succeeded yet
+    Aging,
+    /// Older than it is allowed to be, or there is no data at all
+    Stale,
+    /// The most recent run failed, the data shown is from an earlier run
+    Failed,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Freshness {
+    pub widget: WidgetId,
+    pub state: FreshnessState,
+    /// When the latest successful run ended, i.e. when the shown data was produced
+    pub last_success: Option<DateTime<Utc>>,
+    /// Seconds since `last_success` when the freshness was computed by the backend
+    pub age_seconds: Option<i64>,
+    /// When the data becomes stale if it is not refreshed before, `None` if it never does
+    pub stale_at: Option<DateTime<Utc>>,
+}
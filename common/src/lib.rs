@@ -1,7 +1,10 @@
 //! Contains types that are shared between the backend and the frontend
 //! such as Widget state definitions and the enums of all widget states etc.
+pub mod alert;
 pub mod backend;
-use std::{fmt::Display, marker::PhantomData};
+pub mod freshness;
+pub mod notification;
+use std::{convert::Infallible, fmt::Display, marker::PhantomData, str::FromStr};
 
 use serde::{Deserialize, Serialize};
 
@@ -15,6 +18,14 @@ impl Display for WidgetId {
     }
 }
 
+impl FromStr for WidgetId {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(WidgetId(s.into()))
+    }
+}
+
 /// State Trait defines what is required for the widget state that will be shared with the frontend
 pub trait State: Serialize {}
 
@@ -23,11 +34,89 @@ impl<T: Serialize> State for T {}
 
 // TODO: move BackendRun here...
 
+/// A named selection of widgets, shown at `/d/<name>` in the frontend
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct Dashboard {
+    pub name: String,
+    /// Shown in the header instead of the name
+    #[serde(default)]
+    pub title: Option<String>,
+    /// The widgets in the order they are shown
+    pub widgets: Vec<WidgetId>,
+}
+
+/// Describes when a widget should be run automatically by the backend
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    /// Cron expression evaluated in the local time zone of the backend, e.g. `5 4 * * *`
+    pub cron: String,
+
+    /// Retry failed runs with exponential backoff, failures are not retried if not set
+    #[serde(default)]
+    pub retry: Option<RetryPolicy>,
+}
+
+/// How scheduled runs that fail with a transient error are retried
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first run
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+
+    /// Delay before the first retry, doubled for every following one
+    #[serde(default = "default_initial_delay_seconds")]
+    pub initial_delay_seconds: u64,
+
+    /// Upper limit of the delay between two attempts
+    #[serde(default = "default_max_delay_seconds")]
+    pub max_delay_seconds: u64,
+}
+
+fn default_max_attempts() -> u32 {
+    3
+}
+
+fn default_initial_delay_seconds() -> u64 {
+    30
+}
+
+fn default_max_delay_seconds() -> u64 {
+    3600
+}
+
+impl RetryPolicy {
+    /// The delay after the given (1 based) attempt failed
+    pub fn delay(&self, attempt: u32) -> chrono::TimeDelta {
+        let seconds = self
+            .initial_delay_seconds
+            .saturating_mul(2u64.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay_seconds);
+        chrono::TimeDelta::seconds(seconds as i64)
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
 pub struct WidgetDefinition<C: Serialize + PartialEq, S: State> {
     /// The unique ID of this widget
     pub id: WidgetId,
 
+    /// Automatic updates of this widget, if any
+    #[serde(default)]
+    pub schedule: Option<Schedule>,
+
+    /// Widgets whose output this widget uses. It is run automatically after any of them succeeds.
+    #[serde(default)]
+    pub depends_on: Vec<WidgetId>,
+
+    /// The output is considered stale when it is older than this. Scheduled widgets are otherwise
+    /// stale when a scheduled run has not succeeded in time, other widgets never are.
+    #[serde(default)]
+    pub max_age_minutes: Option<u64>,
+
+    /// Names of the secrets (defined at the top of the config) this widget may use
+    #[serde(default)]
+    pub secrets: Vec<String>,
+
     /// The configuration that belongs to this widget
     pub config: C,
 
@@ -38,6 +127,63 @@ pub struct WidgetDefinition<C: Serialize + PartialEq, S: State> {
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub enum WidgetEnum {
     Weather(weather::Widget),
+    Push(push::Widget),
+    Clothing(clothing::Widget),
+    System(system::Widget),
+    Uptime(uptime::Widget),
+    Certificate(certificate::Widget),
+    Agenda(agenda::Widget),
+    Feed(feed::Widget),
+    Command(command::Widget),
+    Fetch(fetch::Widget),
+    LogTail(log_tail::Widget),
+    Git(git::Widget),
+    Heartbeat(heartbeat::Widget),
+    Astronomy(astronomy::Widget),
+}
+
+/// Evaluates the expression with `$w` bound to the [`WidgetDefinition`] of whatever widget type it is
+macro_rules! with_definition {
+    ($widget:expr, $w:ident => $e:expr) => {
+        match $widget {
+            WidgetEnum::Weather($w) => $e,
+            WidgetEnum::Push($w) => $e,
+            WidgetEnum::Clothing($w) => $e,
+            WidgetEnum::System($w) => $e,
+            WidgetEnum::Uptime($w) => $e,
+            WidgetEnum::Certificate($w) => $e,
+            WidgetEnum::Agenda($w) => $e,
+            WidgetEnum::Feed($w) => $e,
+            WidgetEnum::Command($w) => $e,
+            WidgetEnum::Fetch($w) => $e,
+            WidgetEnum::LogTail($w) => $e,
+            WidgetEnum::Git($w) => $e,
+            WidgetEnum::Heartbeat($w) => $e,
+            WidgetEnum::Astronomy($w) => $e,
+        }
+    };
+}
+
+impl WidgetEnum {
+    pub fn id(&self) -> &WidgetId {
+        with_definition!(self, w => &w.id)
+    }
+
+    pub fn schedule(&self) -> Option<&Schedule> {
+        with_definition!(self, w => w.schedule.as_ref())
+    }
+
+    pub fn depends_on(&self) -> &[WidgetId] {
+        with_definition!(self, w => &w.depends_on)
+    }
+
+    pub fn max_age_minutes(&self) -> Option<u64> {
+        with_definition!(self, w => w.max_age_minutes)
+    }
+
+    pub fn secrets(&self) -> &[String] {
+        with_definition!(self, w => &w.secrets)
+    }
 }
 
 /// The definitions for the weather widget
@@ -50,15 +196,829 @@ pub mod weather {
     #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
     pub struct Config {
         pub location: [f64; 2],
+
+        /// Name of the location shown in the frontend, the coordinates are shown if not set
+        #[serde(default)]
+        pub name: Option<String>,
+
+        /// Unit the temperature is shown in by default, it can be toggled in the frontend
+        #[serde(default)]
+        pub unit: TemperatureUnit,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+    pub enum TemperatureUnit {
+        #[default]
+        Celsius,
+        Fahrenheit,
+    }
+
+    impl TemperatureUnit {
+        /// Converts a temperature in °C to this unit
+        pub fn convert(&self, celsius: f64) -> f64 {
+            match self {
+                TemperatureUnit::Celsius => celsius,
+                TemperatureUnit::Fahrenheit => celsius * 9.0 / 5.0 + 32.0,
+            }
+        }
+
+        pub fn symbol(&self) -> &'static str {
+            match self {
+                TemperatureUnit::Celsius => "°C",
+                TemperatureUnit::Fahrenheit => "°F",
+            }
+        }
+    }
+
+    #[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+    pub enum Condition {
+        #[default]
+        Unknown,
+        Clear,
+        PartlyCloudy,
+        Cloudy,
+        Rain,
+        Snow,
     }
 
     #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
     pub struct Output {
+        /// In °C
         pub temperature: f64,
+        #[serde(default)]
+        pub condition: Condition,
+    }
+}
+
+/// The definitions for the clothing advice widget
+pub mod clothing {
+    use super::*;
+
+    /// Suggests what to wear based on the output of a weather widget
+    pub type Widget = WidgetDefinition<Config, Output>;
+
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct Config {
+        /// The weather widget to base the advice on, has to be listed in `depends_on`
+        pub weather: WidgetId,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct Output {
+        pub temperature: f64,
+        pub advice: String,
+    }
+}
+
+/// The definitions for the system resource widget
+pub mod system {
+    use super::*;
+
+    /// Reports CPU, memory and disk usage of the host the backend runs on
+    pub type Widget = WidgetDefinition<Config, Output>;
+
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct Config {
+        /// Mountpoints to report the disk usage of
+        #[serde(default = "default_mountpoints")]
+        pub mountpoints: Vec<String>,
+    }
+
+    fn default_mountpoints() -> Vec<String> {
+        vec!["/".into()]
+    }
+
+    #[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+    pub struct Usage {
+        pub total_bytes: u64,
+        pub used_bytes: u64,
+    }
+
+    impl Usage {
+        pub fn percent(&self) -> f64 {
+            if self.total_bytes == 0 {
+                0.0
+            } else {
+                self.used_bytes as f64 / self.total_bytes as f64 * 100.0
+            }
+        }
+    }
+
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct Disk {
+        pub mountpoint: String,
+        pub usage: Usage,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct Output {
+        /// Utilisation of all cores since the previous run
+        pub cpu_percent: f64,
+        /// Load averages over 1, 5 and 15 minutes
+        pub load: [f64; 3],
+        pub memory: Usage,
+        pub swap: Usage,
+        pub disks: Vec<Disk>,
+    }
+}
+
+/// The definitions for the HTTP uptime widget
+pub mod uptime {
+    use super::*;
+
+    /// Checks that a list of URLs respond as expected
+    pub type Widget = WidgetDefinition<Config, Output>;
+
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct Config {
+        pub targets: Vec<Target>,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct Target {
+        pub url: String,
+
+        /// The expected status code, any 2xx status if not set
+        #[serde(default)]
+        pub status: Option<u16>,
+
+        /// Text the body has to contain
+        #[serde(default)]
+        pub contains: Option<String>,
+
+        #[serde(default = "default_timeout_seconds")]
+        pub timeout_seconds: u64,
+    }
+
+    fn default_timeout_seconds() -> u64 {
+        10
+    }
+
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct TargetResult {
+        pub url: String,
+        pub up: bool,
+        /// `None` if no response was received
+        pub status: Option<u16>,
+        /// Time until the response headers were received, or until the request failed
+        pub latency_ms: u64,
+        /// `None` for plain HTTP and if the connection failed before the TLS handshake
+        pub tls_valid: Option<bool>,
+        /// `None` if the target has no `contains` or no body was received
+        pub body_matched: Option<bool>,
+        /// Why the target is considered down
+        pub error: Option<String>,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct Output {
+        pub targets: Vec<TargetResult>,
+    }
+
+    /// Uptime of a target computed from the run history of the widget
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct TargetUptime {
+        pub url: String,
+        pub checks: usize,
+        pub up: usize,
+        /// `None` if the target has not been checked within the period
+        pub percent: Option<f64>,
+    }
+}
+
+/// The definitions for the TLS certificate expiry widget
+pub mod certificate {
+    use chrono::prelude::*;
+
+    use super::*;
+    use crate::notification::Level;
+
+    /// Checks when the TLS certificates of a list of servers expire
+    pub type Widget = WidgetDefinition<Config, Output>;
+
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct Config {
+        pub targets: Vec<Target>,
+
+        /// Certificates expiring within this many days are reported as warnings
+        #[serde(default = "default_warning_days")]
+        pub warning_days: i64,
+
+        /// Certificates expiring within this many days are reported as critical
+        #[serde(default = "default_critical_days")]
+        pub critical_days: i64,
+    }
+
+    fn default_warning_days() -> i64 {
+        21
+    }
+
+    fn default_critical_days() -> i64 {
+        7
+    }
+
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct Target {
+        pub host: String,
+
+        #[serde(default = "default_port")]
+        pub port: u16,
+
+        /// Name sent in the TLS handshake (SNI), `host` if not set
+        #[serde(default)]
+        pub server_name: Option<String>,
+    }
+
+    fn default_port() -> u16 {
+        443
+    }
+
+    /// The certificate presented by a server
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct Certificate {
+        pub subject: String,
+        pub issuer: String,
+        pub not_after: DateTime<Utc>,
+        /// Negative if the certificate has expired
+        pub days_remaining: i64,
+        /// Subject alternative names (DNS names and IP addresses)
+        pub sans: Vec<String>,
+        /// Number of certificates presented, including the leaf
+        pub chain_length: usize,
+        /// Whether the chain is trusted by the Mozilla root certificates for the server name
+        pub trusted: bool,
+        pub trust_error: Option<String>,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct CertificateStatus {
+        pub host: String,
+        pub port: u16,
+        /// `Info` if the certificate is fine, otherwise according to the thresholds
+        pub level: Level,
+        /// `None` if the certificate could not be retrieved
+        pub certificate: Option<Certificate>,
+        pub error: Option<String>,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct Output {
+        pub certificates: Vec<CertificateStatus>,
+    }
+}
+
+/// The definitions for the calendar agenda widget
+pub mod agenda {
+    use chrono::prelude::*;
+
+    use super::*;
+
+    /// Shows upcoming events from iCalendar (`.ics`) files
+    pub type Widget = WidgetDefinition<Config, Output>;
+
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct Config {
+        /// Paths or `http(s)://` URLs of `.ics` files
+        pub calendars: Vec<String>,
+
+        /// Number of upcoming events to show
+        #[serde(default = "default_count")]
+        pub count: usize,
+
+        /// How many days ahead to look for upcoming events
+        #[serde(default = "default_days")]
+        pub days: u32,
+
+        /// IANA time zone (e.g. `Europe/Stockholm`) used for all-day and floating events and to
+        /// decide what "today" is, the time zone of the server if not set
+        #[serde(default)]
+        pub timezone: Option<String>,
+    }
+
+    fn default_count() -> usize {
+        10
+    }
+
+    fn default_days() -> u32 {
+        30
+    }
+
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct Event {
+        pub summary: String,
+        pub location: Option<String>,
+        pub start: DateTime<Utc>,
+        pub end: DateTime<Utc>,
+        pub all_day: bool,
+        /// The calendar (path or URL) the event is from
+        pub calendar: String,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct Output {
+        /// The next `count` events that have not ended yet, ordered by start
+        pub upcoming: Vec<Event>,
+        /// All events that take place (partly) today, ordered by start
+        pub today: Vec<Event>,
+        /// Start of today in the configured time zone
+        pub day_start: DateTime<Utc>,
+    }
+}
+
+/// The definitions for the RSS/Atom feed widget
+pub mod feed {
+    use chrono::prelude::*;
+
+    use super::*;
+
+    /// Shows the latest headlines of RSS 2.0 and Atom feeds
+    pub type Widget = WidgetDefinition<Config, Output>;
+
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct Config {
+        /// URLs (or local paths) of the feeds
+        pub feeds: Vec<String>,
+
+        /// Number of headlines to show
+        #[serde(default = "default_count")]
+        pub count: usize,
+
+        /// How long an item is highlighted as new after it first showed up
+        #[serde(default = "default_new_hours")]
+        pub new_hours: i64,
+    }
+
+    fn default_count() -> usize {
+        20
+    }
+
+    fn default_new_hours() -> i64 {
+        24
+    }
+
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct Item {
+        pub title: String,
+        pub link: Option<String>,
+        /// When the item was published (or last updated) according to the feed
+        pub date: Option<DateTime<Utc>>,
+        /// Title of the feed the item is from
+        pub feed: String,
+        /// When a run first saw the item, `None` if it was there when the backend started
+        pub first_seen: Option<DateTime<Utc>>,
+        /// Whether the item showed up within the last `new_hours`
+        pub new: bool,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct Output {
+        /// Newest first
+        pub items: Vec<Item>,
+    }
+}
+
+/// The definitions for the shell command widget
+pub mod command {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    /// Runs a command and turns what it prints into the output
+    pub type Widget = WidgetDefinition<Config, Output>;
+
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct Config {
+        /// The program followed by its arguments, not passed through a shell
+        pub argv: Vec<String>,
+
+        #[serde(default)]
+        pub working_dir: Option<String>,
+
+        /// Added to the environment of the backend. Never sent to the frontend since it can
+        /// contain secrets.
+        #[serde(default, skip_serializing)]
+        pub env: BTreeMap<String, String>,
+
+        /// The command is killed and the run fails if it takes longer than this
+        #[serde(default = "default_timeout_seconds")]
+        pub timeout_seconds: u64,
+
+        /// How stdout is turned into the output
+        #[serde(default)]
+        pub parse: Parse,
+    }
+
+    fn default_timeout_seconds() -> u64 {
+        60
+    }
+
+    #[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+    #[serde(rename_all = "snake_case")]
+    pub enum Parse {
+        /// No output, stdout is only kept in the log
+        #[default]
+        None,
+        /// Stdout as a string, without surrounding whitespace
+        Text,
+        /// Stdout is a JSON document
+        Json,
+        /// Stdout is a single number
+        Number,
+        /// Stdout has one `key=value` pair per line, values that look like numbers or booleans
+        /// become numbers or booleans
+        KeyValue,
+    }
+
+    /// Depends on `Config::parse`
+    pub type Output = serde_json::Value;
+}
+
+/// The definitions for the JSON fetch widget
+pub mod fetch {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    /// Fetches JSON from a URL and picks values out of it with JSONPath
+    pub type Widget = WidgetDefinition<Config, Output>;
+
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct Config {
+        pub url: String,
+
+        #[serde(default)]
+        pub method: Method,
+
+        /// Header values can use secrets of the widget as `${name}`, e.g. `Bearer ${api_token}`.
+        /// Never sent to the frontend.
+        #[serde(default, skip_serializing)]
+        pub headers: BTreeMap<String, String>,
+
+        /// Sent as JSON, only used with `POST`
+        #[serde(default)]
+        pub body: Option<serde_json::Value>,
+
+        #[serde(default = "default_timeout_seconds")]
+        pub timeout_seconds: u64,
+
+        pub fields: Vec<Field>,
+    }
+
+    fn default_timeout_seconds() -> u64 {
+        10
     }
+
+    #[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+    pub enum Method {
+        #[default]
+        #[serde(alias = "get")]
+        Get,
+        #[serde(alias = "post")]
+        Post,
+    }
+
+    /// A value in the output, the unit and format are hints for the frontend
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct Field {
+        pub name: String,
+
+        /// JSONPath (RFC 9535) into the response, e.g. `$.database.latency_ms`
+        pub path: String,
+
+        #[serde(default)]
+        pub unit: Option<String>,
+
+        #[serde(default)]
+        pub format: Format,
+
+        /// Number of decimals to show numbers with
+        #[serde(default)]
+        pub decimals: Option<usize>,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+    #[serde(rename_all = "snake_case")]
+    pub enum Format {
+        /// Shown as it is
+        #[default]
+        Auto,
+        /// A fraction between 0 and 1 shown as a percentage
+        Ratio,
+        /// A number of bytes, shown with a binary prefix
+        Bytes,
+        /// A number of seconds, shown as e.g. `2h 5m`
+        Duration,
+        /// An RFC 3339 date or a Unix timestamp in seconds, shown in local time
+        Timestamp,
+        /// A boolean shown as a check mark or a cross
+        Status,
+    }
+
+    /// The value of each field by name, `null` if the path matched nothing and an array if it
+    /// matched several values
+    pub type Output = BTreeMap<String, serde_json::Value>;
 }
 
-// #[cfg(test)]
-// mod tests {
-//     use super::*;
-// }
+/// The definitions for the log file tail widget
+pub mod log_tail {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    /// Follows a log file and counts the lines that match patterns
+    pub type Widget = WidgetDefinition<Config, Output>;
+
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct Config {
+        pub path: String,
+
+        pub patterns: Vec<Pattern>,
+
+        /// Number of matching lines to show
+        #[serde(default = "default_lines")]
+        pub lines: usize,
+
+        /// At most this much is read per run, the rest is read by the next runs
+        #[serde(default = "default_max_read_bytes")]
+        pub max_read_bytes: u64,
+    }
+
+    fn default_lines() -> usize {
+        10
+    }
+
+    fn default_max_read_bytes() -> u64 {
+        16 * 1024 * 1024
+    }
+
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct Pattern {
+        pub name: String,
+        /// Regular expression a line has to match (anywhere in the line)
+        pub regex: String,
+    }
+
+    /// Where in the file the next run continues
+    #[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+    pub struct Cursor {
+        pub inode: u64,
+        pub offset: u64,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct Line {
+        /// Name of the first pattern the line matched
+        pub pattern: String,
+        pub text: String,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct Output {
+        /// Number of lines that matched each pattern since the previous run, by pattern name
+        pub counts: BTreeMap<String, u64>,
+        /// The last matching lines, oldest first
+        pub lines: Vec<Line>,
+        /// Number of lines read in this run
+        pub lines_read: u64,
+        /// Whether the file was rotated or truncated since the previous run
+        pub rotated: bool,
+        pub cursor: Cursor,
+    }
+}
+
+/// The definitions for the git repository status widget
+pub mod git {
+    use chrono::prelude::*;
+
+    use super::*;
+
+    /// Shows the branch, latest commit and recent activity of local git repositories
+    pub type Widget = WidgetDefinition<Config, Output>;
+
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct Config {
+        pub repositories: Vec<Repository>,
+
+        /// Commits made within this many hours count as recent
+        #[serde(default = "default_recent_hours")]
+        pub recent_hours: i64,
+    }
+
+    fn default_recent_hours() -> i64 {
+        24
+    }
+
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct Repository {
+        /// Path to the working tree (or a bare repository) on the machine running the backend
+        pub path: String,
+
+        /// Shown instead of the last part of the path
+        #[serde(default)]
+        pub name: Option<String>,
+
+        /// Remote-tracking branch to compare with, e.g. `origin/release`, the upstream of the
+        /// current branch if not set. Only as recent as the last `git fetch`.
+        #[serde(default)]
+        pub upstream: Option<String>,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct Commit {
+        /// Abbreviated hash
+        pub hash: String,
+        pub author: String,
+        /// The first line of the message
+        pub message: String,
+        pub date: DateTime<Utc>,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct RepositoryStatus {
+        pub name: String,
+        /// `None` if HEAD is detached
+        pub branch: Option<String>,
+        pub last_commit: Option<Commit>,
+        /// Number of commits on HEAD within `recent_hours`
+        pub recent_commits: u64,
+        /// The branch that was compared with, `None` if there is nothing to compare with
+        pub upstream: Option<String>,
+        /// Commits on HEAD that are not on the upstream
+        pub ahead: Option<u64>,
+        /// Commits on the upstream that are not on HEAD
+        pub behind: Option<u64>,
+        /// What could not be read, the fields it concerns keep their defaults
+        pub error: Option<String>,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct Output {
+        pub repositories: Vec<RepositoryStatus>,
+    }
+}
+
+/// The definitions for the sun and moon widget
+pub mod astronomy {
+    use chrono::prelude::*;
+
+    use super::*;
+
+    /// Computes sunrise, sunset, twilight and the phase of the moon, without any network access
+    pub type Widget = WidgetDefinition<Config, Output>;
+
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct Config {
+        /// Latitude and longitude in degrees, north and east are positive (like the weather widget)
+        pub location: [f64; 2],
+
+        /// Name of the location shown in the frontend, the coordinates are shown if not set
+        #[serde(default)]
+        pub name: Option<String>,
+
+        /// IANA time zone of the location (e.g. `Europe/Stockholm`), the time zone of the backend
+        /// if not set. It decides which day the times are for and their UTC offset.
+        #[serde(default)]
+        pub timezone: Option<String>,
+    }
+
+    /// Whether the sun stays on one side of the horizon for the whole day
+    #[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+    pub enum Polar {
+        /// The sun never sets (midnight sun)
+        Day,
+        /// The sun never rises
+        Night,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+    pub enum MoonPhase {
+        New,
+        WaxingCrescent,
+        FirstQuarter,
+        WaxingGibbous,
+        Full,
+        WaningGibbous,
+        LastQuarter,
+        WaningCrescent,
+    }
+
+    impl MoonPhase {
+        pub fn name(&self) -> &'static str {
+            match self {
+                MoonPhase::New => "New moon",
+                MoonPhase::WaxingCrescent => "Waxing crescent",
+                MoonPhase::FirstQuarter => "First quarter",
+                MoonPhase::WaxingGibbous => "Waxing gibbous",
+                MoonPhase::Full => "Full moon",
+                MoonPhase::WaningGibbous => "Waning gibbous",
+                MoonPhase::LastQuarter => "Last quarter",
+                MoonPhase::WaningCrescent => "Waning crescent",
+            }
+        }
+    }
+
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct Moon {
+        pub phase: MoonPhase,
+        /// Fraction of the disk that is lit, from 0 to 1
+        pub illumination: f64,
+        /// Days since the last new moon, approximately
+        pub age_days: f64,
+    }
+
+    /// The times are in the time zone of the location, `None` if the sun does not pass the
+    /// respective altitude on this day
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct Output {
+        /// The day the times are for
+        pub date: NaiveDate,
+        /// Start of civil twilight, when the sun is 6° below the horizon
+        pub dawn: Option<DateTime<FixedOffset>>,
+        pub sunrise: Option<DateTime<FixedOffset>>,
+        /// When the sun is highest
+        pub solar_noon: DateTime<FixedOffset>,
+        pub sunset: Option<DateTime<FixedOffset>>,
+        /// End of civil twilight
+        pub dusk: Option<DateTime<FixedOffset>>,
+        /// From sunrise to sunset, all day during the midnight sun and 0 during the polar night
+        pub day_length_seconds: i64,
+        pub polar: Option<Polar>,
+        /// At the time of the run
+        pub moon: Moon,
+    }
+}
+
+/// The definitions for the heartbeat (dead man's switch) widget
+pub mod heartbeat {
+    use super::*;
+
+    /// A widget that expects external jobs to ping `/api/widget/:id/heartbeat` regularly
+    pub type Widget = WidgetDefinition<Config, Output>;
+
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct Config {
+        /// Token that has to be sent as `Authorization: Bearer <token>` with a ping.
+        /// Never sent to the frontend.
+        #[serde(default, skip_serializing)]
+        pub token: String,
+
+        /// How often the job is expected to ping
+        pub period_minutes: u64,
+
+        /// How late a ping may be before the job is considered down
+        #[serde(default = "default_grace_minutes")]
+        pub grace_minutes: u64,
+    }
+
+    fn default_grace_minutes() -> u64 {
+        5
+    }
+
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct Output {
+        /// Text sent as the body of the ping, e.g. a summary of what the job did
+        pub message: Option<String>,
+    }
+}
+
+/// The definitions for widgets whose data is pushed to the backend by external systems
+pub mod push {
+    use super::*;
+
+    /// A widget that is updated by POSTing JSON to `/api/widget/:id/ingest`
+    pub type Widget = WidgetDefinition<Config, Output>;
+
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    pub struct Config {
+        /// Token that has to be sent as `Authorization: Bearer <token>` when pushing data.
+        /// Never sent to the frontend.
+        #[serde(default, skip_serializing)]
+        pub token: String,
+
+        /// Optional JSON schema that pushed data has to conform to
+        #[serde(default)]
+        pub schema: Option<serde_json::Value>,
+    }
+
+    /// Whatever JSON was pushed
+    pub type Output = serde_json::Value;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_delay_doubles_up_to_max() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            initial_delay_seconds: 30,
+            max_delay_seconds: 100,
+        };
+        let delays: Vec<i64> = (1..=5).map(|a| policy.delay(a).num_seconds()).collect();
+        assert_eq!(delays, [30, 60, 100, 100, 100]);
+
+        // does not overflow for absurd attempts
+        assert_eq!(policy.delay(u32::MAX).num_seconds(), 100);
+    }
+}
@@ -0,0 +1,32 @@
+//! Notifications raised by widget backends
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::WidgetId;
+
+/// How important a notification is, ordered from least to most severe
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    Info,
+    Warning,
+    Critical,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct NotificationId(pub usize);
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Notification {
+    pub id: NotificationId,
+    pub widget: WidgetId,
+    pub level: Level,
+    pub title: String,
+    pub body: String,
+    /// When the notification was first raised
+    pub created: DateTime<Utc>,
+    /// When the notification was most recently raised again (see `occurrences`)
+    pub last_seen: DateTime<Utc>,
+    /// How many times the same notification was raised while it was not acknowledged
+    pub occurrences: usize,
+    pub acknowledged: bool,
+}
@@ -0,0 +1,184 @@
+//! Today's agenda as a timeline followed by the upcoming events
+use chrono::{prelude::*, TimeDelta};
+use common::agenda::{self, Event};
+use wasm_bindgen_futures::spawn_local;
+use yew::prelude::*;
+
+use crate::app::fetch_latest_output;
+
+/// Hours of the day that are always shown on the timeline
+const WORKDAY: (f64, f64) = (8.0, 18.0);
+
+/// Hours since the start of the day, clamped to the day
+fn hours(day_start: DateTime<Utc>, time: DateTime<Utc>) -> f64 {
+    ((time - day_start).num_minutes() as f64 / 60.0).clamp(0.0, 24.0)
+}
+
+/// Assigns each event a lane so that overlapping events are shown next to each other, returns
+/// the lane of each event and the number of lanes
+fn lanes(events: &[&Event]) -> (Vec<usize>, usize) {
+    let mut ends: Vec<DateTime<Utc>> = Vec::new();
+    let lanes = events
+        .iter()
+        .map(
+            |event| match ends.iter().position(|end| *end <= event.start) {
+                Some(lane) => {
+                    ends[lane] = event.end;
+                    lane
+                }
+                None => {
+                    ends.push(event.end);
+                    ends.len() - 1
+                }
+            },
+        )
+        .collect();
+    (lanes, ends.len().max(1))
+}
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.with_timezone(&Local).format("%H:%M").to_string()
+}
+
+fn timeline(output: &agenda::Output) -> Html {
+    let day_start = output.day_start;
+    let (all_day, timed): (Vec<&Event>, Vec<&Event>) = output.today.iter().partition(|e| e.all_day);
+
+    // show the workday, extended to whole hours to fit all events
+    let first = timed
+        .iter()
+        .map(|e| hours(day_start, e.start).floor())
+        .fold(WORKDAY.0, f64::min);
+    let last = timed
+        .iter()
+        .map(|e| hours(day_start, e.end).ceil())
+        .fold(WORKDAY.1, f64::max);
+    let span = (last - first).max(1.0);
+    let percent = |h: f64| (h - first) / span * 100.0;
+
+    let (lanes, lane_count) = lanes(&timed);
+    let now = hours(day_start, Utc::now());
+
+    html! {
+        <div class="today">
+            <div class="all-day">
+                { all_day.iter().map(|e| html! { <span class="event">{ &e.summary }</span> }).collect::<Html>() }
+            </div>
+            <div class="timeline" style={format!("height: {}rem", span * 2.0)}>
+            {
+                (first as i64..=last as i64).map(|h| html! {
+                    <div class="hour" style={format!("top: {}%", percent(h as f64))}>
+                        { format_time(day_start + TimeDelta::hours(h)) }
+                    </div>
+                }).collect::<Html>()
+            }
+            {
+                timed.iter().zip(lanes).map(|(event, lane)| {
+                    let top = percent(hours(day_start, event.start));
+                    let height = (percent(hours(day_start, event.end)) - top).max(3.0);
+                    let style = format!(
+                        "top: {top}%; height: {height}%; left: calc(3rem + (100% - 3rem) * {lane} / {lane_count}); width: calc((100% - 3rem) / {lane_count})"
+                    );
+                    let past = event.end <= Utc::now();
+                    html! {
+                        <div class={classes!("event", past.then_some("past"))} {style}
+                            title={event.location.clone().unwrap_or_default()}>
+                            <span class="time">{ format_time(event.start) }</span>{ " " }{ &event.summary }
+                        </div>
+                    }
+                }).collect::<Html>()
+            }
+            {
+                if (first..=last).contains(&now) {
+                    html! { <div class="now" style={format!("top: {}%", percent(now))}></div> }
+                } else {
+                    html! {}
+                }
+            }
+            </div>
+        </div>
+    }
+}
+
+fn upcoming(events: &[Event]) -> Html {
+    let mut day = None;
+    events
+        .iter()
+        .map(|event| {
+            let local = event.start.with_timezone(&Local);
+            let heading = (day != Some(local.date_naive())).then(|| {
+                day = Some(local.date_naive());
+                html! { <div class="day">{ local.format("%a %e %b").to_string() }</div> }
+            });
+            let time = if event.all_day {
+                "all day".to_string()
+            } else {
+                format_time(event.start)
+            };
+
+            html! {
+                <>
+                    { heading }
+                    <div class="event">
+                        <span class="time">{ time }</span>
+                        <span class="summary">{ &event.summary }</span>
+                        {
+                            event.location.as_ref().map(|l| html! {
+                                <span class="location">{ l }</span>
+                            })
+                        }
+                    </div>
+                </>
+            }
+        })
+        .collect()
+}
+
+#[derive(Clone, PartialEq, Properties)]
+pub struct AgendaWidgetProps {
+    pub definition: agenda::Widget,
+}
+
+/// Today's events on a timeline and a list of the next events
+#[function_component(AgendaWidget)]
+pub fn agenda_widget(props: &AgendaWidgetProps) -> Html {
+    let AgendaWidgetProps { definition } = props;
+    let state = use_state(|| None);
+
+    {
+        let state = state.clone();
+        let id = definition.id.clone();
+        use_effect_with((), move |_| {
+            spawn_local(async move {
+                let result = fetch_latest_output::<agenda::Output>(&id).await;
+                state.set(Some(result));
+            });
+        });
+    }
+
+    match state.as_ref() {
+        None => html! {
+            <div>{"No server response"}</div>
+        },
+        Some(Ok(None)) => html! {
+            <div class="widget">{"No events loaded yet"}</div>
+        },
+        Some(Ok(Some(data))) => html! {
+            <div class="widget agenda">
+                { timeline(data) }
+                <div class="upcoming">
+                {
+                    if data.upcoming.is_empty() {
+                        html! { <div class="empty">{ "No upcoming events" }</div> }
+                    } else {
+                        upcoming(&data.upcoming)
+                    }
+                }
+                </div>
+            </div>
+        },
+        Some(Err(err)) => html! {
+            <div>{"Error requesting data from server: "}{err}</div>
+        },
+    }
+}
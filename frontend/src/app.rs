@@ -1,23 +1,73 @@
-use common::{backend::BackendRun, weather, WidgetEnum};
+use chrono::Local;
+use common::{
+    alert::{AlertState, AlertStatus},
+    backend::BackendRun,
+    certificate, clothing, feed,
+    fetch::{self, Format},
+    freshness::{Freshness, FreshnessState},
+    git, heartbeat, log_tail,
+    notification::{Level, Notification, NotificationId},
+    system, uptime,
+    weather::{self, Condition, TemperatureUnit},
+    Dashboard, WidgetEnum, WidgetId,
+};
+use serde::{de::DeserializeOwned, Deserialize};
 use yew::prelude::*;
 use yew_router::prelude::*;
 
 use gloo_net::http::Request;
+use gloo_timers::callback::Interval;
 use wasm_bindgen_futures::spawn_local;
 
+use crate::{
+    agenda::AgendaWidget,
+    astronomy::AstronomyWidget,
+    generic::{self, format_key, render_value, GenericWidget},
+    history::WidgetHistory,
+};
+
+/// How often to check for new notifications
+const NOTIFICATION_POLL_MS: u32 = 30_000;
+
+/// How often to refresh the state of the alerts
+const ALERT_POLL_MS: u32 = 30_000;
+
+/// How often to refresh the freshness of the widgets, this also updates the relative timestamps
+const FRESHNESS_POLL_MS: u32 = 30_000;
+
+/// How long each headline of a feed widget is shown
+const TICKER_ROTATE_MS: u32 = 8_000;
+
 #[derive(Clone, Routable, PartialEq)]
-enum Route {
+pub enum Route {
+    /// Dashboard with all widgets
     #[at("/")]
     Home,
 
+    /// A dashboard defined in the configuration
+    #[at("/d/:name")]
+    Dashboard { name: String },
+
+    /// Where the dashboard used to be, kept so that old bookmarks still work
     #[at("/hello-server")]
     HelloServer,
+
+    /// Run history of a single widget
+    #[at("/widget/:id")]
+    Widget { id: WidgetId },
+
+    #[not_found]
+    #[at("/404")]
+    NotFound,
 }
 
 fn switch(routes: Route) -> Html {
     match routes {
-        Route::Home => html! { <h1>{ "Hello Frontend" }</h1> },
-        Route::HelloServer => html! { <HelloServer /> },
+        Route::Home => html! { <DashboardPage /> },
+        Route::Dashboard { name } => html! { <DashboardPage name={Some(name)} /> },
+        Route::HelloServer => html! { <Redirect<Route> to={Route::Home} /> },
+        Route::Widget { id } => html! { <WidgetHistory {id} /> },
+        Route::NotFound => html! { <NotFound /> },
     }
 }
 
@@ -26,48 +76,82 @@ pub fn app() -> Html {
     html! {
         <BrowserRouter>
             <Switch<Route> render={switch} />
+            <Toasts />
         </BrowserRouter>
     }
 }
 
-#[function_component(HelloServer)]
-fn hello_server() -> Html {
+#[function_component(NotFound)]
+fn not_found() -> Html {
+    html! {
+        <div class="not-found">
+            <h1>{ "404" }</h1>
+            <p>{ "There is nothing here." }</p>
+            <Link<Route> to={Route::Home}>{ "Go to the dashboard" }</Link<Route>>
+        </div>
+    }
+}
+
+#[derive(Deserialize)]
+struct ViewQuery {
+    #[serde(default)]
+    kiosk: Option<String>,
+}
+
+/// Kiosk mode (`?kiosk=1`) hides everything but the widgets, for wall mounted screens
+#[hook]
+fn use_kiosk() -> bool {
+    use_location()
+        .and_then(|location| location.query::<ViewQuery>().ok())
+        .is_some_and(|query| matches!(query.kiosk.as_deref(), Some("1" | "true")))
+}
+
+async fn fetch_json<T: DeserializeOwned>(url: &str) -> Result<T, String> {
+    let resp = Request::get(url)
+        .send()
+        .await
+        .map_err(|err| err.to_string())?;
+
+    if !resp.ok() {
+        return Err(format!(
+            "Error fetching data {} ({})",
+            resp.status(),
+            resp.status_text()
+        ));
+    }
+
+    // successful, get the text and try to parse it
+    let text = resp.text().await.map_err(|err| err.to_string())?;
+    serde_json::from_str::<T>(&text).map_err(|err| err.to_string())
+}
+
+#[derive(Clone, PartialEq, Properties)]
+struct DashboardPageProps {
+    /// The dashboard from the configuration to show, all widgets if not set
+    #[prop_or_default]
+    name: Option<String>,
+}
+
+#[function_component(DashboardPage)]
+fn dashboard_page(props: &DashboardPageProps) -> Html {
     let data = use_state(|| None);
+    let kiosk = use_kiosk();
 
-    // Request `/api/widgets` once
     {
         let data = data.clone();
-        use_effect(move || {
-            if data.is_none() {
-                spawn_local(async move {
-                    let resp = Request::get("/api/widgets").send().await.unwrap();
-                    let result = {
-                        if !resp.ok() {
-                            Err(format!(
-                                "Error fetching data {} ({})",
-                                resp.status(),
-                                resp.status_text()
-                            ))
-                        } else {
-                            // successful, get the text and try to parse it into the list of widgets
-                            resp.text()
-                                .await
-                                .map_err(|err| err.to_string())
-                                .and_then(|text| {
-                                    serde_json::from_str::<Vec<WidgetEnum>>(&text)
-                                        .map_err(|err| err.to_string())
-                                })
-                        }
-                    };
-                    data.set(Some(result));
-                });
-            }
-
-            || {}
+        use_effect_with(props.name.clone(), move |name| {
+            let url = match name {
+                Some(name) => format!("/api/dashboard/{name}"),
+                None => "/api/widgets".into(),
+            };
+            data.set(None);
+            spawn_local(async move {
+                data.set(Some(fetch_json::<Vec<WidgetEnum>>(&url).await));
+            });
         });
     }
 
-    match data.as_ref() {
+    let content = match data.as_ref() {
         None => {
             html! {
                 <div>{"No server response"}</div>
@@ -79,9 +163,23 @@ fn hello_server() -> Html {
                 {
                     // construct the right component for each widget
                     data.iter().map(|widget| {
-                        match widget {
+                        // widgets without a component of their own show their output as is
+                        let content = match widget {
                             WidgetEnum::Weather(w) => html!{<WeatherWidget definition={w.clone()} />},
-                        }
+                            WidgetEnum::Clothing(w) => html!{<ClothingWidget definition={w.clone()} />},
+                            WidgetEnum::System(w) => html!{<SystemWidget definition={w.clone()} />},
+                            WidgetEnum::Uptime(w) => html!{<UptimeWidget definition={w.clone()} />},
+                            WidgetEnum::Certificate(w) => html!{<CertificateWidget definition={w.clone()} />},
+                            WidgetEnum::Agenda(w) => html!{<AgendaWidget definition={w.clone()} />},
+                            WidgetEnum::Feed(w) => html!{<FeedWidget definition={w.clone()} />},
+                            WidgetEnum::Fetch(w) => html!{<FetchWidget definition={w.clone()} />},
+                            WidgetEnum::LogTail(w) => html!{<LogTailWidget definition={w.clone()} />},
+                            WidgetEnum::Git(w) => html!{<GitWidget definition={w.clone()} />},
+                            WidgetEnum::Heartbeat(w) => html!{<HeartbeatWidget definition={w.clone()} />},
+                            WidgetEnum::Astronomy(w) => html!{<AstronomyWidget definition={w.clone()} />},
+                            _ => html!{<GenericWidget id={widget.id().clone()} />},
+                        };
+                        html!{<WidgetFrame id={widget.id().clone()} {kiosk}>{content}</WidgetFrame>}
                     }).collect::<Html>()
                 }
                 </div>
@@ -92,6 +190,235 @@ fn hello_server() -> Html {
                 <div>{"Error requesting data from server: "}{err}</div>
             }
         }
+    };
+
+    html! {
+        <div class={classes!("dashboard", kiosk.then_some("kiosk"))}>
+            if !kiosk {
+                <DashboardNav current={props.name.clone()} />
+            }
+            <AlertBanner />
+            { content }
+        </div>
+    }
+}
+
+#[derive(Clone, PartialEq, Properties)]
+struct DashboardNavProps {
+    current: Option<String>,
+}
+
+/// Links to all dashboards
+#[function_component(DashboardNav)]
+fn dashboard_nav(props: &DashboardNavProps) -> Html {
+    let dashboards = use_state(Vec::<Dashboard>::new);
+
+    {
+        let dashboards = dashboards.clone();
+        use_effect_with((), move |_| {
+            spawn_local(async move {
+                match fetch_json::<Vec<Dashboard>>("/api/dashboards").await {
+                    Ok(list) => dashboards.set(list),
+                    Err(err) => log::warn!("{err}"),
+                }
+            });
+        });
+    }
+
+    let title = props
+        .current
+        .as_ref()
+        .map(|name| {
+            dashboards
+                .iter()
+                .find(|d| d.name == *name)
+                .and_then(|d| d.title.clone())
+                .unwrap_or_else(|| name.clone())
+        })
+        .unwrap_or_else(|| "Dashboard".into());
+
+    html! {
+        <nav class="dashboard-nav">
+            <h1>{ title }</h1>
+            <Link<Route> to={Route::Home} classes={classes!(props.current.is_none().then_some("active"))}>
+                { "All widgets" }
+            </Link<Route>>
+            {
+                dashboards.iter().map(|d| {
+                    let active = props.current.as_ref() == Some(&d.name);
+                    html! {
+                        <Link<Route> to={Route::Dashboard { name: d.name.clone() }} classes={classes!(active.then_some("active"))}>
+                            { d.title.clone().unwrap_or_else(|| d.name.clone()) }
+                        </Link<Route>>
+                    }
+                }).collect::<Html>()
+            }
+        </nav>
+    }
+}
+
+#[derive(Clone, PartialEq, Properties)]
+struct WidgetFrameProps {
+    id: WidgetId,
+    children: Html,
+    /// Hides the link to the run history
+    #[prop_or_default]
+    kiosk: bool,
+}
+
+/// Wraps a widget and shows how up to date its data is
+#[function_component(WidgetFrame)]
+fn widget_frame(props: &WidgetFrameProps) -> Html {
+    let freshness = use_state(|| None::<Freshness>);
+
+    {
+        let freshness = freshness.clone();
+        use_effect_with(props.id.clone(), move |id| {
+            let url = format!("/api/widget/{id}/freshness");
+            let refresh = move || {
+                let freshness = freshness.clone();
+                let url = url.clone();
+                spawn_local(async move {
+                    let result = match Request::get(&url).send().await {
+                        Ok(resp) if resp.ok() => resp
+                            .json::<Freshness>()
+                            .await
+                            .map_err(|err| err.to_string()),
+                        Ok(resp) => Err(format!(
+                            "Error fetching freshness {} ({})",
+                            resp.status(),
+                            resp.status_text()
+                        )),
+                        Err(err) => Err(err.to_string()),
+                    };
+
+                    match result {
+                        Ok(f) => freshness.set(Some(f)),
+                        Err(err) => log::warn!("{err}"),
+                    }
+                });
+            };
+
+            refresh();
+            let interval = Interval::new(FRESHNESS_POLL_MS, refresh);
+            move || drop(interval)
+        });
+    }
+
+    let (class, label) = match freshness.as_ref() {
+        Some(f) => (freshness_class(f.state), freshness_label(f)),
+        None => ("", String::new()),
+    };
+
+    html! {
+        <div class={classes!("widget-frame", class)}>
+            { props.children.clone() }
+            <div class="freshness">
+                { label }
+                if !props.kiosk {
+                    <Link<Route> to={Route::Widget { id: props.id.clone() }}>{ "history" }</Link<Route>>
+                }
+            </div>
+        </div>
+    }
+}
+
+fn freshness_class(state: FreshnessState) -> &'static str {
+    match state {
+        FreshnessState::Fresh => "fresh",
+        FreshnessState::Aging => "aging",
+        FreshnessState::Stale => "stale",
+        FreshnessState::Failed => "failed",
+    }
+}
+
+fn freshness_label(freshness: &Freshness) -> String {
+    let Some(age) = freshness.age_seconds else {
+        return match freshness.state {
+            FreshnessState::Failed => "failed, no data yet".into(),
+            _ => "no data yet".into(),
+        };
+    };
+
+    let age = format_age(age);
+    match freshness.state {
+        FreshnessState::Fresh | FreshnessState::Aging => format!("updated {age}"),
+        FreshnessState::Stale => format!("stale, updated {age}"),
+        FreshnessState::Failed => format!("last run failed, updated {age}"),
+    }
+}
+
+/// Formats a number of seconds as a relative timestamp, e.g. `5 min ago`
+fn format_age(seconds: i64) -> String {
+    match seconds {
+        s if s < 60 => "just now".into(),
+        s if s < 60 * 60 => format!("{} min ago", s / 60),
+        s if s < 24 * 60 * 60 => format!("{} h ago", s / (60 * 60)),
+        s => format!("{} days ago", s / (24 * 60 * 60)),
+    }
+}
+
+/// Lists the alerts that are currently firing
+#[function_component(AlertBanner)]
+fn alert_banner() -> Html {
+    let alerts = use_state(Vec::<AlertStatus>::new);
+
+    {
+        let alerts = alerts.clone();
+        use_effect_with((), move |_| {
+            let refresh = move || {
+                let alerts = alerts.clone();
+                spawn_local(async move {
+                    let result = match Request::get("/api/alerts").send().await {
+                        Ok(resp) if resp.ok() => resp
+                            .json::<Vec<AlertStatus>>()
+                            .await
+                            .map_err(|err| err.to_string()),
+                        Ok(resp) => Err(format!(
+                            "Error fetching alerts {} ({})",
+                            resp.status(),
+                            resp.status_text()
+                        )),
+                        Err(err) => Err(err.to_string()),
+                    };
+
+                    match result {
+                        Ok(list) => alerts.set(list),
+                        Err(err) => log::warn!("{err}"),
+                    }
+                });
+            };
+
+            refresh();
+            let interval = Interval::new(ALERT_POLL_MS, refresh);
+            move || drop(interval)
+        });
+    }
+
+    let firing: Vec<&AlertStatus> = alerts
+        .iter()
+        .filter(|a| a.state == AlertState::Firing)
+        .collect();
+
+    if firing.is_empty() {
+        return html! {};
+    }
+
+    html! {
+        <div class="alerts">
+        {
+            firing.into_iter().map(|alert| {
+                let value = alert.last_value.as_ref().map(|v| v.to_string()).unwrap_or_default();
+
+                html! {
+                    <div class={classes!("alert", level_class(alert.level))}>
+                        <strong>{ &alert.name }</strong>
+                        { format!(" {} (value {})", alert.rule, value) }
+                    </div>
+                }
+            }).collect::<Html>()
+        }
+        </div>
     }
 }
 
@@ -103,80 +430,920 @@ struct WeatherWidgetProps {
 #[function_component(WeatherWidget)]
 fn weather_widget(props: &WeatherWidgetProps) -> Html {
     let WeatherWidgetProps { definition } = props;
-    // get the most recent run here,
-    // TODO: create a nicer looking UI etc
+    let config = &definition.config;
 
     let state = use_state(|| None);
+    let unit = use_state(|| config.unit);
 
-    // Request `/api/widgets` once
     {
         let state = state.clone();
         let id = definition.id.clone();
-        use_effect(move || {
-            if state.is_none() {
-                spawn_local(async move {
-                    let resp = Request::get(&format!("/api/widget/{}/latest", id))
-                        .send()
-                        .await
-                        .unwrap();
-                    let result = {
-                        if !resp.ok() {
-                            Err(format!(
-                                "Error fetching data {} ({})",
-                                resp.status(),
-                                resp.status_text()
-                            ))
-                        } else {
-                            // successful, get the text and try to parse it into the list of widgets
-                            let result =
-                                resp.text()
-                                    .await
-                                    .map_err(|err| err.to_string())
-                                    .and_then(|text| {
-                                        serde_json::from_str::<BackendRun>(&text)
-                                            .map_err(|err| format!("{} content: {}", err, text))
-                                    });
-
-                            result.and_then(|run| {
-                                run.result
-                                    .map_err(|err| format!("Backend Error: {:?}", err))
-                                    .map(|r| {
-                                        r.map(|text| {
-                                            serde_json::from_str::<weather::Output>(&text).map_err(
-                                                |err| format!("{:?} content: {}", err, text),
-                                            )
-                                        })
-                                    })
-                            })
-                        }
-                    };
-                    state.set(Some(result));
+        use_effect_with((), move |_| {
+            spawn_local(async move {
+                let result = fetch_latest_run(&id).await.and_then(|run| {
+                    let ended = run.ended;
+                    parse_output::<weather::Output>(run).map(|output| output.map(|o| (o, ended)))
                 });
+                state.set(Some(result));
+            });
+        });
+    }
+
+    let toggle_unit = {
+        let unit = unit.clone();
+        Callback::from(move |_| {
+            unit.set(match *unit {
+                TemperatureUnit::Celsius => TemperatureUnit::Fahrenheit,
+                TemperatureUnit::Fahrenheit => TemperatureUnit::Celsius,
+            })
+        })
+    };
+
+    let location = config
+        .name
+        .clone()
+        .unwrap_or_else(|| format!("{:.2}, {:.2}", config.location[0], config.location[1]));
+
+    match state.as_ref() {
+        None => html! {
+            <div class="weather loading">
+                <div class="skeleton icon" />
+                <div class="skeleton temperature" />
+                <div class="skeleton line" />
+            </div>
+        },
+        Some(Ok(None)) => html! {
+            <div class="weather">
+                <div class="location">{ location }</div>
+                <div class="empty">{ "No weather data yet" }</div>
+            </div>
+        },
+        Some(Ok(Some((data, ended)))) => html! {
+            <div class="weather">
+                <div class="icon" title={format!("{:?}", data.condition)}>
+                    { condition_icon(data.condition) }
+                </div>
+                <button class="temperature" onclick={toggle_unit} title="Toggle unit">
+                    { format!("{:.1} {}", unit.convert(data.temperature), unit.symbol()) }
+                </button>
+                <div class="location">{ location }</div>
+                <div class="updated">
+                    { format!("Updated {}", ended.with_timezone(&Local).format("%H:%M")) }
+                </div>
+            </div>
+        },
+        Some(Err(err)) => html! {
+            <div class="weather error">
+                <div class="location">{ location }</div>
+                <div class="message">{ err }</div>
+            </div>
+        },
+    }
+}
+
+fn condition_icon(condition: Condition) -> &'static str {
+    match condition {
+        Condition::Unknown => "❔",
+        Condition::Clear => "☀️",
+        Condition::PartlyCloudy => "⛅",
+        Condition::Cloudy => "☁️",
+        Condition::Rain => "🌧️",
+        Condition::Snow => "❄️",
+    }
+}
+
+/// Fetches the most recent run of a widget
+async fn fetch_latest_run(id: &WidgetId) -> Result<BackendRun, String> {
+    fetch_json(&format!("/api/widget/{}/latest", id)).await
+}
+
+/// Parses the output of a run, failed runs are turned into an error message
+fn parse_output<T: DeserializeOwned>(run: BackendRun) -> Result<Option<T>, String> {
+    // a failed scheduled run might be tried again
+    let retrying = run
+        .attempt
+        .filter(|a| a.next_retry.is_some())
+        .map(|a| format!(", retrying ({}/{})", a.number + 1, a.max_attempts))
+        .unwrap_or_default();
+
+    run.result
+        .map_err(|err| format!("Backend Error: {err}{retrying}"))?
+        .map(|text| {
+            serde_json::from_str::<T>(&text).map_err(|err| format!("{:?} content: {}", err, text))
+        })
+        .transpose()
+}
+
+/// Fetches the most recent run of a widget and parses its output
+pub async fn fetch_latest_output<T: DeserializeOwned>(id: &WidgetId) -> Result<Option<T>, String> {
+    parse_output(fetch_latest_run(id).await?)
+}
+
+#[derive(Clone, PartialEq, Properties)]
+struct ClothingWidgetProps {
+    definition: common::clothing::Widget,
+}
+
+/// Shows what to wear
+#[function_component(ClothingWidget)]
+fn clothing_widget(props: &ClothingWidgetProps) -> Html {
+    let ClothingWidgetProps { definition } = props;
+    let state = use_state(|| None);
+
+    {
+        let state = state.clone();
+        let id = definition.id.clone();
+        use_effect_with((), move |_| {
+            spawn_local(async move {
+                let result = fetch_latest_output::<clothing::Output>(&id).await;
+                state.set(Some(result));
+            });
+        });
+    }
+
+    match state.as_ref() {
+        None => html! {
+            <div>{"No server response"}</div>
+        },
+        Some(Ok(None)) => html! {
+            <div class="widget">{"Waiting for the weather"}</div>
+        },
+        Some(Ok(Some(data))) => html! {
+            <div class="widget">
+                <strong>{ &data.advice }</strong>
+                <div>{ format!("{:.1} °C", data.temperature) }</div>
+            </div>
+        },
+        Some(Err(err)) => html! {
+            <div>{"Error requesting data from server: "}{err}</div>
+        },
+    }
+}
+
+#[derive(Clone, PartialEq, Properties)]
+struct GaugeProps {
+    label: AttrValue,
+    /// 0 to 100
+    percent: f64,
+    /// Shown below the label, e.g. the absolute values
+    #[prop_or_default]
+    detail: AttrValue,
+}
+
+/// A ring that fills up with the percentage, colored by how full it is
+#[function_component(Gauge)]
+fn gauge(props: &GaugeProps) -> Html {
+    const RADIUS: f64 = 40.0;
+    let circumference = 2.0 * std::f64::consts::PI * RADIUS;
+    let percent = props.percent.clamp(0.0, 100.0);
+    let filled = circumference * percent / 100.0;
+
+    let class = match percent {
+        p if p >= 90.0 => "critical",
+        p if p >= 75.0 => "warning",
+        _ => "ok",
+    };
+
+    html! {
+        <div class={classes!("gauge", class)}>
+            <svg viewBox="0 0 100 100">
+                <circle class="track" cx="50" cy="50" r={RADIUS.to_string()} />
+                <circle class="value" cx="50" cy="50" r={RADIUS.to_string()}
+                    stroke-dasharray={format!("{filled} {circumference}")}
+                    transform="rotate(-90 50 50)" />
+                <text x="50" y="55" text-anchor="middle">{ format!("{percent:.0}%") }</text>
+            </svg>
+            <div class="label">{ &props.label }</div>
+            if !props.detail.is_empty() {
+                <div class="detail">{ &props.detail }</div>
             }
+        </div>
+    }
+}
 
-            || {}
+fn usage_detail(usage: &system::Usage) -> String {
+    format!(
+        "{} / {}",
+        generic::format_bytes(usage.used_bytes as f64),
+        generic::format_bytes(usage.total_bytes as f64)
+    )
+}
+
+#[derive(Clone, PartialEq, Properties)]
+struct SystemWidgetProps {
+    definition: common::system::Widget,
+}
+
+/// Gauges for the resource usage of the host
+#[function_component(SystemWidget)]
+fn system_widget(props: &SystemWidgetProps) -> Html {
+    let SystemWidgetProps { definition } = props;
+    let state = use_state(|| None);
+
+    {
+        let state = state.clone();
+        let id = definition.id.clone();
+        use_effect_with((), move |_| {
+            spawn_local(async move {
+                let result = fetch_latest_output::<system::Output>(&id).await;
+                state.set(Some(result));
+            });
         });
     }
 
     match state.as_ref() {
-        None => {
+        None => html! {
+            <div>{"No server response"}</div>
+        },
+        Some(Ok(None)) => html! {
+            <div class="widget">{"No data yet"}</div>
+        },
+        Some(Ok(Some(data))) => html! {
+            <div class="widget system">
+                <div class="gauges">
+                    <Gauge label="CPU" percent={data.cpu_percent}
+                        detail={format!("load {:.2} {:.2} {:.2}", data.load[0], data.load[1], data.load[2])} />
+                    <Gauge label="Memory" percent={data.memory.percent()} detail={usage_detail(&data.memory)} />
+                    if data.swap.total_bytes > 0 {
+                        <Gauge label="Swap" percent={data.swap.percent()} detail={usage_detail(&data.swap)} />
+                    }
+                    {
+                        data.disks.iter().map(|disk| html! {
+                            <Gauge label={disk.mountpoint.clone()} percent={disk.usage.percent()}
+                                detail={usage_detail(&disk.usage)} />
+                        }).collect::<Html>()
+                    }
+                </div>
+            </div>
+        },
+        Some(Err(err)) => html! {
+            <div>{"Error requesting data from server: "}{err}</div>
+        },
+    }
+}
+
+#[derive(Clone, PartialEq, Properties)]
+struct UptimeWidgetProps {
+    definition: common::uptime::Widget,
+}
+
+/// Status of the checked URLs with their uptime over the last day
+#[function_component(UptimeWidget)]
+fn uptime_widget(props: &UptimeWidgetProps) -> Html {
+    let UptimeWidgetProps { definition } = props;
+    let state = use_state(|| None);
+    let uptime = use_state(Vec::<uptime::TargetUptime>::new);
+
+    {
+        let state = state.clone();
+        let uptime = uptime.clone();
+        let id = definition.id.clone();
+        use_effect_with((), move |_| {
+            spawn_local(async move {
+                let result = fetch_latest_output::<uptime::Output>(&id).await;
+                state.set(Some(result));
+
+                match fetch_json::<Vec<uptime::TargetUptime>>(&format!("/api/widget/{id}/uptime"))
+                    .await
+                {
+                    Ok(list) => uptime.set(list),
+                    Err(err) => log::warn!("{err}"),
+                }
+            });
+        });
+    }
+
+    match state.as_ref() {
+        None => html! {
+            <div>{"No server response"}</div>
+        },
+        Some(Ok(None)) => html! {
+            <div class="widget">{"Not checked yet"}</div>
+        },
+        Some(Ok(Some(data))) => html! {
+            <div class="widget uptime">
+                <table>
+                {
+                    data.targets.iter().map(|target| {
+                        let percent = uptime
+                            .iter()
+                            .find(|u| u.url == target.url)
+                            .and_then(|u| u.percent)
+                            .map(|p| format!("{p:.2}%"))
+                            .unwrap_or_else(|| "-".into());
+                        let tls = match target.tls_valid {
+                            Some(true) => "🔒",
+                            Some(false) => "⚠️",
+                            None => "",
+                        };
+
+                        html! {
+                            <tr class={classes!(if target.up { "up" } else { "down" })}>
+                                <td class="dot">{ "●" }</td>
+                                <td class="url" title={target.error.clone().unwrap_or_default()}>
+                                    { tls }{ " " }{ &target.url }
+                                </td>
+                                <td>{ target.status.map(|s| s.to_string()).unwrap_or_else(|| "-".into()) }</td>
+                                <td>{ format!("{} ms", target.latency_ms) }</td>
+                                <td>{ percent }</td>
+                            </tr>
+                        }
+                    }).collect::<Html>()
+                }
+                </table>
+            </div>
+        },
+        Some(Err(err)) => html! {
+            <div>{"Error requesting data from server: "}{err}</div>
+        },
+    }
+}
+
+#[derive(Clone, PartialEq, Properties)]
+struct CertificateWidgetProps {
+    definition: certificate::Widget,
+}
+
+/// Days until the TLS certificates of the checked servers expire
+#[function_component(CertificateWidget)]
+fn certificate_widget(props: &CertificateWidgetProps) -> Html {
+    let CertificateWidgetProps { definition } = props;
+    let state = use_state(|| None);
+
+    {
+        let state = state.clone();
+        let id = definition.id.clone();
+        use_effect_with((), move |_| {
+            spawn_local(async move {
+                let result = fetch_latest_output::<certificate::Output>(&id).await;
+                state.set(Some(result));
+            });
+        });
+    }
+
+    match state.as_ref() {
+        None => html! {
+            <div>{"No server response"}</div>
+        },
+        Some(Ok(None)) => html! {
+            <div class="widget">{"Not checked yet"}</div>
+        },
+        Some(Ok(Some(data))) => html! {
+            <div class="widget certificate">
+                <table>
+                {
+                    data.certificates.iter().map(|status| {
+                        let (days, title) = match (&status.certificate, &status.error) {
+                            (Some(cert), _) => (
+                                format!("{} days", cert.days_remaining),
+                                format!(
+                                    "Valid until {}\nIssued by {}\nNames: {}{}",
+                                    cert.not_after.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+                                    cert.issuer,
+                                    cert.sans.join(", "),
+                                    cert.trust_error
+                                        .as_ref()
+                                        .map(|e| format!("\nNot trusted: {e}"))
+                                        .unwrap_or_default(),
+                                ),
+                            ),
+                            (None, error) => ("-".into(), error.clone().unwrap_or_default()),
+                        };
+                        let trusted = match &status.certificate {
+                            Some(cert) if !cert.trusted => "⚠️ ",
+                            _ => "",
+                        };
+
+                        html! {
+                            <tr class={level_class(status.level)} {title}>
+                                <td class="dot">{ "●" }</td>
+                                <td class="host">{ trusted }{ format!("{}:{}", status.host, status.port) }</td>
+                                <td class="days">{ days }</td>
+                            </tr>
+                        }
+                    }).collect::<Html>()
+                }
+                </table>
+            </div>
+        },
+        Some(Err(err)) => html! {
+            <div>{"Error requesting data from server: "}{err}</div>
+        },
+    }
+}
+
+#[derive(Clone, PartialEq, Properties)]
+struct FeedWidgetProps {
+    definition: feed::Widget,
+}
+
+/// Rotates through the latest headlines of the feeds, new ones are highlighted
+#[function_component(FeedWidget)]
+fn feed_widget(props: &FeedWidgetProps) -> Html {
+    let FeedWidgetProps { definition } = props;
+    let state = use_state(|| None);
+    let index = use_state(|| 0usize);
+
+    {
+        let state = state.clone();
+        let id = definition.id.clone();
+        use_effect_with((), move |_| {
+            spawn_local(async move {
+                let result = fetch_latest_output::<feed::Output>(&id).await;
+                state.set(Some(result));
+            });
+        });
+    }
+
+    {
+        let index = index.clone();
+        use_effect_with((), move |_| {
+            let interval = Interval::new(TICKER_ROTATE_MS, move || index.set(*index + 1));
+            move || drop(interval)
+        });
+    }
+
+    match state.as_ref() {
+        None => html! {
+            <div>{"No server response"}</div>
+        },
+        Some(Ok(None)) => html! {
+            <div class="widget">{"No headlines yet"}</div>
+        },
+        Some(Ok(Some(data))) if data.items.is_empty() => html! {
+            <div class="widget feed">{"The feeds are empty"}</div>
+        },
+        Some(Ok(Some(data))) => {
+            let position = *index % data.items.len();
+            let item = &data.items[position];
+            let age = item
+                .date
+                .map(|d| format_age((chrono::Utc::now() - d).num_seconds()))
+                .unwrap_or_default();
+
             html! {
-                <div>{"No server response"}</div>
+                <div class={classes!("widget", "feed", item.new.then_some("new"))}>
+                    <div class="source">
+                        { &item.feed }
+                        <span class="position">{ format!("{}/{}", position + 1, data.items.len()) }</span>
+                    </div>
+                    <div class="headline" key={position}>
+                        if item.new {
+                            <span class="badge">{ "NEW" }</span>
+                        }
+                        {
+                            match &item.link {
+                                Some(link) => html! { <a href={link.clone()} target="_blank">{ &item.title }</a> },
+                                None => html! { <span>{ &item.title }</span> },
+                            }
+                        }
+                    </div>
+                    <div class="age">{ age }</div>
+                </div>
             }
         }
-        Some(Ok(data)) => {
-            html! {
-                <div class="widgets">
+        Some(Err(err)) => html! {
+            <div>{"Error requesting data from server: "}{err}</div>
+        },
+    }
+}
+
+/// Formats a number of seconds as e.g. `2h 5m`, showing the two largest units
+fn format_duration(seconds: f64) -> String {
+    let units = [("d", 86_400), ("h", 3_600), ("m", 60), ("s", 1)];
+    let mut rest = seconds.max(0.0) as u64;
+    let parts: Vec<String> = units
+        .iter()
+        .filter_map(|(unit, size)| {
+            let count = rest / size;
+            rest %= size;
+            (count > 0).then(|| format!("{count}{unit}"))
+        })
+        .take(2)
+        .collect();
+
+    if parts.is_empty() {
+        "0s".into()
+    } else {
+        parts.join(" ")
+    }
+}
+
+/// Formats a scalar value of a fetch widget according to the hints of its field
+fn format_field_value(field: &fetch::Field, value: &serde_json::Value) -> String {
+    use serde_json::Value;
+
+    let decimals = |n: f64, default: Option<usize>| match field.decimals.or(default) {
+        Some(decimals) => format!("{n:.decimals$}"),
+        None => n.to_string(),
+    };
+    let text = match (field.format, value) {
+        (_, Value::Null) => return "-".into(),
+        (Format::Ratio, Value::Number(n)) => {
+            format!("{}%", decimals(n.as_f64().unwrap_or(0.0) * 100.0, Some(1)))
+        }
+        (Format::Bytes, Value::Number(n)) => generic::format_bytes(n.as_f64().unwrap_or(0.0)),
+        (Format::Duration, Value::Number(n)) => format_duration(n.as_f64().unwrap_or(0.0)),
+        (Format::Timestamp, Value::Number(n)) => {
+            chrono::DateTime::from_timestamp(n.as_i64().unwrap_or(0), 0)
+                .map(|t| t.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_else(|| n.to_string())
+        }
+        (Format::Timestamp, Value::String(s)) => chrono::DateTime::parse_from_rfc3339(s)
+            .map(|t| t.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_else(|_| s.clone()),
+        (Format::Status, Value::Bool(b)) => return if *b { "✓" } else { "✗" }.into(),
+        (_, Value::Number(n)) => match n.as_f64() {
+            Some(f) if field.decimals.is_some() => decimals(f, None),
+            _ => n.to_string(),
+        },
+        (_, Value::Bool(b)) => if *b { "yes" } else { "no" }.into(),
+        (_, Value::String(s)) => s.clone(),
+        (_, value) => value.to_string(),
+    };
+
+    match &field.unit {
+        Some(unit) => format!("{text} {unit}"),
+        None => text,
+    }
+}
+
+#[derive(Clone, PartialEq, Properties)]
+struct FetchWidgetProps {
+    definition: fetch::Widget,
+}
+
+/// The values picked out of a JSON response, in the order of the configured fields
+#[function_component(FetchWidget)]
+fn fetch_widget(props: &FetchWidgetProps) -> Html {
+    let FetchWidgetProps { definition } = props;
+    let state = use_state(|| None);
+
+    {
+        let state = state.clone();
+        let id = definition.id.clone();
+        use_effect_with((), move |_| {
+            spawn_local(async move {
+                let result = fetch_latest_output::<fetch::Output>(&id).await;
+                state.set(Some(result));
+            });
+        });
+    }
+
+    match state.as_ref() {
+        None => html! {
+            <div>{"No server response"}</div>
+        },
+        Some(Ok(None)) => html! {
+            <div class="widget">{"Not fetched yet"}</div>
+        },
+        Some(Ok(Some(data))) => html! {
+            <div class="widget fetch">
+                <dl>
+                {
+                    definition.config.fields.iter().map(|field| {
+                        let value = data.get(&field.name).unwrap_or(&serde_json::Value::Null);
+                        let content = match value {
+                            serde_json::Value::Array(items) if items.iter().all(|i| !i.is_object() && !i.is_array()) => {
+                                html! { items.iter().map(|i| format_field_value(field, i)).collect::<Vec<_>>().join(", ") }
+                            }
+                            serde_json::Value::Array(_) | serde_json::Value::Object(_) => render_value(None, value),
+                            _ => html! { format_field_value(field, value) },
+                        };
+                        let class = match (field.format, value) {
+                            (Format::Status, serde_json::Value::Bool(true)) => "up",
+                            (Format::Status, serde_json::Value::Bool(false)) => "down",
+                            _ => "",
+                        };
+
+                        html! {
+                            <>
+                                <dt>{ format_key(&field.name) }</dt>
+                                <dd class={class}>{ content }</dd>
+                            </>
+                        }
+                    }).collect::<Html>()
+                }
+                </dl>
+            </div>
+        },
+        Some(Err(err)) => html! {
+            <div>{"Error requesting data from server: "}{err}</div>
+        },
+    }
+}
+
+#[derive(Clone, PartialEq, Properties)]
+struct LogTailWidgetProps {
+    definition: log_tail::Widget,
+}
+
+/// How many lines matched each pattern since the previous run, and the last matching lines
+#[function_component(LogTailWidget)]
+fn log_tail_widget(props: &LogTailWidgetProps) -> Html {
+    let LogTailWidgetProps { definition } = props;
+    let state = use_state(|| None);
+
+    {
+        let state = state.clone();
+        let id = definition.id.clone();
+        use_effect_with((), move |_| {
+            spawn_local(async move {
+                let result = fetch_latest_output::<log_tail::Output>(&id).await;
+                state.set(Some(result));
+            });
+        });
+    }
+
+    match state.as_ref() {
+        None => html! {
+            <div>{"No server response"}</div>
+        },
+        Some(Ok(None)) => html! {
+            <div class="widget">{"Not read yet"}</div>
+        },
+        Some(Ok(Some(data))) => html! {
+            <div class="widget log-tail">
+                <ul class="counts">
                 {
-                    format!("{:?}", data)
+                    definition.config.patterns.iter().map(|pattern| {
+                        let count = data.counts.get(&pattern.name).copied().unwrap_or(0);
+                        html! {
+                            <li class={classes!((count > 0).then_some("matched"))}>
+                                <span class="count">{ count }</span>
+                                <span class="name">{ &pattern.name }</span>
+                            </li>
+                        }
+                    }).collect::<Html>()
                 }
+                </ul>
+                <pre class="lines">
+                {
+                    data.lines.iter().map(|line| html! {
+                        <div title={line.pattern.clone()}>{ &line.text }</div>
+                    }).collect::<Html>()
+                }
+                </pre>
+                <div class="status">
+                    { format!("{} lines read", data.lines_read) }
+                    if data.rotated {
+                        { ", file was rotated" }
+                    }
                 </div>
+            </div>
+        },
+        Some(Err(err)) => html! {
+            <div>{"Error requesting data from server: "}{err}</div>
+        },
+    }
+}
+
+#[derive(Clone, PartialEq, Properties)]
+struct GitWidgetProps {
+    definition: git::Widget,
+}
+
+/// Branch, latest commit and recent activity of each repository
+#[function_component(GitWidget)]
+fn git_widget(props: &GitWidgetProps) -> Html {
+    let GitWidgetProps { definition } = props;
+    let state = use_state(|| None);
+
+    {
+        let state = state.clone();
+        let id = definition.id.clone();
+        use_effect_with((), move |_| {
+            spawn_local(async move {
+                let result = fetch_latest_output::<git::Output>(&id).await;
+                state.set(Some(result));
+            });
+        });
+    }
+
+    match state.as_ref() {
+        None => html! {
+            <div>{"No server response"}</div>
+        },
+        Some(Ok(None)) => html! {
+            <div class="widget">{"Not checked yet"}</div>
+        },
+        Some(Ok(Some(data))) => html! {
+            <div class="widget git">
+            {
+                data.repositories.iter().map(|repo| {
+                    let branch = repo.branch.clone().unwrap_or_else(|| "detached".into());
+                    let sync = match (repo.ahead, repo.behind) {
+                        (Some(ahead), Some(behind)) => format!("↑{ahead} ↓{behind}"),
+                        _ => String::new(),
+                    };
+                    let sync_title = repo
+                        .upstream
+                        .as_ref()
+                        .map(|u| format!("Compared with {u}"))
+                        .unwrap_or_default();
+
+                    html! {
+                        <div class="repository">
+                            <div class="header">
+                                <span class="name">{ &repo.name }</span>
+                                <span class="branch">{ branch }</span>
+                                <span class="sync" title={sync_title}>{ sync }</span>
+                            </div>
+                            if let Some(commit) = &repo.last_commit {
+                                <div class="commit" title={commit.hash.clone()}>
+                                    <span class="message">{ &commit.message }</span>
+                                    <span class="meta">
+                                        { format!(
+                                            "{}, {}",
+                                            commit.author,
+                                            format_age((chrono::Utc::now() - commit.date).num_seconds())
+                                        ) }
+                                    </span>
+                                </div>
+                            }
+                            <div class="recent">
+                                { format!("{} commits in the last {} h", repo.recent_commits, definition.config.recent_hours) }
+                            </div>
+                            if let Some(error) = &repo.error {
+                                <div class="error">{ error }</div>
+                            }
+                        </div>
+                    }
+                }).collect::<Html>()
             }
-        }
-        Some(Err(err)) => {
+            </div>
+        },
+        Some(Err(err)) => html! {
+            <div>{"Error requesting data from server: "}{err}</div>
+        },
+    }
+}
+
+#[derive(Clone, PartialEq, Properties)]
+struct HeartbeatWidgetProps {
+    definition: heartbeat::Widget,
+}
+
+/// Whether an external job is still pinging, red once a ping is later than the grace period
+#[function_component(HeartbeatWidget)]
+fn heartbeat_widget(props: &HeartbeatWidgetProps) -> Html {
+    let HeartbeatWidgetProps { definition } = props;
+    let state = use_state(|| None);
+
+    {
+        let state = state.clone();
+        use_effect_with(definition.id.clone(), move |id| {
+            let id = id.clone();
+            // pings arrive at any time, so keep checking
+            let refresh = move || {
+                let state = state.clone();
+                let id = id.clone();
+                spawn_local(async move {
+                    let result = async {
+                        let freshness =
+                            fetch_json::<Freshness>(&format!("/api/widget/{id}/freshness")).await?;
+                        let output = match freshness.last_success {
+                            Some(_) => fetch_latest_output::<heartbeat::Output>(&id).await?,
+                            None => None,
+                        };
+                        Ok::<_, String>((freshness, output))
+                    }
+                    .await;
+                    state.set(Some(result));
+                });
+            };
+
+            refresh();
+            let interval = Interval::new(FRESHNESS_POLL_MS, refresh);
+            move || drop(interval)
+        });
+    }
+
+    match state.as_ref() {
+        None => html! {
+            <div>{"No server response"}</div>
+        },
+        Some(Ok((freshness, output))) => {
+            let (class, label) = match (freshness.last_success, freshness.state) {
+                (None, _) => ("down", "No ping yet"),
+                (_, FreshnessState::Fresh) => ("up", "OK"),
+                (_, FreshnessState::Aging) => ("late", "Late"),
+                (_, FreshnessState::Stale | FreshnessState::Failed) => ("down", "Down"),
+            };
+            let period = format!(
+                "expected every {} min (+{} min grace)",
+                definition.config.period_minutes, definition.config.grace_minutes
+            );
+
             html! {
-                <div>{"Error requesting data from server: "}{err}</div>
+                <div class={classes!("widget", "heartbeat", class)}>
+                    <div class="status">{ label }</div>
+                    if let Some(age) = freshness.age_seconds {
+                        <div class="last">{ format!("last ping {}", format_age(age)) }</div>
+                    }
+                    if let Some(message) = output.as_ref().and_then(|o| o.message.as_ref()) {
+                        <div class="message">{ message }</div>
+                    }
+                    <div class="period">{ period }</div>
+                </div>
             }
         }
+        Some(Err(err)) => html! {
+            <div>{"Error requesting data from server: "}{err}</div>
+        },
+    }
+}
+
+/// CSS class used to color things by their severity
+fn level_class(level: Level) -> &'static str {
+    match level {
+        Level::Info => "info",
+        Level::Warning => "warning",
+        Level::Critical => "critical",
+    }
+}
+
+async fn fetch_notifications() -> Result<Vec<Notification>, String> {
+    let resp = Request::get("/api/notifications")
+        .send()
+        .await
+        .map_err(|err| err.to_string())?;
+
+    if !resp.ok() {
+        return Err(format!(
+            "Error fetching notifications {} ({})",
+            resp.status(),
+            resp.status_text()
+        ));
+    }
+
+    resp.json::<Vec<Notification>>()
+        .await
+        .map_err(|err| err.to_string())
+}
+
+/// Shows the unacknowledged notifications as toasts in the corner of the screen
+#[function_component(Toasts)]
+fn toasts() -> Html {
+    let notifications = use_state(Vec::<Notification>::new);
+
+    let refresh = {
+        let notifications = notifications.clone();
+        Callback::from(move |_: ()| {
+            let notifications = notifications.clone();
+            spawn_local(async move {
+                match fetch_notifications().await {
+                    Ok(list) => notifications.set(list),
+                    Err(err) => log::warn!("{err}"),
+                }
+            });
+        })
+    };
+
+    // poll for new notifications for as long as the component is mounted
+    {
+        let refresh = refresh.clone();
+        use_effect_with((), move |_| {
+            refresh.emit(());
+            let interval = Interval::new(NOTIFICATION_POLL_MS, move || refresh.emit(()));
+            move || drop(interval)
+        });
+    }
+
+    let acknowledge = Callback::from(move |id: NotificationId| {
+        let refresh = refresh.clone();
+        spawn_local(async move {
+            let url = format!("/api/notification/{}/acknowledge", id.0);
+            if let Err(err) = Request::post(&url).send().await {
+                log::warn!("could not acknowledge notification: {err}");
+            }
+            refresh.emit(());
+        });
+    });
+
+    html! {
+        <div class="toasts">
+        {
+            notifications.iter().filter(|n| !n.acknowledged).map(|n| {
+                let onclick = {
+                    let acknowledge = acknowledge.clone();
+                    let id = n.id;
+                    Callback::from(move |_| acknowledge.emit(id))
+                };
+
+                html! {
+                    <div class={classes!("toast", level_class(n.level))}>
+                        <strong>{ &n.title }</strong>
+                        if n.occurrences > 1 {
+                            <span class="occurrences">{ format!(" (x{})", n.occurrences) }</span>
+                        }
+                        <div class="widget">{ n.widget.to_string() }</div>
+                        <p>{ &n.body }</p>
+                        <button {onclick}>{ "Dismiss" }</button>
+                    </div>
+                }
+            }).collect::<Html>()
+        }
+        </div>
     }
 }
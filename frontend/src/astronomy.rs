@@ -0,0 +1,166 @@
+//! Sunrise, sunset and the phase of the moon, with a bar showing day, twilight and night
+use chrono::{prelude::*, TimeDelta};
+use common::astronomy::{self, MoonPhase, Polar};
+use wasm_bindgen_futures::spawn_local;
+use yew::prelude::*;
+
+use crate::app::fetch_latest_output;
+
+/// Hours marked on the day/night bar
+const BAR_HOURS: [u32; 3] = [6, 12, 18];
+
+/// Position of a time on the bar of `date` in percent, times on other days are at the ends
+fn position(date: NaiveDate, time: DateTime<FixedOffset>) -> f64 {
+    let minutes = (time.naive_local() - date.and_time(NaiveTime::MIN)).num_minutes();
+    (minutes as f64 / (24.0 * 60.0) * 100.0).clamp(0.0, 100.0)
+}
+
+/// The part of the bar between `start` and `end`. A missing end means the period goes on past
+/// the end of the day, if both are missing it lasts all day or not at all.
+fn span(
+    date: NaiveDate,
+    start: Option<DateTime<FixedOffset>>,
+    end: Option<DateTime<FixedOffset>>,
+    all_day: bool,
+) -> Option<(f64, f64)> {
+    match (start, end) {
+        (None, None) => all_day.then_some((0.0, 100.0)),
+        (start, end) => Some((
+            start.map_or(0.0, |t| position(date, t)),
+            end.map_or(100.0, |t| position(date, t)),
+        )),
+    }
+}
+
+fn day_night_bar(output: &astronomy::Output) -> Html {
+    let date = output.date;
+    let twilight = span(
+        date,
+        output.dawn,
+        output.dusk,
+        output.polar != Some(Polar::Night),
+    );
+    let day = span(
+        date,
+        output.sunrise,
+        output.sunset,
+        output.polar == Some(Polar::Day),
+    );
+    let now = Utc::now().with_timezone(output.solar_noon.offset());
+
+    let segment = |class: &'static str, span: Option<(f64, f64)>| match span {
+        Some((start, end)) => html! {
+            <div class={class} style={format!("left: {start}%; width: {}%", end - start)}></div>
+        },
+        None => html! {},
+    };
+
+    html! {
+        <div class="bar">
+            { segment("twilight", twilight) }
+            { segment("day", day) }
+            {
+                BAR_HOURS.iter().map(|hour| html! {
+                    <div class="hour" style={format!("left: {}%", *hour as f64 / 24.0 * 100.0)}>
+                        { format!("{hour:02}") }
+                    </div>
+                }).collect::<Html>()
+            }
+            if now.date_naive() == date {
+                <div class="now" style={format!("left: {}%", position(date, now.fixed_offset()))}></div>
+            }
+        </div>
+    }
+}
+
+fn format_time(time: Option<DateTime<FixedOffset>>) -> String {
+    time.map(|t| t.format("%H:%M").to_string())
+        .unwrap_or_else(|| "-".into())
+}
+
+fn format_day_length(seconds: i64) -> String {
+    let length = TimeDelta::seconds(seconds);
+    format!("{} h {} min", length.num_hours(), length.num_minutes() % 60)
+}
+
+fn moon_icon(phase: MoonPhase) -> &'static str {
+    match phase {
+        MoonPhase::New => "🌑",
+        MoonPhase::WaxingCrescent => "🌒",
+        MoonPhase::FirstQuarter => "🌓",
+        MoonPhase::WaxingGibbous => "🌔",
+        MoonPhase::Full => "🌕",
+        MoonPhase::WaningGibbous => "🌖",
+        MoonPhase::LastQuarter => "🌗",
+        MoonPhase::WaningCrescent => "🌘",
+    }
+}
+
+#[derive(Clone, PartialEq, Properties)]
+pub struct AstronomyWidgetProps {
+    pub definition: astronomy::Widget,
+}
+
+/// Today's sunrise and sunset in the time zone of the location, and the phase of the moon
+#[function_component(AstronomyWidget)]
+pub fn astronomy_widget(props: &AstronomyWidgetProps) -> Html {
+    let AstronomyWidgetProps { definition } = props;
+    let state = use_state(|| None);
+
+    {
+        let state = state.clone();
+        let id = definition.id.clone();
+        use_effect_with((), move |_| {
+            spawn_local(async move {
+                let result = fetch_latest_output::<astronomy::Output>(&id).await;
+                state.set(Some(result));
+            });
+        });
+    }
+
+    let config = &definition.config;
+    let location = config
+        .name
+        .clone()
+        .unwrap_or_else(|| format!("{:.2}, {:.2}", config.location[0], config.location[1]));
+
+    match state.as_ref() {
+        None => html! {
+            <div>{"No server response"}</div>
+        },
+        Some(Ok(None)) => html! {
+            <div class="widget">{"Not computed yet"}</div>
+        },
+        Some(Ok(Some(data))) => html! {
+            <div class="widget astronomy">
+                <div class="location">{ location }</div>
+                <div class="sun">
+                {
+                    match data.polar {
+                        Some(Polar::Day) => html! { <span>{ "☀️ Midnight sun" }</span> },
+                        Some(Polar::Night) => html! { <span>{ "🌌 Polar night" }</span> },
+                        None => html! {
+                            <>
+                                <span title="Sunrise">{ format!("↑ {}", format_time(data.sunrise)) }</span>
+                                <span title="Sunset">{ format!("↓ {}", format_time(data.sunset)) }</span>
+                            </>
+                        },
+                    }
+                }
+                    <span class="length" title="Day length">{ format_day_length(data.day_length_seconds) }</span>
+                </div>
+                { day_night_bar(data) }
+                <div class="twilight-times">
+                    { format!("Civil twilight {} – {}", format_time(data.dawn), format_time(data.dusk)) }
+                </div>
+                <div class="moon" title={format!("{:.1} days old", data.moon.age_days)}>
+                    <span class="icon">{ moon_icon(data.moon.phase) }</span>
+                    { format!("{}, {:.0}% lit", data.moon.phase.name(), data.moon.illumination * 100.0) }
+                </div>
+            </div>
+        },
+        Some(Err(err)) => html! {
+            <div>{"Error requesting data from server: "}{err}</div>
+        },
+    }
+}
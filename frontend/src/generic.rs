@@ -0,0 +1,207 @@
+//! Renders the output of any widget, used for widgets that do not have a component of their own
+use common::WidgetId;
+use serde_json::{Map, Value};
+use wasm_bindgen_futures::spawn_local;
+use yew::prelude::*;
+
+use crate::app::fetch_latest_output;
+
+/// Guesses the unit of a number from the name of the field it is in
+fn unit_hint(key: &str) -> Option<&'static str> {
+    let key = key.to_lowercase();
+    let hints = [
+        ("percent", "%"),
+        ("ratio", "%"),
+        ("temperature", "°C"),
+        ("celsius", "°C"),
+        ("_ms", "ms"),
+        ("millis", "ms"),
+        ("seconds", "s"),
+        ("_secs", "s"),
+        ("minutes", "min"),
+        ("hours", "h"),
+        ("days", "days"),
+        ("bytes", "B"),
+    ];
+
+    hints
+        .iter()
+        .find(|(suffix, _)| key.ends_with(suffix))
+        .map(|(_, unit)| *unit)
+}
+
+/// Formats a byte count with a binary prefix, e.g. `1.5 GiB`
+pub fn format_bytes(bytes: f64) -> String {
+    let units = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value.abs() >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{value} B")
+    } else {
+        format!("{value:.1} {}", units[unit])
+    }
+}
+
+fn format_number(key: Option<&str>, number: f64) -> String {
+    let hint = key.and_then(unit_hint);
+    let is_ratio = key.is_some_and(|k| k.to_lowercase().ends_with("ratio"));
+
+    // show at most two decimals, but no trailing zeros for whole numbers
+    let format = |n: f64| {
+        if n.fract() == 0.0 {
+            format!("{n}")
+        } else {
+            format!("{n:.2}")
+        }
+    };
+
+    match hint {
+        Some("B") => format_bytes(number),
+        Some("%") if is_ratio => format!("{}%", format(number * 100.0)),
+        Some("%") => format!("{}%", format(number)),
+        Some(unit) => format!("{} {unit}", format(number)),
+        None => format(number),
+    }
+}
+
+/// Turns `snake_case` and `camelCase` keys into something readable
+pub fn format_key(key: &str) -> String {
+    let mut label = String::new();
+    for (i, c) in key.chars().enumerate() {
+        match c {
+            '_' | '-' => label.push(' '),
+            c if c.is_uppercase() && i > 0 => {
+                label.push(' ');
+                label.extend(c.to_lowercase());
+            }
+            c if i == 0 => label.extend(c.to_uppercase()),
+            c => label.push(c),
+        }
+    }
+    label
+}
+
+/// Returns the keys if all items are objects, so that the array can be shown as a table
+fn table_columns(items: &[Value]) -> Option<Vec<String>> {
+    let mut columns: Vec<String> = Vec::new();
+    for item in items {
+        for key in item.as_object()?.keys() {
+            if !columns.contains(key) {
+                columns.push(key.clone());
+            }
+        }
+    }
+    Some(columns)
+}
+
+fn render_object(object: &Map<String, Value>) -> Html {
+    html! {
+        <dl class="object">
+        {
+            object.iter().map(|(key, value)| html! {
+                <>
+                    <dt>{ format_key(key) }</dt>
+                    <dd>{ render_value(Some(key), value) }</dd>
+                </>
+            }).collect::<Html>()
+        }
+        </dl>
+    }
+}
+
+fn render_array(key: Option<&str>, items: &[Value]) -> Html {
+    if items.is_empty() {
+        return html! { <span class="empty">{ "none" }</span> };
+    }
+
+    match table_columns(items) {
+        Some(columns) => html! {
+            <table>
+                <thead>
+                    <tr>{ columns.iter().map(|c| html! { <th>{ format_key(c) }</th> }).collect::<Html>() }</tr>
+                </thead>
+                <tbody>
+                {
+                    items.iter().map(|item| html! {
+                        <tr>
+                        {
+                            columns.iter().map(|c| html! {
+                                <td>{ item.get(c).map(|v| render_value(Some(c), v)).unwrap_or_default() }</td>
+                            }).collect::<Html>()
+                        }
+                        </tr>
+                    }).collect::<Html>()
+                }
+                </tbody>
+            </table>
+        },
+        None => html! {
+            <ul>
+                { items.iter().map(|item| html! { <li>{ render_value(key, item) }</li> }).collect::<Html>() }
+            </ul>
+        },
+    }
+}
+
+/// Renders a JSON value, `key` is the name of the field it is in (used for unit hints)
+pub fn render_value(key: Option<&str>, value: &Value) -> Html {
+    match value {
+        Value::Null => html! { <span class="empty">{ "-" }</span> },
+        Value::Bool(b) => html! { <span class="bool">{ if *b { "yes" } else { "no" } }</span> },
+        Value::Number(n) => match n.as_f64() {
+            Some(number) => html! { <span class="number">{ format_number(key, number) }</span> },
+            None => html! { <span class="number">{ n.to_string() }</span> },
+        },
+        Value::String(s) => html! { <span class="string">{ s }</span> },
+        Value::Array(items) => render_array(key, items),
+        Value::Object(object) => render_object(object),
+    }
+}
+
+#[derive(Clone, PartialEq, Properties)]
+pub struct GenericWidgetProps {
+    pub id: WidgetId,
+}
+
+/// Shows the output of the most recent run of any widget as a card
+#[function_component(GenericWidget)]
+pub fn generic_widget(props: &GenericWidgetProps) -> Html {
+    let state = use_state(|| None);
+
+    {
+        let state = state.clone();
+        use_effect_with(props.id.clone(), move |id| {
+            let id = id.clone();
+            spawn_local(async move {
+                let result = fetch_latest_output::<Value>(&id).await;
+                state.set(Some(result));
+            });
+        });
+    }
+
+    match state.as_ref() {
+        None => html! {
+            <div>{"No server response"}</div>
+        },
+        Some(Ok(None)) => html! {
+            <div class="widget generic">
+                <div class="title">{ props.id.to_string() }</div>
+                <div class="empty">{ "No output yet" }</div>
+            </div>
+        },
+        Some(Ok(Some(data))) => html! {
+            <div class="widget generic">
+                <div class="title">{ props.id.to_string() }</div>
+                { render_value(None, data) }
+            </div>
+        },
+        Some(Err(err)) => html! {
+            <div>{"Error requesting data from server: "}{err}</div>
+        },
+    }
+}
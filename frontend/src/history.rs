@@ -0,0 +1,195 @@
+//! Detail page of a widget with its run history, used to debug widgets
+use chrono::Local;
+use common::{
+    backend::{BackendRun, RunPage},
+    WidgetId,
+};
+use gloo_net::http::Request;
+use wasm_bindgen_futures::spawn_local;
+use yew::prelude::*;
+use yew_router::prelude::*;
+
+use crate::app::Route;
+
+/// Number of runs shown per page
+const PER_PAGE: usize = 20;
+
+async fn fetch_history(id: &WidgetId, page: usize) -> Result<RunPage, String> {
+    let resp = Request::get(&format!(
+        "/api/widget/{id}/history?page={page}&per_page={PER_PAGE}"
+    ))
+    .send()
+    .await
+    .map_err(|err| err.to_string())?;
+
+    if !resp.ok() {
+        return Err(format!(
+            "Error fetching run history {} ({})",
+            resp.status(),
+            resp.status_text()
+        ));
+    }
+
+    resp.json::<RunPage>().await.map_err(|err| err.to_string())
+}
+
+#[derive(Clone, PartialEq, Properties)]
+pub struct WidgetHistoryProps {
+    pub id: WidgetId,
+}
+
+/// Paginated run history of a widget with a button to run it right away
+#[function_component(WidgetHistory)]
+pub fn widget_history(props: &WidgetHistoryProps) -> Html {
+    let page = use_state(|| 0usize);
+    let history = use_state(|| None);
+    // bumped to fetch the history again after a manual run
+    let reload = use_state(|| 0u32);
+    let running = use_state(|| false);
+
+    {
+        let history = history.clone();
+        use_effect_with((props.id.clone(), *page, *reload), move |(id, page, _)| {
+            let id = id.clone();
+            let page = *page;
+            spawn_local(async move {
+                history.set(Some(fetch_history(&id, page).await));
+            });
+        });
+    }
+
+    let run_now = {
+        let id = props.id.clone();
+        let page = page.clone();
+        let reload = reload.clone();
+        let running = running.clone();
+        Callback::from(move |_| {
+            let id = id.clone();
+            let page = page.clone();
+            let reload = reload.clone();
+            let running = running.clone();
+            running.set(true);
+            spawn_local(async move {
+                match Request::get(&format!("/api/widget/{id}/trigger"))
+                    .send()
+                    .await
+                {
+                    Ok(resp) if !resp.ok() => {
+                        log::warn!("could not run widget {id}: {}", resp.status_text())
+                    }
+                    Ok(_) => {}
+                    Err(err) => log::warn!("could not run widget {id}: {err}"),
+                }
+                running.set(false);
+                // show the new run, which is always first
+                page.set(0);
+                reload.set(*reload + 1);
+            });
+        })
+    };
+
+    let pagination = match history.as_ref() {
+        Some(Ok(history)) => {
+            let pages = history.total.div_ceil(history.per_page).max(1);
+            let previous = {
+                let page = page.clone();
+                Callback::from(move |_| page.set(page.saturating_sub(1)))
+            };
+            let next = {
+                let page = page.clone();
+                Callback::from(move |_| page.set(*page + 1))
+            };
+
+            html! {
+                <div class="pagination">
+                    <button onclick={previous} disabled={history.page == 0}>{ "Newer" }</button>
+                    <span>{ format!("Page {} of {} ({} runs)", history.page + 1, pages, history.total) }</span>
+                    <button onclick={next} disabled={history.page + 1 >= pages}>{ "Older" }</button>
+                </div>
+            }
+        }
+        _ => html! {},
+    };
+
+    html! {
+        <div class="history">
+            <div class="header">
+                <Link<Route> to={Route::Home}>{ "← Dashboard" }</Link<Route>>
+                <h2>{ props.id.to_string() }</h2>
+                <button onclick={run_now} disabled={*running}>
+                    { if *running { "Running..." } else { "Run now" } }
+                </button>
+            </div>
+            {
+                match history.as_ref() {
+                    None => html! { <div>{ "Loading..." }</div> },
+                    Some(Err(err)) => html! {
+                        <div class="error">{ "Error requesting data from server: " }{ err }</div>
+                    },
+                    Some(Ok(history)) if history.runs.is_empty() => html! {
+                        <div>{ "This widget has not been run yet" }</div>
+                    },
+                    Some(Ok(history)) => html! {
+                        <table>
+                            <thead>
+                                <tr>
+                                    <th>{ "Run" }</th>
+                                    <th>{ "Started" }</th>
+                                    <th>{ "Duration" }</th>
+                                    <th>{ "Initiator" }</th>
+                                    <th>{ "Result" }</th>
+                                </tr>
+                            </thead>
+                            { history.runs.iter().map(run_row).collect::<Html>() }
+                        </table>
+                    },
+                }
+            }
+            { pagination }
+        </div>
+    }
+}
+
+fn run_row(run: &BackendRun) -> Html {
+    let duration = run.ended - run.started;
+    let (class, result) = match &run.result {
+        Ok(_) => ("success", "Success".to_string()),
+        Err(err) => ("failure", format!("Failed: {err}")),
+    };
+
+    let output = match &run.result {
+        Ok(Some(output)) => serde_json::from_str::<serde_json::Value>(output)
+            .and_then(|value| serde_json::to_string_pretty(&value))
+            .unwrap_or_else(|_| output.clone()),
+        Ok(None) => "No output".into(),
+        Err(_) => "-".into(),
+    };
+
+    let attempt = run
+        .attempt
+        .map(|a| format!(" (attempt {}/{})", a.number, a.max_attempts))
+        .unwrap_or_default();
+
+    html! {
+        <tbody class={classes!("run", class)}>
+            <tr>
+                <td>{ run.id.0 }</td>
+                <td>{ run.started.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S").to_string() }</td>
+                <td>{ format!("{} ms", duration.num_milliseconds()) }</td>
+                <td>{ format!("{:?}{attempt}", run.initiated) }</td>
+                <td>{ result }</td>
+            </tr>
+            <tr>
+                <td colspan="5">
+                    <details>
+                        <summary>{ "Log and output" }</summary>
+                        <h4>{ "Log" }</h4>
+                        <pre>{ if run.log.is_empty() { "(empty)" } else { &run.log } }</pre>
+                        <h4>{ "Output" }</h4>
+                        <pre>{ output }</pre>
+                    </details>
+                </td>
+            </tr>
+        </tbody>
+    }
+}
@@ -1,4 +1,8 @@
+mod agenda;
 mod app;
+mod astronomy;
+mod generic;
+mod history;
 
 use app::App;
 